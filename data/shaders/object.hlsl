@@ -7,14 +7,21 @@ cbuffer Constants : register(b0)
     row_major float4x4 viewProjMat;
     float3 cameraPos;
     float specularAmount;
-    float4 materialColor;
-    float4 ambientColor;
-    float3 specularColor;
-    float specularPower;
+    float4 ambientLight;
     float4 sunColor;
     float3 sunDir;
 }
 
+cbuffer MaterialConstants : register(b1)
+{
+    float4 diffuseColor;
+    float3 ambientColor;
+    float specularPower;
+    float3 specularColor;
+    float lighting;
+    float3 emissiveColor;
+}
+
 struct VSInput
 {
     float3 pos : POSITION;
@@ -48,18 +55,18 @@ PSInput VSMain(VSInput input)
 
 float4 PSMain(PSInput input) : SV_Target0
 {
-    float4 albedo = input.color;
-    albedo.rgb *= materialColor;
+    float4 albedo = input.color * diffuseColor;
     albedo *= albedo_map.Sample(linear_wrap_sampler, input.uv);
 
     float3 n = normalize(input.normal);
     float3 h = reflect(normalize(input.viewDir), n);
-    float3 diffuse = ambientColor + sunColor.rgb * saturate(dot(n, sunDir));
+    float3 diffuse = ambientLight.rgb * ambientColor + sunColor.rgb * saturate(dot(n, sunDir));
+    diffuse = lerp(1.0f, diffuse, lighting);
     float3 specular = specularAmount * specularColor * pow(saturate(dot(h, sunDir)), specularPower);
 
     float4 color = albedo;
     color.rgb *= diffuse;
-    color.rgb += specular;
+    color.rgb += specular + emissiveColor;
 
     return color;
 }
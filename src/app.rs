@@ -1,5 +1,9 @@
 mod window;
 mod application;
+mod input;
+mod timing;
 
 pub use self::window::*;
-pub use self::application::*;
\ No newline at end of file
+pub use self::application::*;
+pub use self::input::*;
+pub use self::timing::*;
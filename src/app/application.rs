@@ -1,13 +1,28 @@
-use crate::gfx::{Graphics, Display};
-use crate::app::Window;
+// local refs
+use crate::gfx::{BackendType, Graphics, Display};
+use crate::error::Error;
+use crate::app::{Window, WindowBackendType, InputState, FrameTimer, FrameStats, UpdateMode};
 use crate::numerics::*;
 
 ///
 /// Interface to be implemented by the user to extend application functionality
 ///
 pub trait AppInterface {
-    fn update(&mut self, app: &mut Application);
+    ///
+    /// Advances the application by `dt` seconds. Depending on the timer's update mode this is
+    /// called once per frame with the measured frame time, or zero or more times with a fixed step.
+    ///
+    fn update(&mut self, app: &mut Application, dt: f32);
     fn render(&mut self, app: &mut Application);
+
+    ///
+    /// Called when the window's client area changes size. Implementations that render to the
+    /// display must release their render targets, resize `app.display` and recreate them, e.g. with
+    /// `RenderTargetState::resize_with_display`. An error stops the application.
+    ///
+    fn resize(&mut self, _app: &mut Application, _width: u32, _height: u32) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 ///
@@ -17,56 +32,104 @@ pub struct Application {
     pub window: Window,
     pub graphics: Graphics,
     pub display: Display,
+    pub input: InputState,
+    pub timer: FrameTimer,
+    pub frame_stats: FrameStats,
 
     display_size: Vector2F
 }
 
 impl Application {
     ///
-    /// Attempts to create and initialize the application
+    /// Attempts to create and initialize the application with the platform's default window
     ///
-    pub fn create(title: &str, width: u32, height: u32) -> Result<Application, ()> {
+    pub fn create(title: &str, width: u32, height: u32) -> Result<Application, Error> {
+        Self::create_with_window_backend(WindowBackendType::platform_default(), title, width,
+            height)
+    }
 
-        if let Ok(graphics) = Graphics::new() {
-            let window = Window::create(title, width, height);
-            if let Ok(display) = graphics.create_display(window.get_handle()) {
-                return Ok(Application {
-                    window,
-                    graphics,
-                    display,
-                    display_size: Vector2F::new(width as f32, height as f32)
-                });
-            }
+    ///
+    /// Attempts to create and initialize the application with the specified kind of window
+    ///
+    pub fn create_with_window_backend(window_type: WindowBackendType, title: &str, width: u32,
+        height: u32) -> Result<Application, Error> {
+
+        // without a native window there's nothing for a hardware swap chain to present to
+        let graphics = if window_type.is_native() {
+            Graphics::new()?
         }
+        else {
+            Graphics::new_with_backend(BackendType::Software)?
+        };
+        let window = Window::create_with_backend(window_type, title, width, height)?;
+        let (display_width, display_height) = window.get_window_size();
+        let display = graphics.create_display(window.get_handle(), display_width, display_height)?;
 
-        Err(())
+        Ok(Application {
+            window,
+            graphics,
+            display,
+            input: InputState::new(),
+            timer: FrameTimer::new(UpdateMode::default()),
+            frame_stats: FrameStats::default(),
+            display_size: Vector2F::new(display_width as f32, display_height as f32)
+        })
     }
 
     ///
     /// Displays the window and runs the application until the user exits or the application is otherwise told
     ///
-    pub fn run(&mut self, interface: &mut impl AppInterface) -> Result<(), i32> {
+    /// # Returns
+    /// An error if resizing the display or presenting a frame fails, which stops the application
+    ///
+    pub fn run(&mut self, interface: &mut impl AppInterface) -> Result<(), Error> {
         self.window.show(true);
 
         loop {
-            if !self.window.process_messages() {
+            if !self.window.process_messages(&mut self.input) {
                 break;
             }
+            self.window.poll_gamepads(&mut self.input);
+            if let Some((width, height)) = self.window.take_resize() {
+                self.resize(interface, width, height)?;
+            }
 
-            self.update(interface);
+            let steps = self.timer.tick();
+            self.frame_stats.record(steps.elapsed);
+            for _ in 0..steps.count {
+                self.update(interface, steps.dt);
+                // transitions are only cleared once an update has seen them, so presses landing on
+                // a frame without a fixed step aren't lost and aren't repeated by catch-up steps
+                self.input.begin_frame();
+            }
             self.render(interface);
 
-            self.display.present();
+            self.graphics.present(&self.display)?;
         }
 
         Ok(())
     }
 
+    ///
+    /// Propagates a change in the window's size to the interface
+    ///
+    fn resize(&mut self, interface: &mut impl AppInterface, width: u32, height: u32)
+        -> Result<(), Error> {
+
+        let size = Vector2F::new(width as f32, height as f32);
+        if width == 0 || height == 0 || size == self.display_size {
+            return Ok(());
+        }
+
+        self.display_size = size;
+        interface.resize(self, width, height)
+    }
+
     ///
     /// Updates the application for the frame
     ///
-    fn update(&mut self, interface: &mut impl AppInterface) {
-        interface.update(self);
+    fn update(&mut self, interface: &mut impl AppInterface, dt: f32) {
+        interface.update(self, dt);
     }
 
     ///
@@ -78,4 +141,67 @@ impl Application {
 
         interface.render(self);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///
+    /// Interface that closes the window after a number of frames
+    ///
+    struct FrameCounter {
+        frames: u32,
+        updates: u32,
+        time: f32
+    }
+
+    impl AppInterface for FrameCounter {
+        fn update(&mut self, _app: &mut Application, dt: f32) {
+            self.updates += 1;
+            self.time += dt;
+        }
+
+        fn render(&mut self, app: &mut Application) {
+            self.frames += 1;
+            if self.frames == 3 {
+                app.window.close();
+            }
+        }
+    }
+
+    #[test]
+    fn runs_with_null_window() {
+        let mut app = Application::create_with_window_backend(WindowBackendType::Null, "test", 64,
+            32).unwrap();
+        assert_eq!(app.graphics.get_backend_type(), BackendType::Software);
+        assert_eq!((app.display.width, app.display.height), (64, 32));
+
+        let mut counter = FrameCounter { frames: 0, updates: 0, time: 0.0f32 };
+        app.run(&mut counter).unwrap();
+        assert_eq!((counter.frames, counter.updates), (3, 3));
+        assert!(counter.time >= 0.0f32);
+        assert_eq!(app.frame_stats.get_total_frames(), 3);
+    }
+
+    #[test]
+    fn resize_errors_stop_the_application() {
+        struct FailingResize;
+
+        impl AppInterface for FailingResize {
+            fn update(&mut self, _app: &mut Application, _dt: f32) {}
+            fn render(&mut self, _app: &mut Application) {}
+            fn resize(&mut self, _app: &mut Application, _width: u32, _height: u32)
+                -> Result<(), Error> {
+                Err(Error::device("resize the display"))
+            }
+        }
+
+        let mut app = Application::create_with_window_backend(WindowBackendType::Null, "test", 64,
+            32).unwrap();
+        // an unchanged or empty size doesn't reach the interface
+        assert!(app.resize(&mut FailingResize, 64, 32).is_ok());
+        assert!(app.resize(&mut FailingResize, 0, 32).is_ok());
+        assert!(matches!(app.resize(&mut FailingResize, 128, 64), Err(Error::Device { .. })));
+    }
+}
@@ -0,0 +1,384 @@
+// external refs
+use std::collections::HashSet;
+
+// local refs
+use crate::numerics::*;
+
+///
+/// Keyboard keys the application can respond to
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Escape,
+    Enter,
+    Space,
+    Tab,
+    Backspace,
+    Home,
+    End,
+    Shift,
+    Control,
+    Alt,
+    Left,
+    Right,
+    Up,
+    Down,
+    /// A key without a platform-neutral name, identified by its platform key code
+    Other(u32)
+}
+
+///
+/// Mouse buttons
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2
+}
+
+///
+/// Gamepad buttons
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    LeftThumb,
+    RightThumb,
+    Start,
+    Back,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+///
+/// Snapshot of a single gamepad's controls
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadState {
+    pub connected: bool,
+    pub buttons: u32,
+    /// Stick positions in [-1, 1] with y pointing up
+    pub left_stick: Vector2F,
+    pub right_stick: Vector2F,
+    /// Trigger positions in [0, 1]
+    pub left_trigger: f32,
+    pub right_trigger: f32
+}
+
+impl GamepadState {
+    ///
+    /// Checks whether the specified button is held
+    ///
+    pub fn is_button_down(&self, button: GamepadButton) -> bool {
+        (self.buttons & Self::button_mask(button)) != 0
+    }
+
+    ///
+    /// Marks the specified button as held or released
+    ///
+    pub fn set_button(&mut self, button: GamepadButton, down: bool) {
+        if down {
+            self.buttons |= Self::button_mask(button);
+        }
+        else {
+            self.buttons &= !Self::button_mask(button);
+        }
+    }
+
+    fn button_mask(button: GamepadButton) -> u32 {
+        1 << button as u32
+    }
+}
+
+impl Default for GamepadState {
+    fn default() -> Self {
+        Self {
+            connected: false,
+            buttons: 0,
+            left_stick: zero(),
+            right_stick: zero(),
+            left_trigger: 0.0f32,
+            right_trigger: 0.0f32
+        }
+    }
+}
+
+///
+/// Platform-neutral input events produced by the window or fed synthetically
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    KeyDown(Key),
+    KeyUp(Key),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    /// Cursor position in pixels relative to the top-left of the window's client area
+    MouseMove(Vector2F),
+    /// Wheel movement in notches, positive away from the user
+    MouseWheel(f32),
+    /// Latest state of the gamepad at the specified index
+    Gamepad(usize, GamepadState),
+    /// The window lost focus, so any held keys or buttons will never see their release
+    FocusLost
+}
+
+///
+/// Input state accumulated from events for the current frame
+///
+#[derive(Debug, Clone)]
+pub struct InputState {
+    keys_down: HashSet<Key>,
+    keys_pressed: HashSet<Key>,
+    keys_released: HashSet<Key>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    cursor_pos: Option<Vector2F>,
+    cursor_delta: Vector2F,
+    wheel_delta: f32,
+    gamepads: [GamepadState; InputState::MAX_GAMEPADS]
+}
+
+impl InputState {
+    pub const MAX_GAMEPADS: usize = 4;
+
+    ///
+    /// Creates an input state with nothing held
+    ///
+    pub fn new() -> Self {
+        Self {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            cursor_pos: None,
+            cursor_delta: zero(),
+            wheel_delta: 0.0f32,
+            gamepads: [GamepadState::default(); Self::MAX_GAMEPADS]
+        }
+    }
+
+    ///
+    /// Clears the per-frame transitions and deltas. Call before feeding a frame's events.
+    ///
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.cursor_delta = zero();
+        self.wheel_delta = 0.0f32;
+    }
+
+    ///
+    /// Updates the state from a single event
+    ///
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyDown(key) => {
+                // auto-repeat sends more downs while held, which aren't new presses
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            }
+            InputEvent::KeyUp(key) => {
+                if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            }
+            InputEvent::MouseDown(button) => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            InputEvent::MouseUp(button) => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+            InputEvent::MouseMove(pos) => {
+                if let Some(prev) = self.cursor_pos {
+                    self.cursor_delta += pos - prev;
+                }
+                self.cursor_pos = Some(pos);
+            }
+            InputEvent::MouseWheel(delta) => {
+                self.wheel_delta += delta;
+            }
+            InputEvent::Gamepad(index, state) => {
+                if let Some(pad) = self.gamepads.get_mut(index) {
+                    *pad = state;
+                }
+            }
+            InputEvent::FocusLost => {
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+            }
+        }
+    }
+
+    ///
+    /// Checks whether a key is currently held
+    ///
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    ///
+    /// Checks whether a key went down this frame
+    ///
+    pub fn was_key_pressed(&self, key: Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    ///
+    /// Checks whether a key was let go this frame
+    ///
+    pub fn was_key_released(&self, key: Key) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    ///
+    /// Checks whether a mouse button is currently held
+    ///
+    pub fn is_mouse_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    ///
+    /// Checks whether a mouse button went down this frame
+    ///
+    pub fn was_mouse_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    ///
+    /// Checks whether a mouse button was let go this frame
+    ///
+    pub fn was_mouse_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    ///
+    /// Gets the last known cursor position, if the cursor has been over the window
+    ///
+    pub fn get_cursor_position(&self) -> Option<Vector2F> {
+        self.cursor_pos
+    }
+
+    ///
+    /// Gets how far the cursor moved this frame in pixels
+    ///
+    pub fn get_cursor_delta(&self) -> Vector2F {
+        self.cursor_delta
+    }
+
+    ///
+    /// Gets how many notches the wheel moved this frame
+    ///
+    pub fn get_wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
+
+    ///
+    /// Gets the state of the gamepad at the specified index
+    ///
+    pub fn get_gamepad(&self, index: usize) -> &GamepadState {
+        &self.gamepads[index]
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_transitions_last_one_frame() {
+        let mut input = InputState::new();
+        input.begin_frame();
+        input.handle_event(&InputEvent::KeyDown(Key::W));
+        assert!(input.is_key_down(Key::W) && input.was_key_pressed(Key::W));
+
+        input.begin_frame();
+        input.handle_event(&InputEvent::KeyDown(Key::W)); // auto-repeat
+        assert!(input.is_key_down(Key::W) && !input.was_key_pressed(Key::W));
+
+        input.begin_frame();
+        input.handle_event(&InputEvent::KeyUp(Key::W));
+        assert!(!input.is_key_down(Key::W) && input.was_key_released(Key::W));
+
+        input.begin_frame();
+        assert!(!input.was_key_released(Key::W));
+    }
+
+    #[test]
+    fn cursor_delta_accumulates_within_frame() {
+        let mut input = InputState::new();
+        input.handle_event(&InputEvent::MouseMove(Vector2F::new(10.0f32, 10.0f32)));
+        assert_eq!(input.get_cursor_delta(), Vector2F::zeros());
+
+        input.begin_frame();
+        input.handle_event(&InputEvent::MouseMove(Vector2F::new(12.0f32, 9.0f32)));
+        input.handle_event(&InputEvent::MouseMove(Vector2F::new(15.0f32, 5.0f32)));
+        input.handle_event(&InputEvent::MouseWheel(1.0f32));
+        input.handle_event(&InputEvent::MouseWheel(0.5f32));
+        assert_eq!(input.get_cursor_delta(), Vector2F::new(5.0f32, -5.0f32));
+        assert_eq!(input.get_cursor_position(), Some(Vector2F::new(15.0f32, 5.0f32)));
+        assert_eq!(input.get_wheel_delta(), 1.5f32);
+
+        input.begin_frame();
+        assert_eq!(input.get_cursor_delta(), Vector2F::zeros());
+        assert_eq!(input.get_wheel_delta(), 0.0f32);
+    }
+
+    #[test]
+    fn focus_loss_releases_everything() {
+        let mut input = InputState::new();
+        input.handle_event(&InputEvent::KeyDown(Key::Shift));
+        input.handle_event(&InputEvent::MouseDown(MouseButton::Left));
+
+        input.begin_frame();
+        input.handle_event(&InputEvent::FocusLost);
+        assert!(!input.is_key_down(Key::Shift) && input.was_key_released(Key::Shift));
+        assert!(!input.is_mouse_down(MouseButton::Left));
+        assert!(input.was_mouse_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn gamepad_state_is_replaced() {
+        let mut pad = GamepadState { connected: true, ..Default::default() };
+        pad.set_button(GamepadButton::A, true);
+        pad.set_button(GamepadButton::DPadLeft, true);
+        pad.set_button(GamepadButton::A, false);
+        pad.left_stick = Vector2F::new(0.5f32, -1.0f32);
+
+        let mut input = InputState::new();
+        input.handle_event(&InputEvent::Gamepad(1, pad));
+        input.handle_event(&InputEvent::Gamepad(InputState::MAX_GAMEPADS, pad));
+        assert!(!input.get_gamepad(0).connected);
+        assert!(input.get_gamepad(1).is_button_down(GamepadButton::DPadLeft));
+        assert!(!input.get_gamepad(1).is_button_down(GamepadButton::A));
+        assert_eq!(input.get_gamepad(1).left_stick, Vector2F::new(0.5f32, -1.0f32));
+    }
+}
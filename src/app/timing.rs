@@ -0,0 +1,279 @@
+// external refs
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+// local refs
+use crate::error::Error;
+
+///
+/// How elapsed frame time is turned into application updates
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateMode {
+    /// One update per frame with the measured frame time, clamped to `max_dt` seconds so a stall
+    /// doesn't cause a huge jump
+    Variable { max_dt: f32 },
+    /// Zero or more updates of exactly `step` seconds per frame, catching up on at most
+    /// `max_steps` per frame and dropping any time beyond that
+    Fixed { step: f32, max_steps: u32 }
+}
+
+impl Default for UpdateMode {
+    fn default() -> Self {
+        UpdateMode::Variable { max_dt: 0.25f32 }
+    }
+}
+
+///
+/// Updates to run for a single frame
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSteps {
+    /// Real time since the previous frame in seconds
+    pub elapsed: f32,
+    /// Number of updates to run
+    pub count: u32,
+    /// Seconds each update should advance by
+    pub dt: f32
+}
+
+///
+/// Measures real time between frames and schedules updates according to the update mode
+///
+#[derive(Debug, Clone)]
+pub struct FrameTimer {
+    mode: UpdateMode,
+    last: Option<Instant>,
+    accumulator: f32
+}
+
+impl FrameTimer {
+    ///
+    /// Creates a timer using the specified update mode
+    ///
+    pub fn new(mode: UpdateMode) -> Self {
+        if let UpdateMode::Fixed { step, .. } = mode {
+            assert!(step > 0.0f32, "Fixed update step must be positive");
+        }
+        Self { mode, last: None, accumulator: 0.0f32 }
+    }
+
+    pub fn get_mode(&self) -> UpdateMode { self.mode }
+
+    ///
+    /// Changes the update mode, discarding any partially accumulated fixed step
+    ///
+    pub fn set_mode(&mut self, mode: UpdateMode) {
+        *self = Self { last: self.last, ..Self::new(mode) };
+    }
+
+    ///
+    /// Measures the time since the previous tick and schedules the frame's updates. The first
+    /// tick has no previous frame to measure against and reports no elapsed time.
+    ///
+    pub fn tick(&mut self) -> FrameSteps {
+        let now = Instant::now();
+        let elapsed = self.last.map_or(0.0f32, |last| (now - last).as_secs_f32());
+        self.last = Some(now);
+        self.advance(elapsed)
+    }
+
+    ///
+    /// Schedules the updates for a frame that took the specified number of seconds
+    ///
+    pub fn advance(&mut self, elapsed: f32) -> FrameSteps {
+        match self.mode {
+            UpdateMode::Variable { max_dt } => {
+                FrameSteps { elapsed, count: 1, dt: elapsed.min(max_dt) }
+            }
+            UpdateMode::Fixed { step, max_steps } => {
+                self.accumulator += elapsed;
+                let due = (self.accumulator / step).floor() as u32;
+                let count = due.min(max_steps);
+                self.accumulator = if due > max_steps { 0.0f32 } else { self.accumulator - count as f32 * step };
+                FrameSteps { elapsed, count, dt: step }
+            }
+        }
+    }
+}
+
+///
+/// Summary of the frame times currently held by `FrameStats`, in milliseconds
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTimeSummary {
+    pub count: usize,
+    pub min: f32,
+    pub average: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32
+}
+
+///
+/// Rolling window of recent frame times
+///
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    total_frames: u64
+}
+
+impl FrameStats {
+    ///
+    /// Creates stats that keep the most recent `capacity` frames
+    ///
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Frame stats need room for at least one frame");
+        Self { samples: VecDeque::with_capacity(capacity), capacity, total_frames: 0 }
+    }
+
+    ///
+    /// Records a frame that took the specified number of seconds
+    ///
+    pub fn record(&mut self, seconds: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(seconds * 1000.0f32);
+        self.total_frames += 1;
+    }
+
+    ///
+    /// Gets the recorded frame times in milliseconds, oldest first
+    ///
+    pub fn get_samples(&self) -> impl Iterator<Item = &f32> {
+        self.samples.iter()
+    }
+
+    ///
+    /// Gets the number of frames recorded since creation, including ones no longer held
+    ///
+    pub fn get_total_frames(&self) -> u64 {
+        self.total_frames
+    }
+
+    ///
+    /// Summarizes the held frame times, or `None` if no frames have been recorded
+    ///
+    pub fn summarize(&self) -> Option<FrameTimeSummary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<f32> = self.samples.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // nearest-rank percentile
+        let percentile = |p: f32| {
+            let rank = ((p / 100.0f32) * sorted.len() as f32).ceil() as usize;
+            sorted[rank.max(1) - 1]
+        };
+
+        Some(FrameTimeSummary {
+            count: sorted.len(),
+            min: sorted[0],
+            average: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            max: sorted[sorted.len() - 1],
+            p50: percentile(50.0f32),
+            p95: percentile(95.0f32),
+            p99: percentile(99.0f32)
+        })
+    }
+
+    ///
+    /// Formats the held frame times as CSV, numbering frames from the start of the run
+    ///
+    pub fn to_csv(&self) -> String {
+        let first = self.total_frames - self.samples.len() as u64;
+        let mut csv = String::from("frame,frame_time_ms\n");
+        for (i, ms) in self.samples.iter().enumerate() {
+            csv.push_str(&format!("{},{:.3}\n", first + i as u64, ms));
+        }
+        csv
+    }
+
+    ///
+    /// Writes the held frame times to a CSV file
+    ///
+    pub fn write_csv(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_csv()).map_err(|e| Error::io(path, e))
+    }
+}
+
+impl Default for FrameStats {
+    ///
+    /// Keeps roughly the last ten seconds at 60Hz
+    ///
+    fn default() -> Self {
+        Self::new(600)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variable_mode_clamps_long_frames() {
+        let mut timer = FrameTimer::new(UpdateMode::Variable { max_dt: 0.1f32 });
+        assert_eq!(timer.advance(0.016f32), FrameSteps { elapsed: 0.016f32, count: 1, dt: 0.016f32 });
+        assert_eq!(timer.advance(2.0f32).dt, 0.1f32);
+    }
+
+    #[test]
+    fn fixed_mode_accumulates_partial_steps() {
+        let mut timer = FrameTimer::new(UpdateMode::Fixed { step: 0.01f32, max_steps: 10 });
+        assert_eq!(timer.advance(0.004f32).count, 0);
+        assert_eq!(timer.advance(0.004f32).count, 0);
+        let steps = timer.advance(0.004f32);
+        assert_eq!((steps.count, steps.dt), (1, 0.01f32));
+        assert_eq!(timer.advance(0.025f32).count, 2);
+    }
+
+    #[test]
+    fn fixed_mode_drops_time_beyond_max_steps() {
+        let mut timer = FrameTimer::new(UpdateMode::Fixed { step: 0.01f32, max_steps: 3 });
+        assert_eq!(timer.advance(1.0f32).count, 3);
+        assert_eq!(timer.advance(0.005f32).count, 0);
+    }
+
+    #[test]
+    fn first_tick_reports_no_time() {
+        let mut timer = FrameTimer::new(UpdateMode::default());
+        assert_eq!(timer.tick().elapsed, 0.0f32);
+        assert!(timer.tick().elapsed >= 0.0f32);
+    }
+
+    #[test]
+    fn stats_summarize_rolling_window() {
+        let mut stats = FrameStats::new(100);
+        assert!(stats.summarize().is_none());
+
+        stats.record(1.0f32);
+        for i in 1..=100 {
+            stats.record(i as f32 / 1000.0f32);
+        }
+        assert_eq!(stats.get_total_frames(), 101);
+
+        let summary = stats.summarize().unwrap();
+        let near = |a: f32, b: f32| (a - b).abs() < 1e-3f32;
+        assert_eq!(summary.count, 100);
+        assert!(near(summary.min, 1.0f32) && near(summary.max, 100.0f32));
+        assert!(near(summary.average, 50.5f32));
+        assert!(near(summary.p50, 50.0f32) && near(summary.p95, 95.0f32) && near(summary.p99, 99.0f32));
+    }
+
+    #[test]
+    fn csv_numbers_frames_from_start_of_run() {
+        let mut stats = FrameStats::new(2);
+        stats.record(0.010f32);
+        stats.record(0.020f32);
+        stats.record(0.0305f32);
+        assert_eq!(stats.to_csv(), "frame,frame_time_ms\n1,20.000\n2,30.500\n");
+    }
+}
@@ -1,164 +1,148 @@
-use std::ptr;
-use std::os::windows::ffi::OsStrExt;
-use std::ffi::OsStr;
-use winapi::um::winuser;
-use winapi::shared::windef;
-use winapi::shared::minwindef;
+// local refs
+use crate::app::InputState;
+use crate::error::Error;
+
+#[cfg(windows)]
+mod win32;
+mod null;
+
+#[cfg(windows)]
+pub use self::win32::Win32Window;
+pub use self::null::*;
 
 ///
-/// Generates a wide string from a Rust string slice
+/// The available windowing implementations
 ///
-fn to_wstring(s : &str) -> Vec<u16> {
-    OsStr::new(s).encode_wide().chain(Some(0).into_iter()).collect()
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowBackendType {
+    #[cfg(windows)]
+    Win32,
+    /// Invisible window that never receives input, for platforms without a native implementation
+    /// and for running applications headless
+    Null
+}
+
+impl WindowBackendType {
+    ///
+    /// The windowing implementation used when none is requested explicitly
+    ///
+    #[cfg(windows)]
+    pub fn platform_default() -> Self { WindowBackendType::Win32 }
+    #[cfg(not(windows))]
+    pub fn platform_default() -> Self { WindowBackendType::Null }
+
+    ///
+    /// Checks whether windows of this type are shown on screen
+    ///
+    pub fn is_native(&self) -> bool {
+        *self != WindowBackendType::Null
+    }
 }
 
 ///
-/// Window procedure for the application window
+/// Interface implemented by each windowing implementation. `Window` forwards its API to one of
+/// these.
 ///
-unsafe extern "system" fn window_proc(hwnd: windef::HWND, msg: u32,
-    wparam: minwindef::WPARAM, lparam: minwindef::LPARAM) -> minwindef::LRESULT {
-
-    match msg {
-        winuser::WM_CLOSE => {
-            winuser::DestroyWindow(hwnd);
-            0
-        }
-        winuser::WM_DESTROY => {
-            winuser::PostQuitMessage(0);
-            0
-        }
-        _ => {
-            winuser::DefWindowProcW(hwnd, msg, wparam, lparam)
-        }
-    }
+pub trait WindowBackend {
+    fn get_type(&self) -> WindowBackendType;
+
+    fn show(&self, show: bool);
+    fn close(&self);
+    fn process_messages(&self, input: &mut InputState) -> bool;
+    fn poll_gamepads(&self, input: &mut InputState);
+    fn take_resize(&self) -> Option<(u32, u32)>;
+
+    fn get_handle(&self) -> u64;
+    fn get_window_size(&self) -> (u32, u32);
 }
 
 ///
 /// Data for an application window
 ///
 pub struct Window {
-    handle: windef::HWND
+    backend: Box<dyn WindowBackend>
 }
 
 impl Window {
     ///
-    /// Creates an application window
-    ///
-    pub fn create(title: &str, width: u32, height: u32) -> Window {
-        let class_name = to_wstring("AppWindow");
-        let wnd_title = to_wstring(title);
-        let class = winuser::WNDCLASSW {
-            style: 0,
-            lpfnWndProc: Some(window_proc),
-            cbClsExtra: 0,
-            cbWndExtra: 0,
-            hInstance: 0 as _,
-            hIcon: unsafe { winuser::LoadIconW(0 as _, winuser::IDI_APPLICATION) },
-            hCursor: unsafe { winuser::LoadCursorW(0 as _, winuser::IDI_APPLICATION) },
-            hbrBackground: 16 as _,
-            lpszMenuName: 0 as _,
-            lpszClassName: class_name.as_ptr(),
-        };
+    /// Creates an application window using the default implementation for the platform
+    ///
+    pub fn create(title: &str, width: u32, height: u32) -> Result<Window, Error> {
+        Self::create_with_backend(WindowBackendType::platform_default(), title, width, height)
+    }
 
-        unsafe { winuser::RegisterClassW(&class); }
-
-        let handle = unsafe {
-            winuser::CreateWindowExW(
-                0,
-                class_name.as_ptr(),
-                wnd_title.as_ptr(),
-                winuser::WS_OVERLAPPEDWINDOW,
-                winuser::CW_USEDEFAULT,
-                winuser::CW_USEDEFAULT,
-                width as i32,
-                height as i32,
-                0 as _,
-                0 as _,
-                0 as _,
-                0 as _)
+    ///
+    /// Creates an application window using the specified implementation
+    ///
+    pub fn create_with_backend(backend_type: WindowBackendType, title: &str, width: u32,
+        height: u32) -> Result<Window, Error> {
+
+        let backend: Box<dyn WindowBackend> = match backend_type {
+            #[cfg(windows)]
+            WindowBackendType::Win32 => Box::new(Win32Window::create(title, width, height)?),
+            WindowBackendType::Null => Box::new(NullWindow::create(title, width, height))
         };
 
-        Window {
-            handle
-        }
+        Ok(Window { backend })
+    }
+
+    ///
+    /// Gets the type of the implementation backing the window
+    ///
+    pub fn get_backend_type(&self) -> WindowBackendType {
+        self.backend.get_type()
     }
 
     ///
     /// Shows or hides the window
     ///
     pub fn show(&self, show: bool) {
-        let cmd = if show { winuser::SW_SHOW } else { winuser::SW_HIDE };
-        unsafe { winuser::ShowWindow(self.handle, cmd); }
+        self.backend.show(show);
+    }
+
+    ///
+    /// Asks the window to close. `process_messages` reports `false` once it has.
+    ///
+    pub fn close(&self) {
+        self.backend.close();
     }
 
     ///
-    /// Processes window messages until the message queue is empty
+    /// Processes window messages until the message queue is empty, feeding any input they carry
+    /// into the input state
     ///
     /// # Returns
     /// `false` if the application should exit, `true` otherwise
     ///
-    pub fn process_messages(&self) -> bool {
-        let mut msg = winuser::MSG {
-            hwnd: ptr::null_mut(),
-            message: 0,
-            lParam: 0,
-            wParam: 0,
-            pt: windef::POINT { x: 0, y: 0 },
-            time: 0
-        };
-
-        unsafe {
-            if winuser::PeekMessageW(&mut msg, ptr::null_mut(), 0, 0, winuser::PM_REMOVE) != 0 {
-                winuser::TranslateMessage(&mut msg);
-                winuser::DispatchMessageW(&mut msg);
-
-                if msg.message == winuser::WM_QUIT {
-                    return false;
-                }
-            }
-        }
-
-        true
+    pub fn process_messages(&self, input: &mut InputState) -> bool {
+        self.backend.process_messages(input)
     }
 
     ///
-    /// Destroys the window
+    /// Reads the state of every connected gamepad into the input state
     ///
-    pub fn destroy(&mut self) {
-        if self.handle != ptr::null_mut() {
-            unsafe { winuser::DestroyWindow(self.handle); }
-            self.handle = ptr::null_mut();
-        }
+    pub fn poll_gamepads(&self, input: &mut InputState) {
+        self.backend.poll_gamepads(input);
     }
 
     ///
-    /// Accesses the internal handle to the window
+    /// Takes the client area size the window was last resized to, if it changed since the last call
     ///
-    pub fn get_handle(&self) -> u64 {
-        self.handle as u64
+    pub fn take_resize(&self) -> Option<(u32, u32)> {
+        self.backend.take_resize()
     }
 
     ///
-    /// Gets the size of the window
+    /// Accesses the native handle to the window, or 0 if there isn't one
     ///
-    pub fn get_window_size(&self) -> (u32, u32) {
-        let mut r = windef::RECT {
-            left: 0,
-            top: 0,
-            right: 0,
-            bottom: 0
-        };
-        unsafe { winuser::GetClientRect(self.handle, &mut r); }
-
-        (r.right as _, r.bottom as _)
+    pub fn get_handle(&self) -> u64 {
+        self.backend.get_handle()
     }
-}
 
-impl Drop for Window {
     ///
-    /// Cleans up and properly destroys the window when dropped
+    /// Gets the size of the window's client area
     ///
-    fn drop(&mut self) {
-        self.destroy();
+    pub fn get_window_size(&self) -> (u32, u32) {
+        self.backend.get_window_size()
     }
-}
\ No newline at end of file
+}
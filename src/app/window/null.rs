@@ -0,0 +1,83 @@
+// external refs
+use std::cell::Cell;
+
+// local refs
+use crate::app::*;
+
+///
+/// Window that exists only in memory. It has no native handle, never receives input and stays
+/// open until `close` is called.
+///
+pub struct NullWindow {
+    width: u32,
+    height: u32,
+    closed: Cell<bool>
+}
+
+impl NullWindow {
+    ///
+    /// Creates a window with the specified client area size. The title is unused.
+    ///
+    pub fn create(_title: &str, width: u32, height: u32) -> Self {
+        Self { width, height, closed: Cell::new(false) }
+    }
+}
+
+impl WindowBackend for NullWindow {
+    fn get_type(&self) -> WindowBackendType {
+        WindowBackendType::Null
+    }
+
+    fn show(&self, _show: bool) {}
+
+    fn close(&self) {
+        self.closed.set(true);
+    }
+
+    fn process_messages(&self, _input: &mut InputState) -> bool {
+        !self.closed.get()
+    }
+
+    ///
+    /// Reports every gamepad as disconnected
+    ///
+    fn poll_gamepads(&self, input: &mut InputState) {
+        for index in 0..InputState::MAX_GAMEPADS {
+            input.handle_event(&InputEvent::Gamepad(index, GamepadState::default()));
+        }
+    }
+
+    fn take_resize(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn get_handle(&self) -> u64 {
+        0
+    }
+
+    fn get_window_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_until_closed() {
+        let window = Window::create_with_backend(WindowBackendType::Null, "test", 320, 200)
+            .unwrap();
+        let mut input = InputState::new();
+        assert_eq!(window.get_backend_type(), WindowBackendType::Null);
+        assert_eq!(window.get_window_size(), (320, 200));
+        assert_eq!(window.get_handle(), 0);
+
+        window.show(true);
+        assert!(window.process_messages(&mut input));
+        assert!(window.take_resize().is_none());
+
+        window.close();
+        assert!(!window.process_messages(&mut input));
+    }
+}
@@ -0,0 +1,394 @@
+// external refs
+use std::ptr;
+use std::mem;
+use std::cell::{Cell, RefCell};
+use std::os::windows::ffi::OsStrExt;
+use std::ffi::OsStr;
+use winapi::um::{errhandlingapi, winuser, xinput};
+use winapi::shared::{windef, minwindef, winerror};
+
+// local refs
+use crate::app::*;
+use crate::error::Error;
+use crate::numerics::*;
+
+thread_local! {
+    ///
+    /// Input events translated by the window procedure, waiting to be collected by
+    /// `Win32Window::process_messages`
+    ///
+    static PENDING_EVENTS: RefCell<Vec<InputEvent>> = RefCell::new(Vec::new());
+
+    ///
+    /// Latest client area size reported by the window procedure, waiting to be collected by
+    /// `Win32Window::take_resize`
+    ///
+    static PENDING_RESIZE: Cell<Option<(u32, u32)>> = Cell::new(None);
+}
+
+///
+/// Generates a wide string from a Rust string slice
+///
+fn to_wstring(s : &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(Some(0).into_iter()).collect()
+}
+
+///
+/// Queues an input event for the next call to `Win32Window::process_messages`
+///
+fn push_event(event: InputEvent) {
+    PENDING_EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+///
+/// Maps a Win32 virtual key code to a platform-neutral key
+///
+fn translate_key(vk: i32) -> Key {
+    const LETTERS: [Key; 26] = [Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H,
+        Key::I, Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S,
+        Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z];
+    const DIGITS: [Key; 10] = [Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5,
+        Key::Num6, Key::Num7, Key::Num8, Key::Num9];
+    const FUNCTION_KEYS: [Key; 12] = [Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+        Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12];
+
+    match vk {
+        0x41..=0x5A => LETTERS[(vk - 0x41) as usize],
+        0x30..=0x39 => DIGITS[(vk - 0x30) as usize],
+        winuser::VK_F1..=winuser::VK_F12 => FUNCTION_KEYS[(vk - winuser::VK_F1) as usize],
+        winuser::VK_ESCAPE => Key::Escape,
+        winuser::VK_RETURN => Key::Enter,
+        winuser::VK_SPACE => Key::Space,
+        winuser::VK_TAB => Key::Tab,
+        winuser::VK_BACK => Key::Backspace,
+        winuser::VK_HOME => Key::Home,
+        winuser::VK_END => Key::End,
+        winuser::VK_SHIFT => Key::Shift,
+        winuser::VK_CONTROL => Key::Control,
+        winuser::VK_MENU => Key::Alt,
+        winuser::VK_LEFT => Key::Left,
+        winuser::VK_RIGHT => Key::Right,
+        winuser::VK_UP => Key::Up,
+        winuser::VK_DOWN => Key::Down,
+        _ => Key::Other(vk as u32)
+    }
+}
+
+///
+/// Gets the cursor position packed into the LPARAM of a mouse message
+///
+fn cursor_from_lparam(lparam: minwindef::LPARAM) -> Vector2F {
+    let x = (lparam & 0xFFFF) as i16;
+    let y = ((lparam >> 16) & 0xFFFF) as i16;
+    Vector2F::new(x as f32, y as f32)
+}
+
+///
+/// Window procedure for the application window
+///
+unsafe extern "system" fn window_proc(hwnd: windef::HWND, msg: u32,
+    wparam: minwindef::WPARAM, lparam: minwindef::LPARAM) -> minwindef::LRESULT {
+
+    const ANY_BUTTON: usize = winuser::MK_LBUTTON | winuser::MK_RBUTTON | winuser::MK_MBUTTON
+        | winuser::MK_XBUTTON1 | winuser::MK_XBUTTON2;
+
+    let mouse_button = || match msg {
+        winuser::WM_LBUTTONDOWN | winuser::WM_LBUTTONUP => MouseButton::Left,
+        winuser::WM_RBUTTONDOWN | winuser::WM_RBUTTONUP => MouseButton::Right,
+        winuser::WM_MBUTTONDOWN | winuser::WM_MBUTTONUP => MouseButton::Middle,
+        _ if (wparam >> 16) & 0xFFFF == winuser::XBUTTON1 as usize => MouseButton::X1,
+        _ => MouseButton::X2
+    };
+
+    match msg {
+        winuser::WM_CLOSE => {
+            winuser::DestroyWindow(hwnd);
+            0
+        }
+        winuser::WM_DESTROY => {
+            winuser::PostQuitMessage(0);
+            0
+        }
+        winuser::WM_KEYDOWN | winuser::WM_SYSKEYDOWN => {
+            push_event(InputEvent::KeyDown(translate_key(wparam as i32)));
+            // let system keys through so alt+f4 and friends keep working
+            if msg == winuser::WM_SYSKEYDOWN { winuser::DefWindowProcW(hwnd, msg, wparam, lparam) } else { 0 }
+        }
+        winuser::WM_KEYUP | winuser::WM_SYSKEYUP => {
+            push_event(InputEvent::KeyUp(translate_key(wparam as i32)));
+            if msg == winuser::WM_SYSKEYUP { winuser::DefWindowProcW(hwnd, msg, wparam, lparam) } else { 0 }
+        }
+        winuser::WM_LBUTTONDOWN | winuser::WM_RBUTTONDOWN | winuser::WM_MBUTTONDOWN
+            | winuser::WM_XBUTTONDOWN => {
+            // keep receiving mouse messages while dragging outside of the window
+            winuser::SetCapture(hwnd);
+            push_event(InputEvent::MouseDown(mouse_button()));
+            if msg == winuser::WM_XBUTTONDOWN { minwindef::TRUE as _ } else { 0 }
+        }
+        winuser::WM_LBUTTONUP | winuser::WM_RBUTTONUP | winuser::WM_MBUTTONUP
+            | winuser::WM_XBUTTONUP => {
+            if wparam & ANY_BUTTON == 0 {
+                winuser::ReleaseCapture();
+            }
+            push_event(InputEvent::MouseUp(mouse_button()));
+            if msg == winuser::WM_XBUTTONUP { minwindef::TRUE as _ } else { 0 }
+        }
+        winuser::WM_MOUSEMOVE => {
+            push_event(InputEvent::MouseMove(cursor_from_lparam(lparam)));
+            0
+        }
+        winuser::WM_MOUSEWHEEL => {
+            let delta = ((wparam >> 16) & 0xFFFF) as i16;
+            push_event(InputEvent::MouseWheel(delta as f32 / winuser::WHEEL_DELTA as f32));
+            0
+        }
+        winuser::WM_SIZE => {
+            // minimizing reports a zero size, which nothing can render to
+            if wparam != winuser::SIZE_MINIMIZED as usize {
+                let width = (lparam & 0xFFFF) as u32;
+                let height = ((lparam >> 16) & 0xFFFF) as u32;
+                PENDING_RESIZE.with(|size| size.set(Some((width, height))));
+            }
+            0
+        }
+        winuser::WM_KILLFOCUS => {
+            push_event(InputEvent::FocusLost);
+            0
+        }
+        _ => {
+            winuser::DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+    }
+}
+
+///
+/// Converts an XInput gamepad snapshot to the platform-neutral state
+///
+fn translate_gamepad(pad: &xinput::XINPUT_GAMEPAD) -> GamepadState {
+    const BUTTONS: [(u16, GamepadButton); 14] = [
+        (xinput::XINPUT_GAMEPAD_A, GamepadButton::A),
+        (xinput::XINPUT_GAMEPAD_B, GamepadButton::B),
+        (xinput::XINPUT_GAMEPAD_X, GamepadButton::X),
+        (xinput::XINPUT_GAMEPAD_Y, GamepadButton::Y),
+        (xinput::XINPUT_GAMEPAD_LEFT_SHOULDER, GamepadButton::LeftShoulder),
+        (xinput::XINPUT_GAMEPAD_RIGHT_SHOULDER, GamepadButton::RightShoulder),
+        (xinput::XINPUT_GAMEPAD_LEFT_THUMB, GamepadButton::LeftThumb),
+        (xinput::XINPUT_GAMEPAD_RIGHT_THUMB, GamepadButton::RightThumb),
+        (xinput::XINPUT_GAMEPAD_START, GamepadButton::Start),
+        (xinput::XINPUT_GAMEPAD_BACK, GamepadButton::Back),
+        (xinput::XINPUT_GAMEPAD_DPAD_UP, GamepadButton::DPadUp),
+        (xinput::XINPUT_GAMEPAD_DPAD_DOWN, GamepadButton::DPadDown),
+        (xinput::XINPUT_GAMEPAD_DPAD_LEFT, GamepadButton::DPadLeft),
+        (xinput::XINPUT_GAMEPAD_DPAD_RIGHT, GamepadButton::DPadRight)
+    ];
+
+    // rescales a stick so the dead zone reads as zero and the edge as one
+    let stick = |x: i16, y: i16, dead_zone: i16| {
+        let v = Vector2F::new(x as f32, y as f32);
+        let len = v.norm();
+        if len <= dead_zone as f32 {
+            return Vector2F::zeros();
+        }
+        let scaled = ((len - dead_zone as f32) / (32767.0f32 - dead_zone as f32)).min(1.0f32);
+        v * (scaled / len)
+    };
+    let trigger = |t: u8| {
+        let threshold = xinput::XINPUT_GAMEPAD_TRIGGER_THRESHOLD;
+        if t <= threshold { 0.0f32 } else { (t - threshold) as f32 / (255 - threshold) as f32 }
+    };
+
+    let mut state = GamepadState {
+        connected: true,
+        left_stick: stick(pad.sThumbLX, pad.sThumbLY, xinput::XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE),
+        right_stick: stick(pad.sThumbRX, pad.sThumbRY, xinput::XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE),
+        left_trigger: trigger(pad.bLeftTrigger),
+        right_trigger: trigger(pad.bRightTrigger),
+        ..Default::default()
+    };
+    for (mask, button) in BUTTONS.iter() {
+        state.set_button(*button, (pad.wButtons & mask) != 0);
+    }
+
+    state
+}
+
+///
+/// Application window implemented with Win32
+///
+pub struct Win32Window {
+    handle: windef::HWND
+}
+
+impl Win32Window {
+    ///
+    /// Creates an application window
+    ///
+    pub fn create(title: &str, width: u32, height: u32) -> Result<Self, Error> {
+        let class_name = to_wstring("AppWindow");
+        let wnd_title = to_wstring(title);
+        let class = winuser::WNDCLASSW {
+            style: 0,
+            lpfnWndProc: Some(window_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: 0 as _,
+            hIcon: unsafe { winuser::LoadIconW(0 as _, winuser::IDI_APPLICATION) },
+            hCursor: unsafe { winuser::LoadCursorW(0 as _, winuser::IDI_APPLICATION) },
+            hbrBackground: 16 as _,
+            lpszMenuName: 0 as _,
+            lpszClassName: class_name.as_ptr(),
+        };
+
+        unsafe { winuser::RegisterClassW(&class); }
+
+        let handle = unsafe {
+            winuser::CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                wnd_title.as_ptr(),
+                winuser::WS_OVERLAPPEDWINDOW,
+                winuser::CW_USEDEFAULT,
+                winuser::CW_USEDEFAULT,
+                width as i32,
+                height as i32,
+                0 as _,
+                0 as _,
+                0 as _,
+                0 as _)
+        };
+
+        if handle.is_null() {
+            let code = unsafe { errhandlingapi::GetLastError() };
+            return Err(Error::device(format!("create the window (error {})", code)));
+        }
+
+        Ok(Self {
+            handle
+        })
+    }
+
+    ///
+    /// Destroys the window
+    ///
+    fn destroy(&mut self) {
+        if !self.handle.is_null() {
+            unsafe { winuser::DestroyWindow(self.handle); }
+            self.handle = ptr::null_mut();
+        }
+    }
+}
+
+impl WindowBackend for Win32Window {
+    fn get_type(&self) -> WindowBackendType {
+        WindowBackendType::Win32
+    }
+
+    ///
+    /// Shows or hides the window
+    ///
+    fn show(&self, show: bool) {
+        let cmd = if show { winuser::SW_SHOW } else { winuser::SW_HIDE };
+        unsafe { winuser::ShowWindow(self.handle, cmd); }
+    }
+
+    ///
+    /// Processes window messages until the message queue is empty, feeding any input they carry
+    /// into the input state
+    ///
+    /// # Returns
+    /// `false` if the application should exit, `true` otherwise
+    ///
+    fn process_messages(&self, input: &mut InputState) -> bool {
+        let mut msg = winuser::MSG {
+            hwnd: ptr::null_mut(),
+            message: 0,
+            lParam: 0,
+            wParam: 0,
+            pt: windef::POINT { x: 0, y: 0 },
+            time: 0
+        };
+
+        let mut running = true;
+        unsafe {
+            while winuser::PeekMessageW(&mut msg, ptr::null_mut(), 0, 0, winuser::PM_REMOVE) != 0 {
+                if msg.message == winuser::WM_QUIT {
+                    running = false;
+                    break;
+                }
+
+                winuser::TranslateMessage(&msg);
+                winuser::DispatchMessageW(&msg);
+            }
+        }
+
+        PENDING_EVENTS.with(|events| {
+            for event in events.borrow_mut().drain(..) {
+                input.handle_event(&event);
+            }
+        });
+
+        running
+    }
+
+    ///
+    /// Takes the client area size the window was last resized to, if it changed since the last call
+    ///
+    fn take_resize(&self) -> Option<(u32, u32)> {
+        PENDING_RESIZE.with(|size| size.take())
+    }
+
+    ///
+    /// Reads the state of every XInput gamepad into the input state
+    ///
+    fn poll_gamepads(&self, input: &mut InputState) {
+        for index in 0..InputState::MAX_GAMEPADS {
+            let mut xstate: xinput::XINPUT_STATE = unsafe { mem::zeroed() };
+            let result = unsafe { xinput::XInputGetState(index as u32, &mut xstate) };
+            let state = if result == winerror::ERROR_SUCCESS {
+                translate_gamepad(&xstate.Gamepad)
+            }
+            else {
+                GamepadState::default()
+            };
+            input.handle_event(&InputEvent::Gamepad(index, state));
+        }
+    }
+
+    ///
+    /// Asks the window to close, which ends the message loop once the window is destroyed
+    ///
+    fn close(&self) {
+        unsafe { winuser::PostMessageW(self.handle, winuser::WM_CLOSE, 0, 0); }
+    }
+
+    ///
+    /// Accesses the internal handle to the window
+    ///
+    fn get_handle(&self) -> u64 {
+        self.handle as u64
+    }
+
+    ///
+    /// Gets the size of the window's client area
+    ///
+    fn get_window_size(&self) -> (u32, u32) {
+        let mut r = windef::RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0
+        };
+        unsafe { winuser::GetClientRect(self.handle, &mut r); }
+
+        (r.right as _, r.bottom as _)
+    }
+}
+
+impl Drop for Win32Window {
+    ///
+    /// Cleans up and properly destroys the window when dropped
+    ///
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
\ No newline at end of file
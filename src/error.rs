@@ -0,0 +1,127 @@
+// external refs
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+///
+/// Errors reported by the fallible operations in the crate
+///
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed
+    Io { path: PathBuf, source: io::Error },
+    /// A file's contents couldn't be understood
+    Parse { path: PathBuf, line: Option<usize>, message: String },
+    /// A shader failed to compile, with the compiler's diagnostics
+    ShaderCompile { path: PathBuf, entry_point: String, diagnostics: String },
+    /// The graphics device or platform rejected an operation, with its HRESULT if it gave one
+    Device { operation: String, hresult: Option<i32> },
+    /// The data is in a format or of a kind that isn't supported
+    UnsupportedFormat { what: String, path: Option<PathBuf> },
+    /// Something an operation depends on doesn't exist
+    MissingResource { what: String, path: Option<PathBuf> },
+    /// An argument can't be used for the requested operation
+    InvalidArgument(String)
+}
+
+impl Error {
+    ///
+    /// Creates an I/O error for the specified file
+    ///
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io { path: path.to_path_buf(), source }
+    }
+
+    ///
+    /// Creates a parse error for the specified file, optionally at a 1-based line number
+    ///
+    pub fn parse(path: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Error::Parse { path: path.to_path_buf(), line, message: message.into() }
+    }
+
+    ///
+    /// Creates an error for a failed device call returning the specified HRESULT
+    ///
+    pub fn hresult(operation: impl Into<String>, hresult: i32) -> Self {
+        Error::Device { operation: operation.into(), hresult: Some(hresult) }
+    }
+
+    ///
+    /// Creates an error for a failed device operation without an error code
+    ///
+    pub fn device(operation: impl Into<String>) -> Self {
+        Error::Device { operation: operation.into(), hresult: None }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Parse { path, line: Some(line), message } => {
+                write!(f, "{}:{}: {}", path.display(), line, message)
+            }
+            Error::Parse { path, line: None, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+            Error::ShaderCompile { path, entry_point, diagnostics } => {
+                write!(f, "Failed to compile {} in {}", entry_point, path.display())?;
+                if !diagnostics.is_empty() {
+                    write!(f, ":\n{}", diagnostics.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::Device { operation, hresult: Some(hr) } => {
+                write!(f, "Failed to {} (HRESULT {:#010x})", operation, hr)
+            }
+            Error::Device { operation, hresult: None } => write!(f, "Failed to {}", operation),
+            Error::UnsupportedFormat { what, path: Some(path) } => {
+                write!(f, "{}: unsupported {}", path.display(), what)
+            }
+            Error::UnsupportedFormat { what, path: None } => write!(f, "Unsupported {}", what),
+            Error::MissingResource { what, path: Some(path) } => {
+                write!(f, "{}: missing {}", path.display(), what)
+            }
+            Error::MissingResource { what, path: None } => write!(f, "Missing {}", what),
+            Error::InvalidArgument(message) => write!(f, "Invalid argument: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_carry_context() {
+        let path = Path::new("data").join("objects").join("cube.obj");
+        let shown = |e: Error| e.to_string().replace('\\', "/");
+
+        assert_eq!(shown(Error::parse(&path, Some(12), "expected 3 floats")),
+            "data/objects/cube.obj:12: expected 3 floats");
+        assert_eq!(shown(Error::hresult("create swap chain", 0x887a0001u32 as i32)),
+            "Failed to create swap chain (HRESULT 0x887a0001)");
+        assert_eq!(shown(Error::ShaderCompile { path: PathBuf::from("a.hlsl"),
+            entry_point: "PSMain".to_string(), diagnostics: "a.hlsl(3): error X3000\n".to_string() }),
+            "Failed to compile PSMain in a.hlsl:\na.hlsl(3): error X3000");
+        assert_eq!(shown(Error::MissingResource { what: "texture".to_string(),
+            path: Some(PathBuf::from("albedo.png")) }), "albedo.png: missing texture");
+    }
+
+    #[test]
+    fn io_errors_expose_their_source() {
+        use std::error::Error as _;
+        let e = Error::io(Path::new("out.png"), io::Error::other("disk full"));
+        assert_eq!(e.to_string(), "out.png: disk full");
+        assert!(e.source().is_some());
+    }
+}
@@ -1,3 +1,4 @@
+mod backend;
 mod graphics;
 mod display;
 mod render_target;
@@ -16,7 +17,14 @@ mod shader_compiler;
 mod shader_buffer;
 mod shader_input;
 mod sampler;
+mod image_writer;
+mod image_compare;
+mod camera;
+mod asset_resolver;
+#[cfg(test)]
+mod test_fixtures;
 
+pub use self::backend::*;
 pub use self::graphics::*;
 pub use self::display::*;
 pub use self::render_target::*;
@@ -34,4 +42,8 @@ pub use self::shader::*;
 pub use self::shader_compiler::*;
 pub use self::shader_buffer::*;
 pub use self::shader_input::*;
-pub use self::sampler::*;
\ No newline at end of file
+pub use self::sampler::*;
+pub use self::image_writer::*;
+pub use self::image_compare::*;
+pub use self::camera::*;
+pub use self::asset_resolver::*;
\ No newline at end of file
@@ -0,0 +1,222 @@
+// external refs
+use std::fmt;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+
+///
+/// A file referenced by a model that couldn't be found
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedAsset {
+    /// The path as written in the referencing file
+    pub reference: String,
+    /// The file containing the reference
+    pub referenced_by: PathBuf
+}
+
+impl fmt::Display for UnresolvedAsset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: can't find {}", self.referenced_by.display(), self.reference)
+    }
+}
+
+///
+/// Finds files referenced by models. References are looked for relative to the directories of
+/// the files containing them first, then in each search root in the order they were added.
+///
+#[derive(Debug, Clone)]
+pub struct AssetResolver {
+    search_roots: Vec<PathBuf>
+}
+
+impl AssetResolver {
+    ///
+    /// Creates a resolver that only looks next to the referencing files
+    ///
+    pub fn new() -> Self {
+        Self { search_roots: Vec::new() }
+    }
+
+    ///
+    /// Adds a directory to look in after the referencing files' directories
+    ///
+    pub fn with_search_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.search_roots.push(root.into());
+        self
+    }
+
+    ///
+    /// Gets the directories searched after the referencing files' directories
+    ///
+    pub fn get_search_roots(&self) -> &[PathBuf] {
+        &self.search_roots
+    }
+
+    ///
+    /// Converts a path written in a model file into a path for the current platform, accepting
+    /// either separator and dropping `.` components and surrounding quotes
+    ///
+    pub fn normalize(reference: &str) -> PathBuf {
+        let reference = reference.trim().trim_matches('"');
+        let mut path = PathBuf::new();
+        if reference.starts_with(['/', '\\']) {
+            path.push(MAIN_SEPARATOR.to_string());
+        }
+
+        for part in reference.split(['/', '\\']) {
+            match part {
+                "" | "." => continue,
+                // a drive letter has to be followed by a separator to stay absolute
+                drive if path.as_os_str().is_empty() && drive.ends_with(':') => {
+                    path.push(format!("{}{}", drive, MAIN_SEPARATOR));
+                }
+                _ => path.push(part)
+            }
+        }
+
+        path
+    }
+
+    ///
+    /// Writes a reference to a file for a model file in the specified directory, the reverse of
+    /// `normalize`. The reference is relative unless the two are on different drives, and always
+    /// uses forward slashes.
+    ///
+    pub fn make_reference(file: &Path, base_dir: &Path) -> String {
+        let file = absolute(file);
+        let base_dir = absolute(base_dir);
+        let common = file.components().zip(base_dir.components())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if common == 0 {
+            return file.to_string_lossy().replace('\\', "/");
+        }
+
+        let up = base_dir.components().skip(common).map(|_| "..".into());
+        let down = file.components().skip(common).map(|c| c.as_os_str().to_string_lossy());
+        up.chain(down).collect::<Vec<_>>().join("/")
+    }
+
+    ///
+    /// Looks up a referenced file, first in the specified directories and then in the search
+    /// roots. References are often written with the directory layout of the machine the model
+    /// was authored on, so if the full path isn't found anywhere the file name alone is tried.
+    ///
+    /// # Returns
+    /// The path to the file, or `None` if it doesn't exist in any of the places searched
+    ///
+    pub fn resolve(&self, reference: &str, base_dirs: &[&Path]) -> Option<PathBuf> {
+        let relative = Self::normalize(reference);
+        let file_name = Path::new(relative.file_name()?);
+        let dirs = || base_dirs.iter().copied().chain(self.search_roots.iter().map(PathBuf::as_path));
+
+        dirs().map(|dir| dir.join(&relative))
+            .chain(dirs().map(|dir| dir.join(file_name)))
+            .find(|candidate| candidate.is_file())
+    }
+
+    ///
+    /// Resolves a reference, adding it to the unresolved list if it can't be found
+    ///
+    /// # Returns
+    /// The path to the file if it was found, otherwise where it was expected relative to the
+    /// first base directory
+    ///
+    pub fn resolve_or_record(&self, reference: &str, base_dirs: &[&Path], referenced_by: &Path,
+        unresolved: &mut Vec<UnresolvedAsset>) -> PathBuf {
+
+        self.resolve(reference, base_dirs).unwrap_or_else(|| {
+            unresolved.push(UnresolvedAsset {
+                reference: reference.to_string(),
+                referenced_by: referenced_by.to_path_buf()
+            });
+            let base_dir = base_dirs.first().copied().unwrap_or_else(|| Path::new(""));
+            base_dir.join(Self::normalize(reference))
+        })
+    }
+}
+
+///
+/// Makes a path absolute without requiring it to exist, removing `.` and `..` components
+///
+fn absolute(path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    let full = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    for component in full.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(absolute.components().next_back(), Some(Component::Normal(_))) => {
+                absolute.pop();
+            }
+            _ => absolute.push(component)
+        }
+    }
+    absolute
+}
+
+impl Default for AssetResolver {
+    ///
+    /// Searches the data directory in the working directory and next to the executable
+    ///
+    fn default() -> Self {
+        let resolver = Self::new().with_search_root("data");
+        match std::env::current_exe().ok().as_ref().and_then(|exe| exe.parent()) {
+            Some(exe_dir) => resolver.with_search_root(exe_dir.join("data")),
+            None => resolver
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_separators() {
+        let expected: PathBuf = ["textures", "wood", "oak.png"].iter().collect();
+        assert_eq!(AssetResolver::normalize("textures\\wood\\oak.png"), expected);
+        assert_eq!(AssetResolver::normalize("./textures//wood/oak.png"), expected);
+        assert_eq!(AssetResolver::normalize(" \"textures/wood\\oak.png\" "), expected);
+        assert!(AssetResolver::normalize("/textures/oak.png").has_root());
+    }
+
+    #[test]
+    fn searches_base_dirs_then_roots_then_file_names() {
+        let objects = Path::new("data").join("objects");
+        let resolver = AssetResolver::new().with_search_root("data");
+
+        assert_eq!(resolver.resolve("brick.tga", &[&objects]), Some(objects.join("brick.tga")));
+        assert_eq!(resolver.resolve("objects\\brick.tga", &[Path::new("tests")]),
+            Some(Path::new("data").join("objects").join("brick.tga")));
+        assert_eq!(resolver.resolve("C:\\Users\\artist\\brick.tga", &[&objects]),
+            Some(objects.join("brick.tga")));
+        assert_eq!(resolver.resolve("missing.tga", &[&objects]), None);
+    }
+
+    #[test]
+    fn records_unresolved_references() {
+        let resolver = AssetResolver::new();
+        let mut unresolved = Vec::new();
+        let mtl = Path::new("models").join("car.mtl");
+        let path = resolver.resolve_or_record("paint\\red.png", &[Path::new("models")], &mtl,
+            &mut unresolved);
+
+        assert_eq!(path, Path::new("models").join("paint").join("red.png"));
+        assert_eq!(unresolved, vec![UnresolvedAsset { reference: "paint\\red.png".to_string(),
+            referenced_by: mtl }]);
+    }
+
+    #[test]
+    fn makes_relative_references() {
+        let models = Path::new("models").join("cars");
+        assert_eq!(AssetResolver::make_reference(&models.join("paint").join("red.png"), &models),
+            "paint/red.png");
+        assert_eq!(AssetResolver::make_reference(&Path::new("textures").join("red.png"),
+            &models.join(".").join("..")), "../textures/red.png");
+
+        let reference = AssetResolver::make_reference(Path::new("red.png"), &models);
+        assert_eq!(reference, "../../red.png");
+        assert_eq!(models.join(AssetResolver::normalize(&reference)),
+            models.join("..").join("..").join("red.png"));
+    }
+}
@@ -0,0 +1,132 @@
+// external refs
+use std::any::Any;
+use std::path::Path;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+#[cfg(windows)]
+mod d3d11;
+mod software;
+
+#[cfg(windows)]
+pub use self::d3d11::D3D11Backend;
+pub use self::software::*;
+
+///
+/// Opaque, backend-specific data owned by a graphics resource
+///
+pub type ResourceHandle = Box<dyn Any>;
+
+///
+/// Retrieves the backend-specific data from a resource handle
+///
+/// # Panics
+/// If the resource was created by a different backend
+///
+pub fn native<T: 'static>(handle: &ResourceHandle) -> &T {
+    handle.downcast_ref::<T>().expect("Resource was created by a different graphics backend")
+}
+
+///
+/// Plain data that can be uploaded to the GPU or written to a file as its raw bytes
+///
+/// # Safety
+/// Implementers must have a defined layout (a primitive, an array of plain data or a `repr(C)`
+/// struct of plain data) with no padding, and must not hold pointers or references
+///
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+unsafe impl Pod for Color4 {}
+unsafe impl Pod for Color4F {}
+
+///
+/// Reinterprets a slice of plain data as raw bytes for upload
+///
+pub fn slice_as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    // plain data has every byte initialized, so any of them can be read
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+///
+/// The available graphics backends
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BackendType {
+    #[cfg(windows)]
+    D3D11,
+    Software
+}
+
+impl BackendType {
+    ///
+    /// The backend used when none is requested explicitly
+    ///
+    #[cfg(windows)]
+    pub fn platform_default() -> Self { BackendType::D3D11 }
+    #[cfg(not(windows))]
+    pub fn platform_default() -> Self { BackendType::Software }
+}
+
+///
+/// Interface implemented by each graphics backend. `Graphics` forwards its API to one of these.
+///
+pub trait Backend {
+    fn get_type(&self) -> BackendType;
+
+    // resource creation
+    fn create_display(&self, window_handle: u64, width: u32, height: u32) -> Result<Display, Error>;
+    #[allow(clippy::too_many_arguments)]
+    fn create_texture2d(&self, format: TextureFormat, width: u32, height: u32, mips: u32,
+        generate_mips: bool, render_target: bool, shader_resource: bool, pixel_data: &[u8])
+        -> Result<Texture, Error>;
+    fn create_sampler(&self, data: SamplerData) -> Result<Sampler, Error>;
+    fn create_render_target(&self, display: &Display) -> Result<RenderTarget, Error>;
+    fn create_offscreen_render_target(&self, width: u32, height: u32) -> Result<RenderTarget, Error>;
+    fn create_depth_stencil_target(&self, format: DepthStencilFormat, width: u32, height: u32)
+        -> Result<DepthStencilTarget, Error>;
+    fn create_vertex_buffer(&self, format: &VertexFormat, num_verts: u32, data: &[u8])
+        -> Result<VertexBuffer, Error>;
+    fn create_index_buffer(&self, format: IndexFormat, num_indices: u32, data: &[u8])
+        -> Result<IndexBuffer, Error>;
+    fn create_constant_buffer(&self, size: usize) -> Result<ShaderBuffer, Error>;
+    fn create_texture_shader_input(&self, tex: &Texture) -> Result<ShaderInput, Error>;
+    fn compile_shader(&self, path: &Path, defines: &[(String, Option<String>)], entry_point: &str,
+        profile: ShaderType) -> Result<ShaderCode, Error>;
+    fn create_shader(&self, code: &ShaderCode) -> Result<Shader, Error>;
+    fn create_input_layout(&self, format: &VertexFormat, vs_code: &ShaderCode)
+        -> Result<InputLayout, Error>;
+
+    // data transfer
+    fn set_buffer_data(&self, buffer: &ShaderBuffer, data: &[u8]);
+    fn read_render_target(&self, rt: &RenderTarget) -> Result<Vec<Color4>, Error>;
+    fn resize_display(&self, display: &Display, width: u32, height: u32) -> Result<(), Error>;
+    fn present(&self, display: &Display) -> Result<(), Error>;
+
+    // pipeline state
+    fn clear_render_target(&self, rt: &RenderTarget, rgba: &Color4F);
+    fn clear_depth_stencil_target(&self, ds: &DepthStencilTarget, clear_depth: bool, depth: f32,
+        clear_stencil: bool, stencil: u8);
+    fn set_render_target(&self, rt: &RenderTarget, ds: Option<&DepthStencilTarget>);
+    fn unbind_render_targets(&self);
+    fn set_viewport(&self, x: f32, y: f32, w: f32, h: f32, min_z: f32, max_z: f32);
+    fn set_primitive_topology(&self, topology: PrimitiveTopology);
+    fn set_input_layout(&self, layout: &InputLayout);
+    fn set_vertex_buffer(&self, vb: &VertexBuffer, slot: u32);
+    fn set_index_buffer(&self, ib: &IndexBuffer);
+    fn set_shader(&self, shader: &Shader);
+    fn set_constant_buffer(&self, stage: ShaderType, slot: u32, buffer: &ShaderBuffer);
+    fn set_shader_input(&self, stage: ShaderType, slot: u32, input: &ShaderInput);
+    fn set_sampler(&self, stage: ShaderType, slot: u32, sampler: &Sampler);
+
+    // draw calls
+    fn draw(&self, num_verts: u32, start_vert: u32);
+    fn draw_indexed(&self, num_indices: u32, start_index: u32);
+}
@@ -0,0 +1,1017 @@
+// external refs
+use winapi::um::d3d11;
+use winapi::um::d3dcommon;
+use winapi::um::d3dcompiler;
+use winapi::um::d3d11sdklayers;
+use winapi::um::unknwnbase::IUnknown;
+use winapi::shared::dxgi;
+use winapi::shared::dxgitype;
+use winapi::shared::dxgiformat;
+use winapi::shared::winerror;
+use std::ffi::{CStr, CString, OsStr};
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Generates a wide string from a Rust string slice
+///
+fn to_wstring(s : &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(Some(0).into_iter()).collect()
+}
+
+///
+/// Owning reference to a COM interface that is released when dropped
+///
+struct ComRef<T>(*mut T);
+
+impl<T> ComRef<T> {
+    fn null() -> Self {
+        ComRef(ptr::null_mut())
+    }
+}
+
+impl<T> Drop for ComRef<T> {
+    ///
+    /// Releases the reference to the interface
+    ///
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { (*(self.0 as *mut IUnknown)).Release(); }
+        }
+    }
+}
+
+///
+/// Native data for a depth stencil target
+///
+struct D3D11DepthStencil {
+    dsv: ComRef<d3d11::ID3D11DepthStencilView>,
+    _res: ComRef<d3d11::ID3D11Resource>
+}
+
+///
+/// Native data for a shader
+///
+enum D3D11Shader {
+    Vertex(ComRef<d3d11::ID3D11VertexShader>),
+    Pixel(ComRef<d3d11::ID3D11PixelShader>)
+}
+
+///
+/// Compiled shader blob
+///
+struct D3D11Blob(ComRef<d3dcommon::ID3DBlob>);
+
+impl D3D11Blob {
+    ///
+    /// Retrieve the buffer as a memory slice
+    ///
+    fn to_slice(&self) -> Option<&[u8]> {
+        if (self.0).0.is_null() {
+            None
+        }
+        else {
+            unsafe {
+                let p = (*(self.0).0).GetBufferPointer() as *const u8;
+                let s = (*(self.0).0).GetBufferSize();
+
+                Some(std::slice::from_raw_parts(p, s))
+            }
+        }
+    }
+
+    ///
+    /// Retrieve the buffer as a string slice
+    ///
+    fn to_str(&self) -> Option<&str> {
+        let v = self.to_slice()?;
+
+        std::str::from_utf8(v).ok()
+    }
+}
+
+///
+/// Graphics backend implemented with Direct3D 11
+///
+pub struct D3D11Backend {
+    device: *mut d3d11::ID3D11Device,
+    context: *mut d3d11::ID3D11DeviceContext
+}
+
+impl D3D11Backend {
+    ///
+    /// Creates the D3D11 device and immediate context
+    ///
+    pub fn new() -> Result<Self, Error> {
+        let mut device = ptr::null_mut::<d3d11::ID3D11Device>();
+        let mut context = ptr::null_mut::<d3d11::ID3D11DeviceContext>();
+        let feature_level = d3dcommon::D3D_FEATURE_LEVEL_11_0;
+
+        // create the D3D11 device
+        let flags = if cfg!(debug_assertions) { d3d11::D3D11_CREATE_DEVICE_DEBUG } else { 0 };
+        let mut hr = unsafe {
+            d3d11::D3D11CreateDevice(
+                ptr::null_mut(),
+                d3dcommon::D3D_DRIVER_TYPE_HARDWARE,
+                0 as _,
+                flags,
+                &feature_level as *const _,
+                1,
+                d3d11::D3D11_SDK_VERSION,
+                &mut device as *mut *mut _,
+                ptr::null_mut(),
+                &mut context as *mut *mut _
+            )
+        };
+
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("create the D3D11 device", hr));
+        }
+
+        if cfg!(debug_assertions) {
+            unsafe {
+                // Enable breaking on errors and warnings
+                let mut dbg = ptr::null_mut::<d3d11sdklayers::ID3D11Debug>();
+                hr = (*device).QueryInterface(
+                    &d3d11sdklayers::IID_ID3D11Debug,
+                    &mut dbg as *mut *mut _ as *mut *mut _
+                );
+
+                let mut info = ptr::null_mut::<d3d11sdklayers::ID3D11InfoQueue>();
+                if hr == winerror::S_OK && !dbg.is_null() {
+                    hr = (*dbg).QueryInterface(
+                        &d3d11sdklayers::IID_ID3D11InfoQueue,
+                        &mut info as *mut *mut _ as *mut *mut _
+                    );
+
+                    if hr == winerror::S_OK && !info.is_null() {
+                        const BREAK_SEVERITIES: [d3d11sdklayers::D3D11_MESSAGE_SEVERITY; 3] = [
+                            d3d11sdklayers::D3D11_MESSAGE_SEVERITY_CORRUPTION,
+                            d3d11sdklayers::D3D11_MESSAGE_SEVERITY_ERROR,
+                            d3d11sdklayers::D3D11_MESSAGE_SEVERITY_WARNING,
+                        ];
+
+                        for sev in &BREAK_SEVERITIES {
+                            (*info).SetBreakOnSeverity(*sev, 1);
+                        }
+
+                        (*info).Release();
+                    }
+
+                    (*dbg).Release();
+                }
+            }
+        }
+
+        Ok(Self { device, context })
+    }
+
+    ///
+    /// Creates a buffer with the specified description and optional initial data
+    ///
+    fn create_buffer(&self, desc: &d3d11::D3D11_BUFFER_DESC, init_data: Option<&[u8]>)
+        -> Result<ComRef<d3d11::ID3D11Buffer>, Error> {
+
+        let init_desc = init_data.map(|data| d3d11::D3D11_SUBRESOURCE_DATA {
+            pSysMem: data.as_ptr() as _,
+            SysMemPitch: data.len() as _,
+            SysMemSlicePitch: 0
+        });
+        let init_ptr = match &init_desc {
+            Some(d) => d as *const _,
+            None => ptr::null()
+        };
+
+        let mut buff = ComRef::null();
+        let hr = unsafe { (*self.device).CreateBuffer(desc, init_ptr, &mut buff.0 as *mut *mut _) };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult(format!("create a buffer of size {}", desc.ByteWidth), hr))
+        }
+        else {
+            Ok(buff)
+        }
+    }
+
+    ///
+    /// Creates a render target view for the specified texture resource
+    ///
+    fn create_render_target_view(&self, tex: *mut d3d11::ID3D11Resource, width: u32, height: u32)
+        -> Result<RenderTarget, Error> {
+
+        let mut rtv = ComRef::<d3d11::ID3D11RenderTargetView>::null();
+        let hr = unsafe { (*self.device).CreateRenderTargetView(tex, ptr::null(), &mut rtv.0) };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a render target view", hr))
+        }
+        else {
+            // create RenderTarget object with the render target view
+            Ok(RenderTarget { width, height, handle: Box::new(rtv) })
+        }
+    }
+
+    ///
+    /// Gets the native buffer for the specified shader buffer
+    ///
+    fn buffer_ptr(buffer: &ShaderBuffer) -> *mut d3d11::ID3D11Buffer {
+        native::<ComRef<d3d11::ID3D11Buffer>>(&buffer.handle).0
+    }
+}
+
+impl Drop for D3D11Backend {
+    ///
+    /// Cleans up the internal graphics device when dropped
+    ///
+    fn drop(&mut self) {
+        if !self.context.is_null() {
+            unsafe { (*self.context).Release(); }
+        }
+        if !self.device.is_null() {
+            unsafe { (*self.device).Release(); }
+        }
+    }
+}
+
+impl Backend for D3D11Backend {
+    fn get_type(&self) -> BackendType {
+        BackendType::D3D11
+    }
+
+    ///
+    /// Creates a swap chain for the specified window
+    ///
+    fn create_display(&self, hwnd: u64, width: u32, height: u32) -> Result<Display, Error> {
+        // first, get the DXGI device from the D3D11 device
+        let mut dxgidevice = ComRef::<dxgi::IDXGIDevice>::null();
+        let mut hr = unsafe {
+            (*self.device).QueryInterface(
+                &dxgi::IID_IDXGIDevice,
+                &mut dxgidevice.0 as *mut *mut _ as *mut *mut _)
+        };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("get the DXGI device from the D3D11 device", hr));
+        }
+
+        // then, get the adapter used to create the device
+        let mut adapter = ComRef::<dxgi::IDXGIAdapter>::null();
+        hr = unsafe { (*dxgidevice.0).GetAdapter(&mut adapter.0) };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("get the adapter from the DXGI device", hr));
+        }
+
+        // now get the DXGI factory from the adapter
+        let mut factory = ComRef::<dxgi::IDXGIFactory>::null();
+        hr = unsafe {
+            (*adapter.0).GetParent(
+                &dxgi::IID_IDXGIFactory,
+                &mut factory.0 as *mut *mut _ as *mut *mut _)
+        };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("get the DXGI factory from the adapter", hr));
+        }
+
+        // use the factory to create a swap chain for the window
+        let mut desc = dxgi::DXGI_SWAP_CHAIN_DESC {
+            BufferDesc: dxgitype::DXGI_MODE_DESC {
+                Width: width,
+                Height: height,
+                RefreshRate: dxgitype::DXGI_RATIONAL {
+                    Numerator: 1,
+                    Denominator: 60
+                },
+                Format: dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                ScanlineOrdering: 0,
+                Scaling: 0,
+            },
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0
+            },
+            BufferUsage: dxgitype::DXGI_USAGE_BACK_BUFFER |
+                         dxgitype::DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            BufferCount: 1,
+            OutputWindow: hwnd as _,
+            Windowed: 1,
+            SwapEffect: dxgi::DXGI_SWAP_EFFECT_DISCARD,
+            Flags: 0
+        };
+        let mut sc = ComRef::<dxgi::IDXGISwapChain>::null();
+        hr = unsafe { (*factory.0).CreateSwapChain(self.device as *mut _, &mut desc, &mut sc.0) };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a swap chain for the window", hr))
+        }
+        else {
+            // create Display object from the created swap chain
+            Ok(Display { width, height, handle: Box::new(sc) })
+        }
+    }
+
+    ///
+    /// Creates a new texture from the specified data
+    ///
+    fn create_texture2d(&self, format: TextureFormat, width: u32, height: u32, mips: u32,
+        generate_mips: bool, render_target: bool, shader_resource: bool, pixel_data: &[u8])
+        -> Result<Texture, Error> {
+
+        const TEXTURE_FORMATS: [dxgiformat::DXGI_FORMAT; 7] = [
+            dxgiformat::DXGI_FORMAT_R8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8G8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            dxgiformat::DXGI_FORMAT_R32_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32G32_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32G32B32A32_FLOAT,
+        ];
+
+        let mut bind_flags = 0;
+        if render_target {
+            bind_flags |= d3d11::D3D11_BIND_RENDER_TARGET;
+        }
+        if shader_resource {
+            bind_flags |= d3d11::D3D11_BIND_SHADER_RESOURCE;
+        }
+
+        let mut misc_flags = 0;
+        if generate_mips {
+            misc_flags = d3d11::D3D11_RESOURCE_MISC_GENERATE_MIPS
+        }
+
+        let desc = d3d11::D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: mips,
+            ArraySize: 1,
+            Format: TEXTURE_FORMATS[format as usize],
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: d3d11::D3D11_USAGE_IMMUTABLE,
+            BindFlags: bind_flags,
+            CPUAccessFlags: 0,
+            MiscFlags: misc_flags
+        };
+
+        let init_data = d3d11::D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixel_data.as_ptr() as _,
+            SysMemPitch: width * format.pixel_size(),
+            SysMemSlicePitch: width * height * format.pixel_size()
+        };
+
+        let mut res = ptr::null_mut::<d3d11::ID3D11Texture2D>();
+        let hr = unsafe {
+            (*self.device).CreateTexture2D(&desc, &init_data, &mut res as *mut *mut _)
+        };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult(format!("create a {}x{} texture", width, height), hr))
+        }
+        else {
+            let res = ComRef(res as *mut d3d11::ID3D11Resource);
+            Ok(Texture { format, width, height, depth: 1, handle: Box::new(res) })
+        }
+    }
+
+    ///
+    /// Creates a new sampler state
+    ///
+    fn create_sampler(&self, data: SamplerData) -> Result<Sampler, Error> {
+        const FILTERS: [d3d11::D3D11_FILTER; 6] = [
+            d3d11::D3D11_FILTER_MIN_MAG_MIP_POINT,
+            d3d11::D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            d3d11::D3D11_FILTER_ANISOTROPIC,
+            d3d11::D3D11_FILTER_COMPARISON_MIN_MAG_MIP_POINT,
+            d3d11::D3D11_FILTER_COMPARISON_MIN_MAG_MIP_LINEAR,
+            d3d11::D3D11_FILTER_COMPARISON_ANISOTROPIC,
+        ];
+        const ADDRESS_MODE: [d3d11::D3D11_TEXTURE_ADDRESS_MODE; 5] = [
+            d3d11::D3D11_TEXTURE_ADDRESS_WRAP,
+            d3d11::D3D11_TEXTURE_ADDRESS_MIRROR,
+            d3d11::D3D11_TEXTURE_ADDRESS_CLAMP,
+            d3d11::D3D11_TEXTURE_ADDRESS_BORDER,
+            d3d11::D3D11_TEXTURE_ADDRESS_MIRROR_ONCE
+        ];
+        const COMPARISON_FUNCS: [d3d11::D3D11_COMPARISON_FUNC; 8] = [
+            d3d11::D3D11_COMPARISON_NEVER,
+            d3d11::D3D11_COMPARISON_LESS,
+            d3d11::D3D11_COMPARISON_EQUAL,
+            d3d11::D3D11_COMPARISON_LESS_EQUAL,
+            d3d11::D3D11_COMPARISON_GREATER,
+            d3d11::D3D11_COMPARISON_NOT_EQUAL,
+            d3d11::D3D11_COMPARISON_GREATER_EQUAL,
+            d3d11::D3D11_COMPARISON_ALWAYS
+        ];
+        let desc = d3d11::D3D11_SAMPLER_DESC {
+            Filter: FILTERS[data.mode as usize],
+            AddressU: ADDRESS_MODE[data.address_u as usize],
+            AddressV: ADDRESS_MODE[data.address_v as usize],
+            AddressW: ADDRESS_MODE[data.address_w as usize],
+            MipLODBias: data.lod_bias,
+            MaxAnisotropy: data.max_anisotropy,
+            ComparisonFunc: COMPARISON_FUNCS[data.comparison as usize],
+            BorderColor: data.border_color.to_rgba(),
+            MinLOD: data.min_lod,
+            MaxLOD: data.max_lod
+        };
+
+        let mut samp = ComRef::<d3d11::ID3D11SamplerState>::null();
+        let hr = unsafe { (*self.device).CreateSamplerState(&desc, &mut samp.0 as *mut *mut _) };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a sampler state", hr))
+        }
+        else {
+            Ok(Sampler { data, handle: Box::new(samp) })
+        }
+    }
+
+    ///
+    /// Creates a new render target object from the display's swap chain
+    ///
+    fn create_render_target(&self, display: &Display) -> Result<RenderTarget, Error> {
+        let swap_chain = native::<ComRef<dxgi::IDXGISwapChain>>(&display.handle);
+
+        // get the texture resource from the swap chain
+        let mut tex = ComRef::<d3d11::ID3D11Resource>::null();
+        let hr = unsafe {
+            (*swap_chain.0).GetBuffer(
+                0,
+                &d3d11::IID_ID3D11Resource,
+                &mut tex.0 as *mut *mut _ as *mut *mut _)
+        };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("get the back buffer from the swap chain", hr));
+        }
+
+        // the view holds its own reference to the texture, so ours is released when tex drops
+        self.create_render_target_view(tex.0, display.width, display.height)
+    }
+
+    ///
+    /// Creates a render target backed by its own texture
+    ///
+    fn create_offscreen_render_target(&self, width: u32, height: u32) -> Result<RenderTarget, Error> {
+        let desc = d3d11::D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: d3d11::D3D11_USAGE_DEFAULT,
+            BindFlags: d3d11::D3D11_BIND_RENDER_TARGET,
+            CPUAccessFlags: 0,
+            MiscFlags: 0
+        };
+
+        let mut tex = ComRef::<d3d11::ID3D11Texture2D>::null();
+        let hr = unsafe {
+            (*self.device).CreateTexture2D(&desc, ptr::null(), &mut tex.0 as *mut *mut _)
+        };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("create an offscreen render target", hr));
+        }
+
+        self.create_render_target_view(tex.0 as _, width, height)
+    }
+
+    ///
+    /// Creates a new depth stencil texture and view
+    ///
+    fn create_depth_stencil_target(&self, format: DepthStencilFormat, width: u32, height: u32)
+        -> Result<DepthStencilTarget, Error> {
+
+        const TEXTURE_FORMATS: [dxgiformat::DXGI_FORMAT; 3] = [
+            dxgiformat::DXGI_FORMAT_D16_UNORM,
+            dxgiformat::DXGI_FORMAT_D24_UNORM_S8_UINT,
+            dxgiformat::DXGI_FORMAT_D32_FLOAT
+        ];
+
+        let desc = d3d11::D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: TEXTURE_FORMATS[format as usize],
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: d3d11::D3D11_USAGE_DEFAULT,
+            BindFlags: d3d11::D3D11_BIND_DEPTH_STENCIL,
+            CPUAccessFlags: 0,
+            MiscFlags: 0
+        };
+
+        let mut tex = ptr::null_mut::<d3d11::ID3D11Texture2D>();
+        let mut hr = unsafe {
+            (*self.device).CreateTexture2D(&desc, ptr::null(), &mut tex as *mut *mut _)
+        };
+
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("create a depth texture", hr));
+        }
+        let res = ComRef(tex as *mut d3d11::ID3D11Resource);
+
+        // create a depth stencil view for the texture
+        let mut dsv = ComRef::<d3d11::ID3D11DepthStencilView>::null();
+        hr = unsafe { (*self.device).CreateDepthStencilView(res.0, ptr::null(), &mut dsv.0) };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a depth stencil view", hr))
+        }
+        else {
+            // create DepthStencilTarget object with the texture and dsv
+            let handle = Box::new(D3D11DepthStencil { dsv, _res: res });
+            Ok(DepthStencilTarget { format, width, height, handle })
+        }
+    }
+
+    ///
+    /// Creates a new immutable vertex buffer
+    ///
+    fn create_vertex_buffer(&self, format: &VertexFormat, num_verts: u32, data: &[u8])
+        -> Result<VertexBuffer, Error> {
+
+        let desc = d3d11::D3D11_BUFFER_DESC {
+            ByteWidth: format.stride * num_verts,
+            Usage: d3d11::D3D11_USAGE_IMMUTABLE,
+            BindFlags: d3d11::D3D11_BIND_VERTEX_BUFFER,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+            StructureByteStride: format.stride
+        };
+        let buff = self.create_buffer(&desc, Some(data))?;
+
+        Ok(VertexBuffer { format: *format, num_verts, handle: Box::new(buff) })
+    }
+
+    ///
+    /// Creates a new immutable index buffer
+    ///
+    fn create_index_buffer(&self, format: IndexFormat, num_indices: u32, data: &[u8])
+        -> Result<IndexBuffer, Error> {
+
+        let desc = d3d11::D3D11_BUFFER_DESC {
+            ByteWidth: data.len() as _,
+            Usage: d3d11::D3D11_USAGE_IMMUTABLE,
+            BindFlags: d3d11::D3D11_BIND_INDEX_BUFFER,
+            CPUAccessFlags: 0,
+            MiscFlags: 0,
+            StructureByteStride: format.get_size() as _
+        };
+        let buff = self.create_buffer(&desc, Some(data))?;
+
+        Ok(IndexBuffer { format, num_indices, handle: Box::new(buff) })
+    }
+
+    ///
+    /// Creates a dynamic shader buffer
+    ///
+    fn create_constant_buffer(&self, size: usize) -> Result<ShaderBuffer, Error> {
+        // buffer sizes have to be aligned to 16b
+        let rounded_size = 16 * ((size + 15) / 16) as u32;
+
+        let desc = d3d11::D3D11_BUFFER_DESC {
+            ByteWidth: rounded_size,
+            Usage: d3d11::D3D11_USAGE_DYNAMIC,
+            BindFlags: d3d11::D3D11_BIND_CONSTANT_BUFFER,
+            CPUAccessFlags: d3d11::D3D11_CPU_ACCESS_WRITE,
+            MiscFlags: 0,
+            StructureByteStride: 0
+        };
+        let buff = self.create_buffer(&desc, None)?;
+
+        Ok(ShaderBuffer { size: rounded_size as usize, handle: Box::new(buff) })
+    }
+
+    ///
+    /// Creates a new shader resource view for the specified texture
+    ///
+    fn create_texture_shader_input(&self, tex: &Texture) -> Result<ShaderInput, Error> {
+        let res = native::<ComRef<d3d11::ID3D11Resource>>(&tex.handle);
+        let mut srv = ComRef::<d3d11::ID3D11ShaderResourceView>::null();
+        let hr = unsafe {
+            (*self.device).CreateShaderResourceView(res.0, ptr::null(), &mut srv.0 as *mut *mut _)
+        };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a shader resource view", hr))
+        }
+        else {
+            Ok(ShaderInput { handle: Box::new(srv) })
+        }
+    }
+
+    ///
+    /// Compiles HLSL from file with the D3D shader compiler
+    ///
+    fn compile_shader(&self, path: &Path, defines: &[(String, Option<String>)], entry_point: &str,
+        profile: ShaderType) -> Result<ShaderCode, Error> {
+
+        const SHADER_PROFILES: [&str; 2] = [
+            "vs_5_0",
+            "ps_5_0"
+        ];
+
+        let wfname = to_wstring(path.to_str().ok_or_else(|| Error::InvalidArgument(
+            format!("shader path {:?} isn't valid unicode", path)))?);
+        let mut tempstrs = Vec::with_capacity(defines.len() * 2);
+        let mut defs = Vec::with_capacity(defines.len() + 1);
+        for (name, optvalue) in defines {
+            if name.is_empty() {
+                continue;
+            }
+
+            tempstrs.push(CString::new(name.as_bytes()).expect("Invalid define name encountered"));
+            let mut new_define = d3dcommon::D3D_SHADER_MACRO {
+                Name: tempstrs.last().expect("Sanity check failed").as_ptr(),
+                Definition: ptr::null(),
+            };
+
+            if let Some(value) = optvalue {
+                tempstrs.push(CString::new(value.as_bytes()).expect("Invalid define value encountered"));
+                new_define.Definition = tempstrs.last().expect("Sanity check failed").as_ptr();
+            }
+
+            defs.push(new_define);
+        }
+
+        // the define list is terminated by an empty entry
+        defs.push(d3dcommon::D3D_SHADER_MACRO { Name: ptr::null(), Definition: ptr::null() });
+
+        let entry = CString::new(entry_point.as_bytes()).expect("Invalid entry point name");
+        let prof = CString::new(SHADER_PROFILES[profile as usize]).expect("Invalid profile string");
+        let mut code_blob = D3D11Blob(ComRef::null());
+        let mut error_blob = D3D11Blob(ComRef::null());
+        let hr = unsafe {
+            d3dcompiler::D3DCompileFromFile(
+                wfname.as_ptr(),
+                defs.as_ptr(),
+                d3dcompiler::D3D_COMPILE_STANDARD_FILE_INCLUDE,
+                entry.as_ptr(),
+                prof.as_ptr(),
+                d3dcompiler::D3DCOMPILE_OPTIMIZATION_LEVEL3,
+                0,
+                &mut (code_blob.0).0 as *mut *mut _,
+                &mut (error_blob.0).0 as *mut *mut _
+            )
+        };
+
+        if hr != winerror::S_OK {
+            // the error blob is missing when the compiler didn't get as far as parsing, e.g. when
+            // the file can't be opened
+            let diagnostics = error_blob.to_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("D3DCompileFromFile returned {:#010x}", hr));
+
+            Err(Error::ShaderCompile {
+                path: path.to_path_buf(),
+                entry_point: entry_point.to_string(),
+                diagnostics
+            })
+        }
+        else {
+            Ok(ShaderCode { shader_type: profile, handle: Box::new(code_blob) })
+        }
+    }
+
+    ///
+    /// Creates a shader for the specified blob
+    ///
+    fn create_shader(&self, code: &ShaderCode) -> Result<Shader, Error> {
+        let blob = native::<D3D11Blob>(&code.handle).to_slice()
+            .ok_or_else(|| Error::InvalidArgument("shader code is empty".to_string()))?;
+        let (hr, shader) = match code.shader_type {
+            ShaderType::Vertex => {
+                let mut vs = ComRef::<d3d11::ID3D11VertexShader>::null();
+                let hr = unsafe {
+                    (*self.device).CreateVertexShader(blob.as_ptr() as _, blob.len(),
+                        ptr::null_mut(), &mut vs.0 as *mut *mut _)
+                };
+                (hr, D3D11Shader::Vertex(vs))
+            }
+            ShaderType::Pixel => {
+                let mut ps = ComRef::<d3d11::ID3D11PixelShader>::null();
+                let hr = unsafe {
+                    (*self.device).CreatePixelShader(blob.as_ptr() as _, blob.len(),
+                        ptr::null_mut(), &mut ps.0 as *mut *mut _)
+                };
+                (hr, D3D11Shader::Pixel(ps))
+            }
+        };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create a shader", hr))
+        }
+        else {
+            Ok(Shader { shader_type: code.shader_type, handle: Box::new(shader) })
+        }
+    }
+
+    ///
+    /// Creates an input layout from the specified vertex format and vertex shader code
+    ///
+    fn create_input_layout(&self, format: &VertexFormat, vs_code: &ShaderCode)
+        -> Result<InputLayout, Error> {
+
+        const VERTEX_SEMANTIC_NAMES: [&str; 5] = [
+            "POSITION\0",
+            "NORMAL\0",
+            "TANGENT\0",
+            "BITANGENT\0",
+            "TEXCOORD\0",
+        ];
+
+        const VERTEX_INPUT_FORMATS: [dxgiformat::DXGI_FORMAT; 19] = [
+            dxgiformat::DXGI_FORMAT_R8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8G8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
+            dxgiformat::DXGI_FORMAT_R8_SNORM,
+            dxgiformat::DXGI_FORMAT_R8G8_SNORM,
+            dxgiformat::DXGI_FORMAT_R8G8B8A8_SNORM,
+            dxgiformat::DXGI_FORMAT_R16_UNORM,
+            dxgiformat::DXGI_FORMAT_R16G16_UNORM,
+            dxgiformat::DXGI_FORMAT_R16G16B16A16_UNORM,
+            dxgiformat::DXGI_FORMAT_R16_SNORM,
+            dxgiformat::DXGI_FORMAT_R16G16_SNORM,
+            dxgiformat::DXGI_FORMAT_R16G16B16A16_SNORM,
+            dxgiformat::DXGI_FORMAT_R16_FLOAT,
+            dxgiformat::DXGI_FORMAT_R16G16_FLOAT,
+            dxgiformat::DXGI_FORMAT_R16G16B16A16_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32G32_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32G32B32_FLOAT,
+            dxgiformat::DXGI_FORMAT_R32G32B32A32_FLOAT,
+        ];
+
+        const VERTEX_INPUT_CLASSIFICATIONS: [d3d11::D3D11_INPUT_CLASSIFICATION; 2] = [
+            d3d11::D3D11_INPUT_PER_VERTEX_DATA,
+            d3d11::D3D11_INPUT_PER_INSTANCE_DATA
+        ];
+
+        // create input element descriptors for each element in our format
+        let mut elements = Vec::with_capacity(format.num_inputs as usize);
+        for input in format.inputs.iter().take(format.num_inputs as usize) {
+            let semstr = VERTEX_SEMANTIC_NAMES[input.semantic as usize];
+            let e = d3d11::D3D11_INPUT_ELEMENT_DESC {
+                SemanticName: CStr::from_bytes_with_nul(semstr.as_bytes()).expect("Sanity check failed").as_ptr(),
+                SemanticIndex: input.semantic_index,
+                Format: VERTEX_INPUT_FORMATS[input.format as usize],
+                InputSlot: input.input_slot,
+                AlignedByteOffset: input.byte_offset,
+                InputSlotClass: VERTEX_INPUT_CLASSIFICATIONS[input.slot_class as usize],
+                InstanceDataStepRate: input.instance_step_rate
+            };
+
+            elements.push(e);
+        }
+
+        let blob = native::<D3D11Blob>(&vs_code.handle).to_slice()
+            .ok_or_else(|| Error::InvalidArgument("vertex shader code is empty".to_string()))?;
+        let mut layout = ComRef::<d3d11::ID3D11InputLayout>::null();
+        let hr = unsafe {
+            (*self.device).CreateInputLayout(
+                elements.as_ptr(),
+                elements.len() as _,
+                blob.as_ptr() as _,
+                blob.len(),
+                &mut layout.0 as *mut *mut _
+            )
+        };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("create an input layout", hr))
+        }
+        else {
+            Ok(InputLayout { format: *format, handle: Box::new(layout) })
+        }
+    }
+
+    ///
+    /// Maps the buffer, copies the data in and uploads the contents to the GPU
+    ///
+    fn set_buffer_data(&self, buffer: &ShaderBuffer, data: &[u8]) {
+        let buff = Self::buffer_ptr(buffer);
+        let mut map = d3d11::D3D11_MAPPED_SUBRESOURCE {
+            pData: ptr::null_mut(),
+            RowPitch: 0,
+            DepthPitch: 0
+        };
+
+        unsafe {
+            let hr = (*self.context).Map(buff as _, 0, d3d11::D3D11_MAP_WRITE_DISCARD, 0,
+                &mut map);
+            assert!(hr == winerror::S_OK && !map.pData.is_null(), "Failed to map a buffer!");
+
+            ptr::copy_nonoverlapping(data.as_ptr(), map.pData as *mut u8, data.len());
+
+            (*self.context).Unmap(buff as _, 0);
+        }
+    }
+
+    ///
+    /// Copies the render target to a staging texture and reads it back to the CPU
+    ///
+    fn read_render_target(&self, rt: &RenderTarget) -> Result<Vec<Color4>, Error> {
+        let rtv = native::<ComRef<d3d11::ID3D11RenderTargetView>>(&rt.handle);
+        let mut res = ComRef::<d3d11::ID3D11Resource>::null();
+        unsafe { (*rtv.0).GetResource(&mut res.0); }
+
+        let desc = d3d11::D3D11_TEXTURE2D_DESC {
+            Width: rt.width,
+            Height: rt.height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: d3d11::D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: d3d11::D3D11_CPU_ACCESS_READ,
+            MiscFlags: 0
+        };
+        let mut staging = ComRef::<d3d11::ID3D11Texture2D>::null();
+        let hr = unsafe {
+            (*self.device).CreateTexture2D(&desc, ptr::null(), &mut staging.0 as *mut *mut _)
+        };
+        if hr != winerror::S_OK {
+            return Err(Error::hresult("create a staging texture", hr));
+        }
+
+        let mut map = d3d11::D3D11_MAPPED_SUBRESOURCE {
+            pData: ptr::null_mut(),
+            RowPitch: 0,
+            DepthPitch: 0
+        };
+        let mut pixels = Vec::with_capacity((rt.width * rt.height) as usize);
+        unsafe {
+            (*self.context).CopyResource(staging.0 as _, res.0);
+
+            let hr = (*self.context).Map(staging.0 as _, 0, d3d11::D3D11_MAP_READ, 0, &mut map);
+            if hr != winerror::S_OK {
+                return Err(Error::hresult("map a staging texture", hr));
+            }
+
+            for y in 0..rt.height {
+                let row = (map.pData as *const u8).add((y * map.RowPitch) as usize) as *const Color4;
+                pixels.extend_from_slice(std::slice::from_raw_parts(row, rt.width as usize));
+            }
+
+            (*self.context).Unmap(staging.0 as _, 0);
+        }
+
+        Ok(pixels)
+    }
+
+    ///
+    /// Resizes the swap chain's buffers. Fails if any render target still references them.
+    ///
+    fn resize_display(&self, display: &Display, width: u32, height: u32) -> Result<(), Error> {
+        let swap_chain = native::<ComRef<dxgi::IDXGISwapChain>>(&display.handle);
+        let hr = unsafe {
+            (*swap_chain.0).ResizeBuffers(0, width, height, dxgiformat::DXGI_FORMAT_UNKNOWN, 0)
+        };
+
+        if hr != winerror::S_OK {
+            Err(Error::hresult("resize the swap chain buffers", hr))
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    ///
+    /// Presents the swap chain's back buffer
+    ///
+    fn present(&self, display: &Display) -> Result<(), Error> {
+        let swap_chain = native::<ComRef<dxgi::IDXGISwapChain>>(&display.handle);
+        let hr = unsafe { (*swap_chain.0).Present(1, 0) };
+
+        // status codes such as DXGI_STATUS_OCCLUDED are successes, only negative values are errors
+        if hr < 0 {
+            Err(Error::hresult("present the swap chain", hr))
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    fn clear_render_target(&self, rt: &RenderTarget, rgba: &Color4F) {
+        let rtv = native::<ComRef<d3d11::ID3D11RenderTargetView>>(&rt.handle);
+        let c = [rgba.r, rgba.g, rgba.b, rgba.a];
+        unsafe { (*self.context).ClearRenderTargetView(rtv.0, &c); }
+    }
+
+    fn clear_depth_stencil_target(&self, ds: &DepthStencilTarget, clear_depth: bool, depth: f32,
+        clear_stencil: bool, stencil: u8) {
+
+        let mut clear_flags = 0;
+        if clear_depth {
+            clear_flags |= d3d11::D3D11_CLEAR_DEPTH;
+        }
+        if clear_stencil {
+            clear_flags |= d3d11::D3D11_CLEAR_STENCIL;
+        }
+
+        let target = native::<D3D11DepthStencil>(&ds.handle);
+        unsafe { (*self.context).ClearDepthStencilView(target.dsv.0, clear_flags, depth, stencil); }
+    }
+
+    fn set_render_target(&self, rt: &RenderTarget, ds: Option<&DepthStencilTarget>) {
+        let rtv = native::<ComRef<d3d11::ID3D11RenderTargetView>>(&rt.handle);
+        let dsv = match ds {
+            Some(ds) => native::<D3D11DepthStencil>(&ds.handle).dsv.0,
+            None => ptr::null_mut()
+        };
+        unsafe { (*self.context).OMSetRenderTargets(1, &rtv.0, dsv); }
+    }
+
+    fn unbind_render_targets(&self) {
+        unsafe { (*self.context).OMSetRenderTargets(0, ptr::null_mut(), ptr::null_mut()); }
+    }
+
+    fn set_viewport(&self, x: f32, y: f32, w: f32, h: f32, min_z: f32, max_z: f32) {
+        let vp = d3d11::D3D11_VIEWPORT {
+            TopLeftX: x,
+            TopLeftY: y,
+            Width: w,
+            Height: h,
+            MinDepth: min_z,
+            MaxDepth: max_z
+        };
+        unsafe { (*self.context).RSSetViewports(1, &vp); }
+    }
+
+    fn set_primitive_topology(&self, topology: PrimitiveTopology) {
+        const PRIM_TOPOLOGIES: [d3d11::D3D11_PRIMITIVE_TOPOLOGY; 5] = [
+            d3dcommon::D3D_PRIMITIVE_TOPOLOGY_POINTLIST,
+            d3dcommon::D3D_PRIMITIVE_TOPOLOGY_LINELIST,
+            d3dcommon::D3D_PRIMITIVE_TOPOLOGY_LINESTRIP,
+            d3dcommon::D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+            d3dcommon::D3D_PRIMITIVE_TOPOLOGY_TRIANGLESTRIP
+        ];
+
+        unsafe { (*self.context).IASetPrimitiveTopology(PRIM_TOPOLOGIES[topology as usize]); }
+    }
+
+    fn set_input_layout(&self, layout: &InputLayout) {
+        let data = native::<ComRef<d3d11::ID3D11InputLayout>>(&layout.handle);
+        unsafe { (*self.context).IASetInputLayout(data.0); }
+    }
+
+    fn set_vertex_buffer(&self, vb: &VertexBuffer, slot: u32) {
+        let buff = native::<ComRef<d3d11::ID3D11Buffer>>(&vb.handle);
+        let offset: u32 = 0;
+        unsafe { (*self.context).IASetVertexBuffers(slot, 1, &buff.0, &vb.format.stride, &offset); }
+    }
+
+    fn set_index_buffer(&self, ib: &IndexBuffer) {
+        let buff = native::<ComRef<d3d11::ID3D11Buffer>>(&ib.handle);
+        let format = match ib.format {
+            IndexFormat::U16 => dxgiformat::DXGI_FORMAT_R16_UINT,
+            IndexFormat::U32 => dxgiformat::DXGI_FORMAT_R32_UINT
+        };
+        unsafe { (*self.context).IASetIndexBuffer(buff.0, format, 0) }
+    }
+
+    fn set_shader(&self, shader: &Shader) {
+        match native::<D3D11Shader>(&shader.handle) {
+            D3D11Shader::Vertex(vs) => unsafe { (*self.context).VSSetShader(vs.0, ptr::null(), 0) }
+            D3D11Shader::Pixel(ps) => unsafe { (*self.context).PSSetShader(ps.0, ptr::null(), 0) }
+        }
+    }
+
+    fn set_constant_buffer(&self, stage: ShaderType, slot: u32, buffer: &ShaderBuffer) {
+        let buff = Self::buffer_ptr(buffer);
+        match stage {
+            ShaderType::Vertex => unsafe { (*self.context).VSSetConstantBuffers(slot, 1, &buff) }
+            ShaderType::Pixel => unsafe { (*self.context).PSSetConstantBuffers(slot, 1, &buff) }
+        }
+    }
+
+    fn set_shader_input(&self, stage: ShaderType, slot: u32, input: &ShaderInput) {
+        let srv = native::<ComRef<d3d11::ID3D11ShaderResourceView>>(&input.handle);
+        match stage {
+            ShaderType::Vertex => unsafe { (*self.context).VSSetShaderResources(slot, 1, &srv.0) }
+            ShaderType::Pixel => unsafe { (*self.context).PSSetShaderResources(slot, 1, &srv.0) }
+        }
+    }
+
+    fn set_sampler(&self, stage: ShaderType, slot: u32, sampler: &Sampler) {
+        let samp = native::<ComRef<d3d11::ID3D11SamplerState>>(&sampler.handle);
+        match stage {
+            ShaderType::Vertex => unsafe { (*self.context).VSSetSamplers(slot, 1, &samp.0) }
+            ShaderType::Pixel => unsafe { (*self.context).PSSetSamplers(slot, 1, &samp.0) }
+        }
+    }
+
+    fn draw(&self, num_verts: u32, start_vert: u32) {
+        unsafe { (*self.context).Draw(num_verts, start_vert); }
+    }
+
+    fn draw_indexed(&self, num_indices: u32, start_index: u32) {
+        unsafe { (*self.context).DrawIndexed(num_indices, start_index, 0); }
+    }
+}
@@ -0,0 +1,452 @@
+// external refs
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+mod rasterizer;
+mod shaders;
+mod surface;
+
+pub use self::rasterizer::Viewport;
+pub use self::shaders::*;
+pub use self::surface::*;
+use self::rasterizer::{DrawContext, VertexStream};
+
+type ColorSurface = Rc<RefCell<Surface<Vector4F>>>;
+type DepthSurface = Rc<RefCell<Surface<f32>>>;
+
+///
+/// Everything currently bound to the software pipeline
+///
+struct PipelineState {
+    color_target: Option<ColorSurface>,
+    depth_target: Option<DepthSurface>,
+    viewport: Viewport,
+    topology: PrimitiveTopology,
+    layout: Option<VertexFormat>,
+    vertex_buffers: Vec<Option<VertexStream>>,
+    index_buffer: Option<Rc<Vec<u32>>>,
+    vs: Option<Rc<dyn ShaderProgram>>,
+    ps: Option<Rc<dyn ShaderProgram>>,
+    stages: [StageBindings; 2]
+}
+
+///
+/// Places a value at the specified slot, growing the slot array as needed
+///
+fn bind_slot<T>(slots: &mut Vec<Option<T>>, slot: u32, value: T) {
+    let slot = slot as usize;
+    if slots.len() <= slot {
+        slots.resize_with(slot + 1, || None);
+    }
+    slots[slot] = Some(value);
+}
+
+///
+/// Graphics backend that executes draw calls on the CPU into in-memory color and depth buffers
+///
+pub struct SoftwareBackend {
+    state: RefCell<PipelineState>
+}
+
+impl SoftwareBackend {
+    ///
+    /// Creates a new software backend with nothing bound to the pipeline
+    ///
+    pub fn new() -> Self {
+        let state = PipelineState {
+            color_target: None,
+            depth_target: None,
+            viewport: Viewport::default(),
+            topology: PrimitiveTopology::TriangleList,
+            layout: None,
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            vs: None,
+            ps: None,
+            stages: [StageBindings::default(), StageBindings::default()]
+        };
+
+        Self { state: RefCell::new(state) }
+    }
+
+    ///
+    /// Runs the bound pipeline over the specified vertices. Like a GPU, nothing is drawn if the
+    /// input layout or a shader is missing, which is a bug in the caller.
+    ///
+    fn execute(&self, vertex_ids: &[u32]) {
+        let state = self.state.borrow();
+        let (layout, vs, ps) = match (&state.layout, &state.vs, &state.ps) {
+            (Some(layout), Some(vs), Some(ps)) => (layout, vs, ps),
+            _ => {
+                debug_assert!(false, "input layout and shaders must be bound before drawing");
+                return;
+            }
+        };
+
+        let mut color = state.color_target.as_ref().map(|c| c.borrow_mut());
+        let mut depth = state.depth_target.as_ref().map(|d| d.borrow_mut());
+        let mut ctx = DrawContext {
+            color: color.as_deref_mut(),
+            depth: depth.as_deref_mut(),
+            viewport: state.viewport,
+            topology: state.topology,
+            layout,
+            vertex_buffers: &state.vertex_buffers,
+            vs: vs.as_ref(),
+            ps: ps.as_ref(),
+            vs_resources: ShaderResources::new(&state.stages[ShaderType::Vertex as usize]),
+            ps_resources: ShaderResources::new(&state.stages[ShaderType::Pixel as usize])
+        };
+
+        ctx.draw(vertex_ids);
+    }
+}
+
+impl Default for SoftwareBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for SoftwareBackend {
+    fn get_type(&self) -> BackendType {
+        BackendType::Software
+    }
+
+    ///
+    /// Creates an in-memory back buffer. There's no window to show it in, so the handle is unused.
+    ///
+    fn create_display(&self, _window_handle: u64, width: u32, height: u32) -> Result<Display, Error> {
+        let surface: ColorSurface = Rc::new(RefCell::new(Surface::new(width, height, zero())));
+        Ok(Display { width, height, handle: Box::new(surface) })
+    }
+
+    ///
+    /// Decodes the pixel data so it can be sampled. Only the top mip is kept.
+    ///
+    fn create_texture2d(&self, format: TextureFormat, width: u32, height: u32, _mips: u32,
+        _generate_mips: bool, _render_target: bool, _shader_resource: bool, pixel_data: &[u8])
+        -> Result<Texture, Error> {
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidArgument(format!("can't create a {}x{} texture", width,
+                height)));
+        }
+
+        let tex = Rc::new(SoftwareTexture::new(format, width, height, pixel_data));
+        Ok(Texture { format, width, height, depth: 1, handle: Box::new(tex) })
+    }
+
+    fn create_sampler(&self, data: SamplerData) -> Result<Sampler, Error> {
+        Ok(Sampler { data, handle: Box::new(()) })
+    }
+
+    ///
+    /// Creates a render target that draws into the display's back buffer
+    ///
+    fn create_render_target(&self, display: &Display) -> Result<RenderTarget, Error> {
+        let surface = native::<ColorSurface>(&display.handle).clone();
+        Ok(RenderTarget { width: display.width, height: display.height, handle: Box::new(surface) })
+    }
+
+    fn create_offscreen_render_target(&self, width: u32, height: u32) -> Result<RenderTarget, Error> {
+        let surface: ColorSurface = Rc::new(RefCell::new(Surface::new(width, height, zero())));
+        Ok(RenderTarget { width, height, handle: Box::new(surface) })
+    }
+
+    ///
+    /// Creates a depth buffer. Depth is always stored as 32-bit float regardless of the format.
+    ///
+    fn create_depth_stencil_target(&self, format: DepthStencilFormat, width: u32, height: u32)
+        -> Result<DepthStencilTarget, Error> {
+
+        let surface: DepthSurface = Rc::new(RefCell::new(Surface::new(width, height, 1.0f32)));
+        Ok(DepthStencilTarget { format, width, height, handle: Box::new(surface) })
+    }
+
+    fn create_vertex_buffer(&self, format: &VertexFormat, num_verts: u32, data: &[u8])
+        -> Result<VertexBuffer, Error> {
+
+        let data = Rc::new(data.to_vec());
+        Ok(VertexBuffer { format: *format, num_verts, handle: Box::new(data) })
+    }
+
+    fn create_index_buffer(&self, format: IndexFormat, num_indices: u32, data: &[u8])
+        -> Result<IndexBuffer, Error> {
+
+        // indices are widened up front so draws don't need to know the format
+        let indices: Vec<u32> = match format {
+            IndexFormat::U16 => data.chunks_exact(2)
+                .map(|b| u16::from_ne_bytes([b[0], b[1]]) as u32)
+                .collect(),
+            IndexFormat::U32 => data.chunks_exact(4)
+                .map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        };
+        Ok(IndexBuffer { format, num_indices, handle: Box::new(Rc::new(indices)) })
+    }
+
+    fn create_constant_buffer(&self, size: usize) -> Result<ShaderBuffer, Error> {
+        let data = Rc::new(RefCell::new(vec![0u8; size]));
+        Ok(ShaderBuffer { size, handle: Box::new(data) })
+    }
+
+    fn create_texture_shader_input(&self, tex: &Texture) -> Result<ShaderInput, Error> {
+        let tex = native::<Rc<SoftwareTexture>>(&tex.handle).clone();
+        Ok(ShaderInput { handle: Box::new(tex) })
+    }
+
+    ///
+    /// Resolves the shader file to its built-in Rust port. HLSL can't be executed on the CPU,
+    /// so the file only has to exist and the defines are ignored.
+    ///
+    fn compile_shader(&self, path: &Path, _defines: &[(String, Option<String>)], entry_point: &str,
+        profile: ShaderType) -> Result<ShaderCode, Error> {
+
+        if !path.is_file() {
+            return Err(Error::MissingResource { what: "shader file".to_string(),
+                path: Some(path.to_path_buf()) });
+        }
+
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        match find_builtin_program(name) {
+            Some(program) => Ok(ShaderCode { shader_type: profile, handle: Box::new(program) }),
+            None => Err(Error::ShaderCompile {
+                path: path.to_path_buf(),
+                entry_point: entry_point.to_string(),
+                diagnostics: "no software implementation of the shader".to_string()
+            })
+        }
+    }
+
+    fn create_shader(&self, code: &ShaderCode) -> Result<Shader, Error> {
+        let program = native::<Rc<dyn ShaderProgram>>(&code.handle).clone();
+        Ok(Shader { shader_type: code.shader_type, handle: Box::new(program) })
+    }
+
+    fn create_input_layout(&self, format: &VertexFormat, _vs_code: &ShaderCode)
+        -> Result<InputLayout, Error> {
+
+        Ok(InputLayout { format: *format, handle: Box::new(()) })
+    }
+
+    fn set_buffer_data(&self, buffer: &ShaderBuffer, data: &[u8]) {
+        let mut contents = native::<Rc<RefCell<Vec<u8>>>>(&buffer.handle).borrow_mut();
+        contents[..data.len()].copy_from_slice(data);
+    }
+
+    ///
+    /// Reads the render target back, encoding to sRGB like the D3D11 back buffers do
+    ///
+    fn read_render_target(&self, rt: &RenderTarget) -> Result<Vec<Color4>, Error> {
+        let surface = native::<ColorSurface>(&rt.handle).borrow();
+        let encode = |c: f32| (linear_to_srgb(c.clamp(0.0f32, 1.0f32)) * 255.0f32).round() as u8;
+        let unorm = |c: f32| (c.clamp(0.0f32, 1.0f32) * 255.0f32).round() as u8;
+
+        Ok(surface.data.iter()
+            .map(|c| Color4::from_rgba(encode(c.x), encode(c.y), encode(c.z), unorm(c.w)))
+            .collect())
+    }
+
+    ///
+    /// Reallocates the display surface. Like a swap chain, this fails while a render target still
+    /// references the old surface.
+    ///
+    fn resize_display(&self, display: &Display, width: u32, height: u32) -> Result<(), Error> {
+        let surface = native::<ColorSurface>(&display.handle);
+        if Rc::strong_count(surface) > 1 {
+            return Err(Error::device(
+                "resize the display while a render target still references its surface"));
+        }
+
+        *surface.borrow_mut() = Surface::new(width, height, zero());
+        Ok(())
+    }
+
+    fn present(&self, _display: &Display) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn clear_render_target(&self, rt: &RenderTarget, rgba: &Color4F) {
+        let mut surface = native::<ColorSurface>(&rt.handle).borrow_mut();
+        surface.clear(Vector4F::new(rgba.r, rgba.g, rgba.b, rgba.a));
+    }
+
+    fn clear_depth_stencil_target(&self, ds: &DepthStencilTarget, clear_depth: bool, depth: f32,
+        _clear_stencil: bool, _stencil: u8) {
+
+        if clear_depth {
+            native::<DepthSurface>(&ds.handle).borrow_mut().clear(depth);
+        }
+    }
+
+    fn set_render_target(&self, rt: &RenderTarget, ds: Option<&DepthStencilTarget>) {
+        let mut state = self.state.borrow_mut();
+        state.color_target = Some(native::<ColorSurface>(&rt.handle).clone());
+        state.depth_target = ds.map(|ds| native::<DepthSurface>(&ds.handle).clone());
+    }
+
+    fn unbind_render_targets(&self) {
+        let mut state = self.state.borrow_mut();
+        state.color_target = None;
+        state.depth_target = None;
+    }
+
+    fn set_viewport(&self, x: f32, y: f32, w: f32, h: f32, min_z: f32, max_z: f32) {
+        self.state.borrow_mut().viewport = Viewport { x, y, w, h, min_z, max_z };
+    }
+
+    fn set_primitive_topology(&self, topology: PrimitiveTopology) {
+        self.state.borrow_mut().topology = topology;
+    }
+
+    fn set_input_layout(&self, layout: &InputLayout) {
+        self.state.borrow_mut().layout = Some(layout.format);
+    }
+
+    fn set_vertex_buffer(&self, vb: &VertexBuffer, slot: u32) {
+        let data = native::<Rc<Vec<u8>>>(&vb.handle).clone();
+        bind_slot(&mut self.state.borrow_mut().vertex_buffers, slot, (data, vb.format.stride));
+    }
+
+    fn set_index_buffer(&self, ib: &IndexBuffer) {
+        self.state.borrow_mut().index_buffer = Some(native::<Rc<Vec<u32>>>(&ib.handle).clone());
+    }
+
+    fn set_shader(&self, shader: &Shader) {
+        let program = native::<Rc<dyn ShaderProgram>>(&shader.handle).clone();
+        let mut state = self.state.borrow_mut();
+        match shader.shader_type {
+            ShaderType::Vertex => state.vs = Some(program),
+            ShaderType::Pixel => state.ps = Some(program)
+        }
+    }
+
+    fn set_constant_buffer(&self, stage: ShaderType, slot: u32, buffer: &ShaderBuffer) {
+        let data = native::<Rc<RefCell<Vec<u8>>>>(&buffer.handle).clone();
+        bind_slot(&mut self.state.borrow_mut().stages[stage as usize].constants, slot, data);
+    }
+
+    fn set_shader_input(&self, stage: ShaderType, slot: u32, input: &ShaderInput) {
+        let tex = native::<Rc<SoftwareTexture>>(&input.handle).clone();
+        bind_slot(&mut self.state.borrow_mut().stages[stage as usize].inputs, slot, tex);
+    }
+
+    fn set_sampler(&self, stage: ShaderType, slot: u32, sampler: &Sampler) {
+        bind_slot(&mut self.state.borrow_mut().stages[stage as usize].samplers, slot, sampler.data);
+    }
+
+    fn draw(&self, num_verts: u32, start_vert: u32) {
+        let ids: Vec<u32> = (start_vert..(start_vert + num_verts)).collect();
+        self.execute(&ids);
+    }
+
+    fn draw_indexed(&self, num_indices: u32, start_index: u32) {
+        let ids = match &self.state.borrow().index_buffer {
+            Some(ib) => {
+                let start = (start_index as usize).min(ib.len());
+                let end = (start + num_indices as usize).min(ib.len());
+                ib[start..end].to_vec()
+            }
+            None => return
+        };
+
+        self.execute(&ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::test_fixtures::*;
+
+    ///
+    /// Renders the vertices as a single model and returns the resulting pixels
+    ///
+    fn render(verts: &[MeshVertex], indices: &[u32]) -> Vec<Color4> {
+        let (gfx, rt_state, _cbuff) = setup();
+        let draws = vec![DrawData {
+            start_index: 0,
+            num_tris: (indices.len() / 3) as u32,
+            material_idx: 0
+        }];
+        let model = Model::new(&gfx, verts, indices, draws, vec![object_material(&gfx)], Vec::new())
+            .unwrap();
+
+        rt_state.begin(&gfx);
+        model.draw(&gfx);
+        rt_state.end(&gfx);
+
+        gfx.read_render_target(rt_state.get_render_target()).unwrap()
+    }
+
+    #[test]
+    fn clear_and_read_back() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let rt = gfx.create_offscreen_render_target(4, 2).unwrap();
+        gfx.clear_render_target(&rt, &Color4F::red());
+
+        let pixels = gfx.read_render_target(&rt).unwrap();
+        assert_eq!(pixels.len(), 8);
+        assert!(pixels.iter().all(|p| *p == Color4::red()));
+    }
+
+    #[test]
+    fn draws_clockwise_triangles() {
+        // a single triangle covering the whole viewport
+        let verts = [
+            vert(-1.0f32, -1.0f32, 0.5f32, Color4::green()),
+            vert(-1.0f32, 3.0f32, 0.5f32, Color4::green()),
+            vert(3.0f32, -1.0f32, 0.5f32, Color4::green())
+        ];
+        let pixels = render(&verts, &[0, 1, 2]);
+        assert!(pixels.iter().all(|p| *p == Color4::green()));
+    }
+
+    #[test]
+    fn culls_counter_clockwise_triangles() {
+        let verts = [
+            vert(-1.0f32, -1.0f32, 0.5f32, Color4::green()),
+            vert(-1.0f32, 3.0f32, 0.5f32, Color4::green()),
+            vert(3.0f32, -1.0f32, 0.5f32, Color4::green())
+        ];
+        let pixels = render(&verts, &[0, 2, 1]);
+        assert!(pixels.iter().all(|p| *p == Color4::black()));
+    }
+
+    #[test]
+    fn nearer_triangles_win_depth_test() {
+        let tri = |z: f32, color: Color4| [
+            vert(-1.0f32, -1.0f32, z, color),
+            vert(-1.0f32, 3.0f32, z, color),
+            vert(3.0f32, -1.0f32, z, color)
+        ];
+        let mut verts = tri(0.25f32, Color4::red()).to_vec();
+        verts.extend_from_slice(&tri(0.75f32, Color4::blue()));
+
+        // draw the near triangle first so the far one has to be rejected
+        let pixels = render(&verts, &[0, 1, 2, 3, 4, 5]);
+        assert!(pixels.iter().all(|p| *p == Color4::red()));
+    }
+
+    #[test]
+    fn clips_triangles_to_near_plane() {
+        // the left half of the quad is in front of the near plane
+        let verts = [
+            vert(-1.0f32, -1.0f32, -0.5f32, Color4::white()),
+            vert(-1.0f32, 1.0f32, -0.5f32, Color4::white()),
+            vert(1.0f32, 1.0f32, 0.5f32, Color4::white()),
+            vert(1.0f32, -1.0f32, 0.5f32, Color4::white())
+        ];
+        let pixels = render(&verts, &[0, 1, 2, 0, 2, 3]);
+        let row = &pixels[(SIZE * SIZE / 2) as usize..][..SIZE as usize];
+        assert!(row[..(SIZE / 2) as usize].iter().all(|p| *p == Color4::black()));
+        assert!(row[(SIZE / 2) as usize..].iter().all(|p| *p == Color4::white()));
+    }
+}
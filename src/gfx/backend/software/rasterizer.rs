@@ -0,0 +1,363 @@
+// external refs
+use std::rc::Rc;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+use super::shaders::*;
+use super::surface::Surface;
+
+///
+/// The region of the render target that normalized device coordinates map onto
+///
+#[derive(Debug, Copy, Clone)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+    pub min_z: f32,
+    pub max_z: f32
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self { x: 0.0f32, y: 0.0f32, w: 0.0f32, h: 0.0f32, min_z: 0.0f32, max_z: 1.0f32 }
+    }
+}
+
+///
+/// Vertex buffer data bound to an input slot along with its stride
+///
+pub type VertexStream = (Rc<Vec<u8>>, u32);
+
+///
+/// Output of the vertex program for a single vertex
+///
+#[derive(Copy, Clone)]
+struct ShadedVertex {
+    pos: Vector4F,
+    varyings: Varyings
+}
+
+impl ShadedVertex {
+    ///
+    /// Linearly interpolates between two clip space vertices
+    ///
+    fn lerp(&self, other: &ShadedVertex, t: f32) -> ShadedVertex {
+        let mut varyings = [0.0f32; MAX_VARYINGS];
+        for (i, v) in varyings.iter_mut().enumerate() {
+            *v = self.varyings[i] + (other.varyings[i] - self.varyings[i]) * t;
+        }
+
+        ShadedVertex { pos: self.pos + (other.pos - self.pos) * t, varyings }
+    }
+}
+
+///
+/// A vertex after the perspective divide and viewport transform
+///
+#[derive(Copy, Clone)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    varyings: Varyings
+}
+
+///
+/// Everything bound to the pipeline that a draw call reads from or writes to
+///
+pub struct DrawContext<'a> {
+    pub color: Option<&'a mut Surface<Vector4F>>,
+    pub depth: Option<&'a mut Surface<f32>>,
+    pub viewport: Viewport,
+    pub topology: PrimitiveTopology,
+    pub layout: &'a VertexFormat,
+    pub vertex_buffers: &'a [Option<VertexStream>],
+    pub vs: &'a dyn ShaderProgram,
+    pub ps: &'a dyn ShaderProgram,
+    pub vs_resources: ShaderResources<'a>,
+    pub ps_resources: ShaderResources<'a>
+}
+
+impl<'a> DrawContext<'a> {
+    ///
+    /// Assembles, shades and rasterizes the primitives for the specified vertices
+    ///
+    pub fn draw(&mut self, vertex_ids: &[u32]) {
+        let shaded: Vec<ShadedVertex> = vertex_ids.iter().map(|id| self.shade_vertex(*id)).collect();
+
+        match self.topology {
+            PrimitiveTopology::TriangleList => {
+                for tri in shaded.chunks_exact(3) {
+                    self.draw_triangle(&tri[0], &tri[1], &tri[2]);
+                }
+            }
+            PrimitiveTopology::TriangleStrip => {
+                for (i, tri) in shaded.windows(3).enumerate() {
+                    // every other triangle in a strip is flipped to keep a consistent winding
+                    if i % 2 == 0 {
+                        self.draw_triangle(&tri[0], &tri[1], &tri[2]);
+                    }
+                    else {
+                        self.draw_triangle(&tri[1], &tri[0], &tri[2]);
+                    }
+                }
+            }
+            _ => {
+                // point and line rasterization isn't supported by the software backend yet
+            }
+        }
+    }
+
+    ///
+    /// Fetches a vertex from the bound vertex buffers and runs the vertex program on it
+    ///
+    fn shade_vertex(&self, id: u32) -> ShadedVertex {
+        let mut input = VertexInput::new();
+        for elem in self.layout.inputs.iter().take(self.layout.num_inputs as usize) {
+            let value = match self.vertex_buffers.get(elem.input_slot as usize) {
+                Some(Some((data, stride))) => {
+                    let start = (id * stride + elem.byte_offset) as usize;
+                    data.get(start..).map(|bytes| decode_element(elem.format, bytes))
+                }
+                _ => None
+            };
+            input.push(elem.semantic, elem.semantic_index,
+                value.unwrap_or_else(|| Vector4F::new(0.0f32, 0.0f32, 0.0f32, 1.0f32)));
+        }
+
+        let mut varyings = [0.0f32; MAX_VARYINGS];
+        let pos = self.vs.vertex(&input, &self.vs_resources, &mut varyings);
+
+        ShadedVertex { pos, varyings }
+    }
+
+    ///
+    /// Clips a triangle against the near and far planes and rasterizes the pieces
+    ///
+    fn draw_triangle(&mut self, a: &ShadedVertex, b: &ShadedVertex, c: &ShadedVertex) {
+        const MIN_W: f32 = 1.0e-5f32;
+        let planes: [fn(&Vector4F) -> f32; 3] = [
+            |p| p.z,
+            |p| p.w - p.z,
+            |p| p.w - MIN_W
+        ];
+
+        // Sutherland-Hodgman clipping in homogeneous clip space
+        let mut poly = vec![*a, *b, *c];
+        for plane in &planes {
+            if poly.is_empty() {
+                return;
+            }
+
+            let mut clipped = Vec::with_capacity(poly.len() + 1);
+            for i in 0..poly.len() {
+                let cur = &poly[i];
+                let next = &poly[(i + 1) % poly.len()];
+                let dc = plane(&cur.pos);
+                let dn = plane(&next.pos);
+
+                if dc >= 0.0f32 {
+                    clipped.push(*cur);
+                }
+                if (dc >= 0.0f32) != (dn >= 0.0f32) {
+                    clipped.push(cur.lerp(next, dc / (dc - dn)));
+                }
+            }
+            poly = clipped;
+        }
+
+        if poly.len() < 3 {
+            return;
+        }
+
+        let screen: Vec<ScreenVertex> = poly.iter().map(|v| self.to_screen(v)).collect();
+        for i in 1..(screen.len() - 1) {
+            self.rasterize(&screen[0], &screen[i], &screen[i + 1]);
+        }
+    }
+
+    ///
+    /// Applies the perspective divide and viewport transform
+    ///
+    fn to_screen(&self, v: &ShadedVertex) -> ScreenVertex {
+        let vp = &self.viewport;
+        let inv_w = 1.0f32 / v.pos.w;
+        let ndc = v.pos.xyz() * inv_w;
+
+        let mut varyings = v.varyings;
+        for val in varyings.iter_mut() {
+            *val *= inv_w;
+        }
+
+        ScreenVertex {
+            x: vp.x + (ndc.x + 1.0f32) * 0.5f32 * vp.w,
+            y: vp.y + (1.0f32 - ndc.y) * 0.5f32 * vp.h,
+            z: vp.min_z + ndc.z * (vp.max_z - vp.min_z),
+            inv_w,
+            varyings
+        }
+    }
+
+    ///
+    /// Fills the pixels covered by a screen space triangle
+    ///
+    fn rasterize(&mut self, v0: &ScreenVertex, v1: &ScreenVertex, v2: &ScreenVertex) {
+        // clockwise triangles are front facing and back faces are culled, matching the D3D
+        // default rasterizer state
+        let area = edge(v0, v1, v2.x, v2.y);
+        if area <= 0.0f32 {
+            return;
+        }
+
+        let (target_w, target_h) = match (&self.color, &self.depth) {
+            (Some(c), _) => (c.width, c.height),
+            (None, Some(d)) => (d.width, d.height),
+            (None, None) => return
+        };
+
+        let vp = &self.viewport;
+        let min_x = v0.x.min(v1.x).min(v2.x).max(vp.x).max(0.0f32).floor() as u32;
+        let min_y = v0.y.min(v1.y).min(v2.y).max(vp.y).max(0.0f32).floor() as u32;
+        let max_x = v0.x.max(v1.x).max(v2.x).min(vp.x + vp.w).min(target_w as f32).ceil() as u32;
+        let max_y = v0.y.max(v1.y).max(v2.y).min(vp.y + vp.h).min(target_h as f32).ceil() as u32;
+
+        // edges opposite each vertex and whether they own the pixels lying exactly on them
+        let edges = [(v1, v2), (v2, v0), (v0, v1)];
+        let top_left = [is_top_left(v1, v2), is_top_left(v2, v0), is_top_left(v0, v1)];
+        let min_depth = vp.min_z.min(vp.max_z);
+        let max_depth = vp.min_z.max(vp.max_z);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let px = x as f32 + 0.5f32;
+                let py = y as f32 + 0.5f32;
+
+                let mut bary = [0.0f32; 3];
+                let mut inside = true;
+                for i in 0..3 {
+                    let e = edge(edges[i].0, edges[i].1, px, py);
+                    if e < 0.0f32 || (e == 0.0f32 && !top_left[i]) {
+                        inside = false;
+                        break;
+                    }
+                    bary[i] = e / area;
+                }
+                if !inside {
+                    continue;
+                }
+
+                // depth is affine in screen space
+                let z = (bary[0] * v0.z + bary[1] * v1.z + bary[2] * v2.z)
+                    .max(min_depth).min(max_depth);
+                if let Some(depth) = &self.depth {
+                    if z >= depth.get(x, y) {
+                        continue;
+                    }
+                }
+
+                // perspective correct interpolation of the varyings
+                let inv_w = bary[0] * v0.inv_w + bary[1] * v1.inv_w + bary[2] * v2.inv_w;
+                let mut varyings = [0.0f32; MAX_VARYINGS];
+                for (i, val) in varyings.iter_mut().enumerate() {
+                    *val = (bary[0] * v0.varyings[i] + bary[1] * v1.varyings[i]
+                        + bary[2] * v2.varyings[i]) / inv_w;
+                }
+
+                let color = self.ps.pixel(&varyings, &self.ps_resources);
+
+                if let Some(depth) = &mut self.depth {
+                    depth.set(x, y, z);
+                }
+                if let Some(target) = &mut self.color {
+                    target.set(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+///
+/// Edge function of point (x, y) relative to the edge from a to b. Positive on the inside of
+/// a clockwise triangle.
+///
+#[inline] fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+///
+/// Determines whether an edge of a clockwise triangle is a top or left edge
+///
+#[inline] fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+
+    (dy == 0.0f32 && dx > 0.0f32) || dy < 0.0f32
+}
+
+///
+/// Decodes a single vertex element. Missing components default to (0, 0, 0, 1).
+///
+pub fn decode_element(format: FormatType, bytes: &[u8]) -> Vector4F {
+    let (count, size) = match format {
+        FormatType::R8UNorm | FormatType::R8SNorm => (1, 1),
+        FormatType::R8G8UNorm | FormatType::R8G8SNorm => (2, 1),
+        FormatType::R8G8B8A8UNorm | FormatType::R8G8B8A8SNorm => (4, 1),
+        FormatType::R16UNorm | FormatType::R16SNorm | FormatType::R16Float => (1, 2),
+        FormatType::R16G16UNorm | FormatType::R16G16SNorm | FormatType::R16G16Float => (2, 2),
+        FormatType::R16G16B16A16UNorm | FormatType::R16G16B16A16SNorm
+            | FormatType::R16G16B16A16Float => (4, 2),
+        FormatType::R32Float => (1, 4),
+        FormatType::R32G32Float => (2, 4),
+        FormatType::R32G32B32Float => (3, 4),
+        FormatType::R32G32B32A32Float => (4, 4),
+        FormatType::Invalid | FormatType::NumFormatTypes => (0, 0)
+    };
+
+    let mut v = Vector4F::new(0.0f32, 0.0f32, 0.0f32, 1.0f32);
+    if bytes.len() < count * size {
+        return v;
+    }
+
+    for i in 0..count {
+        let b = &bytes[(i * size)..((i + 1) * size)];
+        v[i] = match format {
+            FormatType::R8UNorm | FormatType::R8G8UNorm | FormatType::R8G8B8A8UNorm => {
+                b[0] as f32 / 255.0f32
+            }
+            FormatType::R8SNorm | FormatType::R8G8SNorm | FormatType::R8G8B8A8SNorm => {
+                (b[0] as i8 as f32 / 127.0f32).max(-1.0f32)
+            }
+            FormatType::R16UNorm | FormatType::R16G16UNorm | FormatType::R16G16B16A16UNorm => {
+                u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0f32
+            }
+            FormatType::R16SNorm | FormatType::R16G16SNorm | FormatType::R16G16B16A16SNorm => {
+                (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0f32).max(-1.0f32)
+            }
+            FormatType::R16Float | FormatType::R16G16Float | FormatType::R16G16B16A16Float => {
+                half_to_f32(u16::from_le_bytes([b[0], b[1]]))
+            }
+            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]])
+        };
+    }
+
+    v
+}
+
+///
+/// Converts an IEEE 754 half precision float to single precision
+///
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if (h & 0x8000) != 0 { -1.0f32 } else { 1.0f32 };
+    let exp = ((h >> 10) & 0x1F) as i32;
+    let mant = (h & 0x3FF) as f32;
+
+    match exp {
+        0 => sign * mant * 2.0f32.powi(-24),
+        31 => if mant == 0.0f32 { sign * f32::INFINITY } else { f32::NAN },
+        _ => sign * (1.0f32 + mant / 1024.0f32) * 2.0f32.powi(exp - 15)
+    }
+}
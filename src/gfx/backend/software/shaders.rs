@@ -0,0 +1,293 @@
+// external refs
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+use super::surface::SoftwareTexture;
+
+///
+/// Number of interpolated values a vertex program can pass to a pixel program
+///
+pub const MAX_VARYINGS: usize = 16;
+
+///
+/// Values interpolated across a primitive between the vertex and pixel programs
+///
+pub type Varyings = [f32; MAX_VARYINGS];
+
+///
+/// The decoded attributes of a single vertex
+///
+pub struct VertexInput {
+    elements: [(SemanticType, u32, Vector4F); MAX_INPUTS],
+    count: usize
+}
+
+impl VertexInput {
+    ///
+    /// Creates an empty vertex input
+    ///
+    pub fn new() -> Self {
+        Self { elements: [(SemanticType::Invalid, 0, Vector4F::zeros()); MAX_INPUTS], count: 0 }
+    }
+
+    ///
+    /// Adds a decoded attribute to the vertex
+    ///
+    pub fn push(&mut self, semantic: SemanticType, index: u32, value: Vector4F) {
+        self.elements[self.count] = (semantic, index, value);
+        self.count += 1;
+    }
+
+    ///
+    /// Gets the attribute with the specified semantic, or (0, 0, 0, 1) if the layout lacks it
+    ///
+    pub fn get(&self, semantic: SemanticType, index: u32) -> Vector4F {
+        self.elements[..self.count].iter()
+            .find(|(s, i, _)| *s == semantic && *i == index)
+            .map(|(_, _, v)| *v)
+            .unwrap_or_else(|| Vector4F::new(0.0f32, 0.0f32, 0.0f32, 1.0f32))
+    }
+}
+
+impl Default for VertexInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// Reads HLSL-packed values out of a constant buffer
+///
+pub struct ConstantReader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> ConstantReader<'a> {
+    ///
+    /// Reads a float at the specified byte offset. Out of range reads return zero.
+    ///
+    pub fn read_f32(&self, offset: usize) -> f32 {
+        match self.data.get(offset..offset + 4) {
+            Some(b) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            None => 0.0f32
+        }
+    }
+
+    ///
+    /// Reads a float3 at the specified byte offset
+    ///
+    pub fn read_vec3(&self, offset: usize) -> Vector3F {
+        Vector3F::new(self.read_f32(offset), self.read_f32(offset + 4), self.read_f32(offset + 8))
+    }
+
+    ///
+    /// Reads a float4 at the specified byte offset
+    ///
+    pub fn read_vec4(&self, offset: usize) -> Vector4F {
+        Vector4F::new(self.read_f32(offset), self.read_f32(offset + 4), self.read_f32(offset + 8),
+            self.read_f32(offset + 12))
+    }
+
+    ///
+    /// Reads a row_major float4x4 written from a `Matrix4F`
+    ///
+    pub fn read_matrix(&self, offset: usize) -> Matrix4F {
+        let mut m = [0.0f32; 16];
+        for (i, v) in m.iter_mut().enumerate() {
+            *v = self.read_f32(offset + i * 4);
+        }
+
+        Matrix4F::from_column_slice(&m)
+    }
+}
+
+///
+/// Resources bound to a single shader stage for a draw
+///
+#[derive(Default)]
+pub struct StageBindings {
+    pub constants: Vec<Option<Rc<RefCell<Vec<u8>>>>>,
+    pub inputs: Vec<Option<Rc<SoftwareTexture>>>,
+    pub samplers: Vec<Option<SamplerData>>
+}
+
+///
+/// Snapshot of a stage's resources that programs read from while executing
+///
+pub struct ShaderResources<'a> {
+    constants: Vec<Vec<u8>>,
+    bindings: &'a StageBindings
+}
+
+impl<'a> ShaderResources<'a> {
+    ///
+    /// Captures the resources bound to a stage for the duration of a draw
+    ///
+    pub fn new(bindings: &'a StageBindings) -> Self {
+        let constants = bindings.constants.iter()
+            .map(|c| c.as_ref().map(|c| c.borrow().clone()).unwrap_or_default())
+            .collect();
+
+        Self { constants, bindings }
+    }
+
+    ///
+    /// Gets a reader for the constant buffer bound at the specified slot
+    ///
+    pub fn constants(&self, slot: usize) -> ConstantReader<'_> {
+        match self.constants.get(slot) {
+            Some(data) => ConstantReader { data: &data[..] },
+            None => ConstantReader { data: &[] }
+        }
+    }
+
+    ///
+    /// Samples the texture at the specified slot. Unbound textures return zero like D3D does.
+    ///
+    pub fn sample(&self, tex_slot: usize, samp_slot: usize, uv: &Vector2F) -> Vector4F {
+        // unbound samplers use the default D3D sampler state
+        const DEFAULT_SAMPLER: SamplerData = SamplerData {
+            mode: SampleMode::Linear,
+            address_u: AddressMode::Clamp,
+            address_v: AddressMode::Clamp,
+            address_w: AddressMode::Clamp,
+            comparison: ComparisonFunc::Never,
+            lod_bias: 0.0f32,
+            max_anisotropy: 1,
+            border_color: BorderColor::OpaqueWhite,
+            min_lod: f32::MIN,
+            max_lod: f32::MAX
+        };
+
+        match self.bindings.inputs.get(tex_slot) {
+            Some(Some(tex)) => {
+                let samp = match self.bindings.samplers.get(samp_slot) {
+                    Some(Some(s)) => s,
+                    _ => &DEFAULT_SAMPLER
+                };
+                tex.sample(samp, uv)
+            }
+            _ => Vector4F::zeros()
+        }
+    }
+}
+
+///
+/// A shader written in Rust that the software rasterizer executes in place of compiled HLSL
+///
+pub trait ShaderProgram {
+    ///
+    /// Transforms a vertex, writing values to interpolate into `out`
+    ///
+    /// # Returns
+    /// The clip space position of the vertex
+    ///
+    fn vertex(&self, input: &VertexInput, res: &ShaderResources, out: &mut Varyings) -> Vector4F;
+
+    ///
+    /// Shades a single pixel from the interpolated vertex values
+    ///
+    fn pixel(&self, input: &Varyings, res: &ShaderResources) -> Vector4F;
+}
+
+///
+/// Looks up the Rust port of a shader shipped in data/shaders by its file stem
+///
+pub fn find_builtin_program(name: &str) -> Option<Rc<dyn ShaderProgram>> {
+    match name {
+        "object" => Some(Rc::new(ObjectProgram)),
+        _ => None
+    }
+}
+
+///
+/// Port of data/shaders/object.hlsl
+///
+struct ObjectProgram;
+
+impl ObjectProgram {
+    // byte offsets into the HLSL-packed Constants cbuffer
+    const WORLD_MAT: usize = 0;
+    const VIEW_PROJ_MAT: usize = 64;
+    const CAMERA_POS: usize = 128;
+    const SPECULAR_AMOUNT: usize = 140;
+    const AMBIENT_LIGHT: usize = 144;
+    const SUN_COLOR: usize = 160;
+    const SUN_DIR: usize = 176;
+
+    // byte offsets into the MaterialConstants cbuffer
+    const DIFFUSE_COLOR: usize = 0;
+    const AMBIENT_COLOR: usize = 16;
+    const SPECULAR_POWER: usize = 28;
+    const SPECULAR_COLOR: usize = 32;
+    const LIGHTING: usize = 44;
+    const EMISSIVE_COLOR: usize = 48;
+
+    // offsets into the varyings
+    const NORMAL: usize = 0;
+    const UV: usize = 3;
+    const COLOR: usize = 5;
+    const VIEW_DIR: usize = 9;
+}
+
+impl ShaderProgram for ObjectProgram {
+    fn vertex(&self, input: &VertexInput, res: &ShaderResources, out: &mut Varyings) -> Vector4F {
+        let cb = res.constants(0);
+        let world = cb.read_matrix(Self::WORLD_MAT);
+        let view_proj = cb.read_matrix(Self::VIEW_PROJ_MAT);
+        let camera_pos = cb.read_vec3(Self::CAMERA_POS);
+
+        let pos = input.get(SemanticType::Position, 0);
+        let world_pos = (world * Vector4F::new(pos.x, pos.y, pos.z, 1.0f32)).xyz();
+        let normal = world.fixed_slice::<U3, U3>(0, 0) * input.get(SemanticType::Normal, 0).xyz();
+        let uv = input.get(SemanticType::TexCoord, 0);
+        let color = input.get(SemanticType::TexCoord, 1);
+        let view_dir = world_pos - camera_pos;
+
+        out[Self::NORMAL..Self::NORMAL + 3].copy_from_slice(normal.as_slice());
+        out[Self::UV..Self::UV + 2].copy_from_slice(&uv.as_slice()[..2]);
+        out[Self::COLOR..Self::COLOR + 4].copy_from_slice(color.as_slice());
+        out[Self::VIEW_DIR..Self::VIEW_DIR + 3].copy_from_slice(view_dir.as_slice());
+
+        view_proj * Vector4F::new(world_pos.x, world_pos.y, world_pos.z, 1.0f32)
+    }
+
+    fn pixel(&self, input: &Varyings, res: &ShaderResources) -> Vector4F {
+        let cb = res.constants(0);
+        let specular_amount = cb.read_f32(Self::SPECULAR_AMOUNT);
+        let ambient_light = cb.read_vec4(Self::AMBIENT_LIGHT);
+        let sun_color = cb.read_vec4(Self::SUN_COLOR);
+        let sun_dir = cb.read_vec3(Self::SUN_DIR);
+
+        let mat = res.constants(1);
+        let diffuse_color = mat.read_vec4(Self::DIFFUSE_COLOR);
+        let ambient_color = mat.read_vec3(Self::AMBIENT_COLOR);
+        let specular_power = mat.read_f32(Self::SPECULAR_POWER);
+        let specular_color = mat.read_vec3(Self::SPECULAR_COLOR);
+        let lighting = mat.read_f32(Self::LIGHTING);
+        let emissive_color = mat.read_vec3(Self::EMISSIVE_COLOR);
+
+        let normal = Vector3F::from_column_slice(&input[Self::NORMAL..Self::NORMAL + 3]);
+        let uv = Vector2F::from_column_slice(&input[Self::UV..Self::UV + 2]);
+        let view_dir = Vector3F::from_column_slice(&input[Self::VIEW_DIR..Self::VIEW_DIR + 3]);
+        let mut albedo = Vector4F::from_column_slice(&input[Self::COLOR..Self::COLOR + 4]);
+        albedo = albedo.component_mul(&diffuse_color);
+        albedo = albedo.component_mul(&res.sample(0, 0, &uv));
+
+        let saturate = |f: f32| f.clamp(0.0f32, 1.0f32);
+        let n = normal.normalize();
+        let i = view_dir.normalize();
+        let h = i - n * (2.0f32 * n.dot(&i));
+        let lit = ambient_light.xyz().component_mul(&ambient_color)
+            + sun_color.xyz() * saturate(n.dot(&sun_dir));
+        let diffuse = Vector3F::repeat(1.0f32).lerp(&lit, lighting);
+        let specular = specular_color * specular_amount
+            * saturate(h.dot(&sun_dir)).powf(specular_power);
+
+        let rgb = albedo.xyz().component_mul(&diffuse) + specular + emissive_color;
+        Vector4F::new(rgb.x, rgb.y, rgb.z, albedo.w)
+    }
+}
@@ -0,0 +1,154 @@
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// A 2D array of pixels in memory
+///
+#[derive(Debug, Clone)]
+pub struct Surface<T> {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<T>
+}
+
+impl<T: Copy> Surface<T> {
+    ///
+    /// Creates a new surface filled with the specified value
+    ///
+    pub fn new(width: u32, height: u32, value: T) -> Self {
+        Self { width, height, data: vec![value; (width * height) as usize] }
+    }
+
+    ///
+    /// Sets every pixel of the surface to the specified value
+    ///
+    pub fn clear(&mut self, value: T) {
+        for p in self.data.iter_mut() {
+            *p = value;
+        }
+    }
+
+    ///
+    /// Gets the pixel at the specified coordinates
+    ///
+    #[inline] pub fn get(&self, x: u32, y: u32) -> T {
+        self.data[(y * self.width + x) as usize]
+    }
+
+    ///
+    /// Sets the pixel at the specified coordinates
+    ///
+    #[inline] pub fn set(&mut self, x: u32, y: u32, value: T) {
+        self.data[(y * self.width + x) as usize] = value;
+    }
+}
+
+///
+/// Texture data decoded to linear floating point texels for sampling
+///
+pub struct SoftwareTexture {
+    pub texels: Surface<Vector4F>
+}
+
+impl SoftwareTexture {
+    ///
+    /// Decodes the top mip of the texture from the source pixel data
+    ///
+    pub fn new(format: TextureFormat, width: u32, height: u32, pixel_data: &[u8]) -> Self {
+        let pixel_size = format.pixel_size() as usize;
+        let num_pixels = (width * height) as usize;
+        assert!(pixel_data.len() >= num_pixels * pixel_size, "Not enough pixel data for the texture");
+
+        let unorm = |b: u8| b as f32 / 255.0f32;
+        let float = |p: &[u8], i: usize| {
+            f32::from_le_bytes([p[i * 4], p[i * 4 + 1], p[i * 4 + 2], p[i * 4 + 3]])
+        };
+
+        let mut data = Vec::with_capacity(num_pixels);
+        for p in pixel_data.chunks(pixel_size).take(num_pixels) {
+            let texel = match format {
+                TextureFormat::R8UNorm => Vector4F::new(unorm(p[0]), 0.0f32, 0.0f32, 1.0f32),
+                TextureFormat::R8G8UNorm => Vector4F::new(unorm(p[0]), unorm(p[1]), 0.0f32, 1.0f32),
+                TextureFormat::R8G8B8A8UNorm => {
+                    Vector4F::new(unorm(p[0]), unorm(p[1]), unorm(p[2]), unorm(p[3]))
+                }
+                TextureFormat::R8G8B8A8UNormSrgb => {
+                    Vector4F::new(
+                        srgb_to_linear(unorm(p[0])),
+                        srgb_to_linear(unorm(p[1])),
+                        srgb_to_linear(unorm(p[2])),
+                        unorm(p[3]))
+                }
+                TextureFormat::R32Float => Vector4F::new(float(p, 0), 0.0f32, 0.0f32, 1.0f32),
+                TextureFormat::R32G32Float => Vector4F::new(float(p, 0), float(p, 1), 0.0f32, 1.0f32),
+                TextureFormat::R32G32B32A32Float => {
+                    Vector4F::new(float(p, 0), float(p, 1), float(p, 2), float(p, 3))
+                }
+            };
+            data.push(texel);
+        }
+
+        Self { texels: Surface { width, height, data } }
+    }
+
+    ///
+    /// Samples the texture at the specified texture coordinates
+    ///
+    pub fn sample(&self, samp: &SamplerData, uv: &Vector2F) -> Vector4F {
+        let w = self.texels.width as i32;
+        let h = self.texels.height as i32;
+        let border = samp.border_color.to_rgba();
+        let border = Vector4F::new(border[0], border[1], border[2], border[3]);
+        let fetch = |x: i32, y: i32| {
+            match (resolve_address(samp.address_u, x, w), resolve_address(samp.address_v, y, h)) {
+                (Some(x), Some(y)) => self.texels.get(x as u32, y as u32),
+                _ => border
+            }
+        };
+
+        let x = uv.x * w as f32;
+        let y = uv.y * h as f32;
+        match samp.mode {
+            SampleMode::Point | SampleMode::ComparisonPoint => {
+                fetch(x.floor() as i32, y.floor() as i32)
+            }
+            _ => {
+                // bilinear filter between the four nearest texel centers
+                let x = x - 0.5f32;
+                let y = y - 0.5f32;
+                let x0 = x.floor();
+                let y0 = y.floor();
+                let fx = x - x0;
+                let fy = y - y0;
+                let (x0, y0) = (x0 as i32, y0 as i32);
+
+                let top = fetch(x0, y0) * (1.0f32 - fx) + fetch(x0 + 1, y0) * fx;
+                let bottom = fetch(x0, y0 + 1) * (1.0f32 - fx) + fetch(x0 + 1, y0 + 1) * fx;
+                top * (1.0f32 - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+///
+/// Maps a texel coordinate into the texture according to the address mode
+///
+/// # Returns
+/// `None` if the border color should be used
+///
+fn resolve_address(mode: AddressMode, c: i32, size: i32) -> Option<i32> {
+    match mode {
+        AddressMode::Wrap => Some(c.rem_euclid(size)),
+        AddressMode::Mirror => {
+            let c = c.rem_euclid(size * 2);
+            Some(if c < size { c } else { size * 2 - 1 - c })
+        }
+        AddressMode::Clamp => Some(c.max(0).min(size - 1)),
+        AddressMode::BorderColor => if c < 0 || c >= size { None } else { Some(c) },
+        AddressMode::MirrorOnce => {
+            let c = if c < 0 { -c - 1 } else { c };
+            Some(c.min(size - 1))
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// local refs
+use crate::numerics::*;
+
+///
+/// How the camera responds to movement input
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Rotates around and moves relative to a target point
+    Orbit,
+    /// Rotates in place and moves freely through the scene
+    Fly
+}
+
+///
+/// Camera controls accumulated for a single update
+///
+#[derive(Debug, Clone, Copy)]
+pub struct CameraInput {
+    /// Turn right (+x) and down (+y), e.g. in cursor pixels, scaled by `CameraSettings::rotate_speed`
+    pub rotate: Vector2F,
+    /// Movement across the view plane as a fraction of the view height
+    pub pan: Vector2F,
+    /// Steps to move toward (positive) or away from (negative) the target
+    pub zoom: f32,
+    /// Fly mode movement direction in view space (x right, y up, z forward)
+    pub movement: Vector3F
+}
+
+impl Default for CameraInput {
+    fn default() -> Self {
+        Self { rotate: zero(), pan: zero(), zoom: 0.0f32, movement: zero() }
+    }
+}
+
+///
+/// Sensitivity of the camera to input
+///
+#[derive(Debug, Clone, Copy)]
+pub struct CameraSettings {
+    /// Radians of rotation per unit of rotate input
+    pub rotate_speed: f32,
+    /// Fraction of the distance to the target moved per zoom step
+    pub zoom_speed: f32,
+    /// Units per second moved in fly mode
+    pub fly_speed: f32,
+    /// Closest the camera can zoom to the target
+    pub min_distance: f32
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self { rotate_speed: 0.005f32, zoom_speed: 0.1f32, fly_speed: 5.0f32, min_distance: 0.01f32 }
+    }
+}
+
+///
+/// Perspective camera controller that can orbit, pan, zoom and fly
+///
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub settings: CameraSettings,
+    mode: CameraMode,
+    target: Point3F,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    fov_y: f32,
+    aspect: f32,
+    near: f32,
+    far: f32
+}
+
+impl Camera {
+    /// Pitch is kept just short of straight up or down so the view never flips
+    const MAX_PITCH: f32 = 89.0f32 * DEG_TO_RAD;
+
+    ///
+    /// Creates a camera orbiting the target at the specified angles (in radians) and distance
+    ///
+    pub fn new_orbit(target: Point3F, yaw: f32, pitch: f32, distance: f32) -> Self {
+        let mut cam = Self {
+            settings: CameraSettings::default(),
+            mode: CameraMode::Orbit,
+            target,
+            yaw: 0.0f32,
+            pitch: 0.0f32,
+            distance: 1.0f32,
+            fov_y: deg_to_rad(45.0f32),
+            aspect: 1.0f32,
+            near: 0.1f32,
+            far: 1000.0f32
+        };
+        cam.set_orbit(yaw, pitch, distance);
+        cam
+    }
+
+    ///
+    /// Creates a camera at the specified position looking at the target
+    ///
+    pub fn new_look_at(position: Point3F, target: Point3F) -> Self {
+        let offset = position - target;
+        let distance = offset.norm();
+        assert!(distance > 0.0f32, "Camera position and target must differ");
+
+        let yaw = offset.x.atan2(offset.z);
+        let pitch = (offset.y / distance).asin();
+        Self::new_orbit(target, yaw, pitch, distance)
+    }
+
+    ///
+    /// Sets the projection parameters. The field of view is vertical and in radians.
+    ///
+    pub fn set_perspective(&mut self, fov_y: f32, aspect: f32, near: f32, far: f32) {
+        assert!(near > 0.0f32 && far > near, "Invalid near/far planes");
+        self.fov_y = fov_y;
+        self.aspect = aspect;
+        self.near = near;
+        self.far = far;
+    }
+
+    ///
+    /// Sets the distances of the near and far clip planes
+    ///
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        assert!(near > 0.0f32 && far > near, "Invalid near/far planes");
+        self.near = near;
+        self.far = far;
+    }
+
+    ///
+    /// Sets the aspect ratio of the projection, e.g. after the view is resized
+    ///
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    ///
+    /// Places the camera around the target at the specified angles (in radians) and distance
+    ///
+    pub fn set_orbit(&mut self, yaw: f32, pitch: f32, distance: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+        self.distance = distance.max(self.settings.min_distance);
+    }
+
+    ///
+    /// Switches between orbit and fly modes, keeping the current view
+    ///
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+    }
+
+    pub fn get_mode(&self) -> CameraMode { self.mode }
+    pub fn get_target(&self) -> Point3F { self.target }
+    pub fn get_yaw(&self) -> f32 { self.yaw }
+    pub fn get_pitch(&self) -> f32 { self.pitch }
+    pub fn get_distance(&self) -> f32 { self.distance }
+
+    ///
+    /// Gets the position of the camera in world space
+    ///
+    pub fn get_position(&self) -> Point3F {
+        self.target - self.get_forward() * self.distance
+    }
+
+    ///
+    /// Gets the direction the camera is looking
+    ///
+    pub fn get_forward(&self) -> Vector3F {
+        let (sy, cy) = self.yaw.sin_cos();
+        let (sp, cp) = self.pitch.sin_cos();
+        -Vector3F::new(cp * sy, sp, cp * cy)
+    }
+
+    ///
+    /// Gets the direction to the right of the camera, always level with the horizon
+    ///
+    pub fn get_right(&self) -> Vector3F {
+        let (sy, cy) = self.yaw.sin_cos();
+        Vector3F::new(cy, 0.0f32, -sy)
+    }
+
+    ///
+    /// Gets the up direction of the camera
+    ///
+    pub fn get_up(&self) -> Vector3F {
+        self.get_right().cross(&self.get_forward())
+    }
+
+    ///
+    /// Gets the world to view space transform
+    ///
+    pub fn get_view_matrix(&self) -> Matrix4F {
+        Matrix4F::look_at_rh(&self.get_position(), &self.target, &Vector3F::y())
+    }
+
+    ///
+    /// Gets the view to clip space transform
+    ///
+    pub fn get_projection_matrix(&self) -> Matrix4F {
+        Matrix4F::new_perspective(self.aspect, self.fov_y, self.near, self.far)
+    }
+
+    ///
+    /// Gets the combined world to clip space transform
+    ///
+    pub fn get_view_projection_matrix(&self) -> Matrix4F {
+        self.get_projection_matrix() * self.get_view_matrix()
+    }
+
+    ///
+    /// Rotates the camera. Orbit mode circles the target while fly mode turns in place.
+    ///
+    pub fn rotate(&mut self, d_yaw: f32, d_pitch: f32) {
+        let position = self.get_position();
+        self.set_orbit(self.yaw + d_yaw, self.pitch + d_pitch, self.distance);
+
+        if self.mode == CameraMode::Fly {
+            self.target = position + self.get_forward() * self.distance;
+        }
+    }
+
+    ///
+    /// Moves the camera and target across the view plane. Offsets are a fraction of the view height
+    /// at the target, so the target tracks the cursor regardless of distance.
+    ///
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let view_height = 2.0f32 * self.distance * (self.fov_y * 0.5f32).tan();
+        self.target += (self.get_right() * -dx + self.get_up() * dy) * view_height;
+    }
+
+    ///
+    /// Moves the camera toward (positive) or away from (negative) the target
+    ///
+    pub fn zoom(&mut self, steps: f32) {
+        let distance = self.distance * (1.0f32 - self.settings.zoom_speed).powf(steps);
+        self.distance = distance.max(self.settings.min_distance);
+    }
+
+    ///
+    /// Moves the camera and target together along the view space direction
+    ///
+    pub fn fly(&mut self, movement: &Vector3F, dt: f32) {
+        let dir = self.get_right() * movement.x + Vector3F::y() * movement.y
+            + self.get_forward() * movement.z;
+        self.target += dir * (self.settings.fly_speed * dt);
+    }
+
+    ///
+    /// Centers the camera on a bounding sphere and backs away until it fills the view
+    ///
+    pub fn frame_sphere(&mut self, center: Point3F, radius: f32) {
+        let half_fov_y = self.fov_y * 0.5f32;
+        let half_fov_x = (half_fov_y.tan() * self.aspect).atan();
+        let half_fov = half_fov_y.min(half_fov_x);
+        self.target = center;
+        self.distance = (radius / half_fov.sin()).max(self.settings.min_distance);
+    }
+
+    ///
+    /// Centers the camera on an axis-aligned bounding box
+    ///
+    pub fn frame_bounds(&mut self, min: &Point3F, max: &Point3F) {
+        let center = Point3F::from((min.coords + max.coords) * 0.5f32);
+        self.frame_sphere(center, (max - min).norm() * 0.5f32);
+    }
+
+    ///
+    /// Applies a frame of input to the camera
+    ///
+    pub fn update(&mut self, input: &CameraInput, dt: f32) {
+        self.rotate(-input.rotate.x * self.settings.rotate_speed,
+            input.rotate.y * self.settings.rotate_speed);
+
+        match self.mode {
+            CameraMode::Orbit => {
+                self.pan(input.pan.x, input.pan.y);
+                self.zoom(input.zoom);
+            }
+            CameraMode::Fly => {
+                self.fly(&input.movement, dt);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4f32;
+
+    fn assert_near(a: &Vector3F, b: &Vector3F) {
+        assert!((a - b).norm() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn look_at_round_trips_position() {
+        let cam = Camera::new_look_at(Point3F::new(1.0f32, 1.5f32, 5.0f32), Point3F::origin());
+        assert_near(&cam.get_position().coords, &Vector3F::new(1.0f32, 1.5f32, 5.0f32));
+        assert_near(&cam.get_forward(), &-Vector3F::new(1.0f32, 1.5f32, 5.0f32).normalize());
+    }
+
+    #[test]
+    fn basis_is_orthonormal() {
+        let cam = Camera::new_orbit(Point3F::origin(), 0.7f32, -0.3f32, 3.0f32);
+        let (f, r, u) = (cam.get_forward(), cam.get_right(), cam.get_up());
+        for v in &[f, r, u] {
+            assert!((v.norm() - 1.0f32).abs() < EPSILON);
+        }
+        assert!(f.dot(&r).abs() < EPSILON && f.dot(&u).abs() < EPSILON && r.dot(&u).abs() < EPSILON);
+        assert!(u.y > 0.0f32);
+    }
+
+    #[test]
+    fn view_matrix_puts_target_in_front() {
+        let cam = Camera::new_orbit(Point3F::new(2.0f32, 0.0f32, -1.0f32), 1.0f32, 0.4f32, 4.0f32);
+        let view = cam.get_view_matrix();
+        let target = view.transform_point(&cam.get_target());
+        assert_near(&target.coords, &Vector3F::new(0.0f32, 0.0f32, -4.0f32));
+
+        let right = view.transform_vector(&cam.get_right());
+        assert_near(&right, &Vector3F::x());
+    }
+
+    #[test]
+    fn projection_maps_near_and_far_planes() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.0f32, 0.0f32, 1.0f32);
+        cam.set_perspective(deg_to_rad(90.0f32), 2.0f32, 1.0f32, 10.0f32);
+        let project = |p: Vector4F| {
+            let clip = cam.get_projection_matrix() * p;
+            clip.xyz() / clip.w
+        };
+        let near = project(Vector4F::new(0.0f32, 1.0f32, -1.0f32, 1.0f32));
+        let far = project(Vector4F::new(20.0f32, 0.0f32, -10.0f32, 1.0f32));
+        assert!((near.z + 1.0f32).abs() < EPSILON && (near.y - 1.0f32).abs() < EPSILON);
+        assert!((far.z - 1.0f32).abs() < EPSILON && (far.x - 1.0f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn orbit_keeps_distance_and_clamps_pitch() {
+        let mut cam = Camera::new_orbit(Point3F::new(1.0f32, 2.0f32, 3.0f32), 0.0f32, 0.0f32, 5.0f32);
+        cam.rotate(1.0f32, 10.0f32);
+        assert!((cam.get_pitch() - Camera::MAX_PITCH).abs() < EPSILON);
+        assert!(((cam.get_position() - cam.get_target()).norm() - 5.0f32).abs() < EPSILON);
+        assert_near(&cam.get_target().coords, &Vector3F::new(1.0f32, 2.0f32, 3.0f32));
+    }
+
+    #[test]
+    fn fly_rotation_keeps_position() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.3f32, 0.2f32, 5.0f32);
+        cam.set_mode(CameraMode::Fly);
+        let position = cam.get_position();
+        cam.rotate(0.5f32, -0.4f32);
+        assert_near(&cam.get_position().coords, &position.coords);
+    }
+
+    #[test]
+    fn pan_moves_target_across_view() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.0f32, 0.0f32, 2.0f32);
+        cam.set_perspective(deg_to_rad(90.0f32), 1.0f32, 0.1f32, 100.0f32);
+        cam.pan(-0.5f32, 0.25f32);
+        // the view is 4 units tall at the target, so the target moves 2 right and 1 up
+        assert_near(&cam.get_target().coords, &Vector3F::new(2.0f32, 1.0f32, 0.0f32));
+        assert!((cam.get_distance() - 2.0f32).abs() < EPSILON);
+    }
+
+    #[test]
+    fn zoom_scales_distance_and_respects_minimum() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.0f32, 0.0f32, 10.0f32);
+        cam.zoom(1.0f32);
+        assert!((cam.get_distance() - 9.0f32).abs() < EPSILON);
+        cam.zoom(-1.0f32);
+        assert!((cam.get_distance() - 10.0f32).abs() < EPSILON);
+        cam.zoom(1000.0f32);
+        assert_eq!(cam.get_distance(), cam.settings.min_distance);
+    }
+
+    #[test]
+    fn fly_moves_along_view_direction() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.0f32, 0.0f32, 1.0f32);
+        cam.set_mode(CameraMode::Fly);
+        let input = CameraInput { movement: Vector3F::new(0.0f32, 0.0f32, 1.0f32), ..Default::default() };
+        cam.update(&input, 0.5f32);
+        // looking down -z at 5 units per second for half a second
+        assert_near(&cam.get_position().coords, &Vector3F::new(0.0f32, 0.0f32, -1.5f32));
+    }
+
+    #[test]
+    fn framing_fits_sphere_in_view() {
+        let mut cam = Camera::new_orbit(Point3F::origin(), 0.5f32, 0.5f32, 1.0f32);
+        cam.set_perspective(deg_to_rad(60.0f32), 2.0f32, 0.1f32, 100.0f32);
+        cam.frame_bounds(&Point3F::new(-1.0f32, 0.0f32, 1.0f32), &Point3F::new(1.0f32, 2.0f32, 3.0f32));
+        assert_near(&cam.get_target().coords, &Vector3F::new(0.0f32, 1.0f32, 2.0f32));
+
+        // vertical fov is the limiting one, so the sphere touches the top and bottom of the view
+        let radius = 3.0f32.sqrt();
+        assert!((cam.get_distance() - radius / deg_to_rad(30.0f32).sin()).abs() < EPSILON);
+    }
+}
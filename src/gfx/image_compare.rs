@@ -0,0 +1,227 @@
+// external refs
+use std::fs;
+use std::path::Path;
+use stb_image::image;
+
+// local refs
+use crate::error::Error;
+use crate::numerics::*;
+
+///
+/// Thresholds an image must stay within to match a reference image
+///
+#[derive(Debug, Clone, Copy)]
+pub struct ImageTolerance {
+    /// Largest per-channel difference for a pixel to still count as matching
+    pub channel: u8,
+    /// Fraction of pixels allowed to exceed the channel tolerance
+    pub max_failed_fraction: f32,
+    /// Largest allowed average CIE76 color difference over the whole image
+    pub max_mean_delta_e: f32
+}
+
+impl Default for ImageTolerance {
+    fn default() -> Self {
+        Self { channel: 2, max_failed_fraction: 0.001f32, max_mean_delta_e: 1.0f32 }
+    }
+}
+
+///
+/// Result of comparing an image against a reference image
+///
+#[derive(Debug, Clone, Copy)]
+pub struct ImageComparison {
+    pub num_pixels: usize,
+    pub failed_pixels: usize,
+    pub max_channel_error: u8,
+    pub mean_delta_e: f32,
+    pub max_delta_e: f32
+}
+
+impl ImageComparison {
+    ///
+    /// Checks whether the comparison is within the specified tolerance
+    ///
+    pub fn passes(&self, tolerance: &ImageTolerance) -> bool {
+        let allowed = (self.num_pixels as f32 * tolerance.max_failed_fraction) as usize;
+        self.failed_pixels <= allowed && self.mean_delta_e <= tolerance.max_mean_delta_e
+    }
+}
+
+///
+/// Compares two equally sized images pixel by pixel
+///
+pub fn compare_images(expected: &[Color4], actual: &[Color4], tolerance: &ImageTolerance)
+    -> ImageComparison {
+
+    assert_eq!(expected.len(), actual.len(), "Images must be the same size to compare");
+
+    let mut result = ImageComparison {
+        num_pixels: expected.len(),
+        failed_pixels: 0,
+        max_channel_error: 0,
+        mean_delta_e: 0.0f32,
+        max_delta_e: 0.0f32
+    };
+
+    let mut total_delta_e = 0.0f64;
+    for (e, a) in expected.iter().zip(actual) {
+        let error = channel_error(e, a);
+        if error > tolerance.channel {
+            result.failed_pixels += 1;
+        }
+        result.max_channel_error = result.max_channel_error.max(error);
+
+        let delta_e = delta_e(e, a);
+        total_delta_e += delta_e as f64;
+        result.max_delta_e = result.max_delta_e.max(delta_e);
+    }
+
+    if !expected.is_empty() {
+        result.mean_delta_e = (total_delta_e / expected.len() as f64) as f32;
+    }
+
+    result
+}
+
+///
+/// Builds an image highlighting where two images differ. Matching pixels are shown as a dimmed
+/// grayscale copy of the expected image and failing pixels are shown in red scaled by the error.
+///
+pub fn create_diff_image(expected: &[Color4], actual: &[Color4], tolerance: &ImageTolerance)
+    -> Vec<Color4> {
+
+    assert_eq!(expected.len(), actual.len(), "Images must be the same size to compare");
+
+    expected.iter().zip(actual).map(|(e, a)| {
+        let error = channel_error(e, a);
+        if error > tolerance.channel {
+            Color4::from_rgba(128u8.saturating_add(error / 2), 0, 0, 255)
+        }
+        else {
+            let luma = (e.r as u32 * 54 + e.g as u32 * 183 + e.b as u32 * 19) >> 8;
+            let dimmed = (luma / 4) as u8;
+            Color4::from_rgba(dimmed, dimmed, dimmed, 255)
+        }
+    }).collect()
+}
+
+///
+/// Loads an image file as 8-bit RGBA pixels
+///
+/// # Returns
+/// The width, height and pixels of the image
+///
+pub fn read_image(path: &Path) -> Result<(u32, u32, Vec<Color4>), Error> {
+    match image::load_with_depth(path, 4, false) {
+        image::LoadResult::ImageU8(img) => {
+            let pixels = img.data.chunks(4)
+                .map(|p| Color4::from_rgba(p[0], p[1], p[2], p[3]))
+                .collect();
+            Ok((img.width as u32, img.height as u32, pixels))
+        }
+        image::LoadResult::ImageF32(_) => Err(Error::UnsupportedFormat {
+            what: "floating point image".to_string(),
+            path: Some(path.to_path_buf())
+        }),
+        image::LoadResult::Error(e) => Err(image_load_error(path, e))
+    }
+}
+
+///
+/// Describes why stb_image couldn't load a file, preferring the I/O error if it can't be read
+///
+pub(crate) fn image_load_error(path: &Path, message: String) -> Error {
+    match fs::metadata(path) {
+        Err(e) => Error::io(path, e),
+        Ok(_) => Error::parse(path, None, message)
+    }
+}
+
+///
+/// Gets the largest absolute difference between the channels of two pixels
+///
+fn channel_error(a: &Color4, b: &Color4) -> u8 {
+    a.r.abs_diff(b.r).max(a.g.abs_diff(b.g)).max(a.b.abs_diff(b.b)).max(a.a.abs_diff(b.a))
+}
+
+///
+/// Computes the CIE76 color difference between two sRGB pixels
+///
+fn delta_e(a: &Color4, b: &Color4) -> f32 {
+    (srgb_to_lab(a) - srgb_to_lab(b)).norm()
+}
+
+///
+/// Converts an 8-bit sRGB pixel to CIE L*a*b* using the D65 white point
+///
+fn srgb_to_lab(c: &Color4) -> Vector3F {
+    let lin = |v: u8| srgb_to_linear(v as f32 / 255.0f32);
+    let rgb = Vector3F::new(lin(c.r), lin(c.g), lin(c.b));
+
+    let to_xyz = Matrix3F::new(
+        0.412_456_4f32, 0.357_576_1f32, 0.180_437_5f32,
+        0.212_672_9f32, 0.715_152_2f32, 0.072_175_0f32,
+        0.019_333_9f32, 0.119_192f32, 0.950_304_1f32);
+    let xyz = to_xyz * rgb;
+
+    let f = |t: f32| {
+        const DELTA: f32 = 6.0f32 / 29.0f32;
+        if t > DELTA * DELTA * DELTA { t.cbrt() } else { t / (3.0f32 * DELTA * DELTA) + 4.0f32 / 29.0f32 }
+    };
+    let fx = f(xyz.x / 0.950_47f32);
+    let fy = f(xyz.y);
+    let fz = f(xyz.z / 1.088_83f32);
+
+    Vector3F::new(116.0f32 * fy - 16.0f32, 500.0f32 * (fx - fy), 200.0f32 * (fy - fz))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_images_match_exactly() {
+        let img = vec![Color4::from_rgba(10, 20, 30, 255); 16];
+        let result = compare_images(&img, &img, &ImageTolerance::default());
+        assert_eq!(result.failed_pixels, 0);
+        assert_eq!(result.max_channel_error, 0);
+        assert_eq!(result.max_delta_e, 0.0f32);
+        assert!(result.passes(&ImageTolerance::default()));
+    }
+
+    #[test]
+    fn small_differences_stay_within_tolerance() {
+        let expected = vec![Color4::from_rgba(100, 100, 100, 255); 100];
+        let actual = vec![Color4::from_rgba(101, 101, 100, 255); 100];
+        let result = compare_images(&expected, &actual, &ImageTolerance::default());
+        assert_eq!(result.failed_pixels, 0);
+        assert_eq!(result.max_channel_error, 1);
+        assert!(result.passes(&ImageTolerance::default()));
+    }
+
+    #[test]
+    fn large_differences_fail_and_show_in_diff() {
+        let expected = vec![Color4::black(); 4];
+        let mut actual = expected.clone();
+        actual[2] = Color4::white();
+
+        let tolerance = ImageTolerance::default();
+        let result = compare_images(&expected, &actual, &tolerance);
+        assert_eq!(result.failed_pixels, 1);
+        assert_eq!(result.max_channel_error, 255);
+        assert!((result.max_delta_e - 100.0f32).abs() < 0.1f32);
+        assert!(!result.passes(&tolerance));
+
+        let diff = create_diff_image(&expected, &actual, &tolerance);
+        assert_eq!(diff[0], Color4::from_rgba(0, 0, 0, 255));
+        assert_eq!(diff[2], Color4::from_rgba(255, 0, 0, 255));
+    }
+
+    #[test]
+    fn white_converts_to_full_lightness() {
+        let lab = srgb_to_lab(&Color4::white());
+        assert!((lab.x - 100.0f32).abs() < 0.01f32);
+        assert!(lab.y.abs() < 0.01f32 && lab.z.abs() < 0.01f32);
+    }
+}
@@ -0,0 +1,216 @@
+// external refs
+use std::fs;
+use std::path::Path;
+
+// local refs
+use crate::error::Error;
+use crate::numerics::*;
+
+///
+/// File formats that rendered images can be written to
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm
+}
+
+impl ImageFormat {
+    ///
+    /// Determines the image format from the extension of a path
+    ///
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None
+        }
+    }
+}
+
+///
+/// Writes 8-bit RGBA pixels to an image file, choosing the format from the file extension
+///
+pub fn write_image(path: &Path, width: u32, height: u32, pixels: &[Color4]) -> Result<(), Error> {
+    let format = ImageFormat::from_path(path)
+        .ok_or_else(|| Error::UnsupportedFormat { what: "image format".to_string(),
+            path: Some(path.to_path_buf()) })?;
+    let bytes = match format {
+        ImageFormat::Png => encode_png(width, height, pixels),
+        ImageFormat::Ppm => encode_ppm(width, height, pixels)
+    };
+
+    fs::write(path, bytes).map_err(|e| Error::io(path, e))
+}
+
+///
+/// Encodes pixels as a binary (P6) PPM. Alpha is discarded.
+///
+pub fn encode_ppm(width: u32, height: u32, pixels: &[Color4]) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height) as usize, "Pixel count doesn't match image size");
+
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    out.reserve(pixels.len() * 3);
+    for p in pixels {
+        out.extend_from_slice(&[p.r, p.g, p.b]);
+    }
+
+    out
+}
+
+///
+/// Encodes pixels as an 8-bit RGBA PNG. The image data is stored uncompressed.
+///
+pub fn encode_png(width: u32, height: u32, pixels: &[Color4]) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height) as usize, "Pixel count doesn't match image size");
+
+    // each scanline is prefixed with its filter type (0 = none)
+    let mut raw = Vec::with_capacity(pixels.len() * 4 + height as usize);
+    for row in pixels.chunks(width.max(1) as usize) {
+        raw.push(0u8);
+        for p in row {
+            raw.extend_from_slice(&[p.r, p.g, p.b, p.a]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // 8 bits per channel, RGBA, no interlacing
+
+    let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    write_png_chunk(&mut out, b"IHDR", &header);
+    write_png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    write_png_chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+///
+/// Appends a length-prefixed, CRC-suffixed chunk to a PNG stream
+///
+fn write_png_chunk(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(tag);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+///
+/// Wraps data in a zlib stream made of uncompressed deflate blocks
+///
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 0xFFFF;
+
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+///
+/// Computes the CRC-32 (ISO-HDLC) checksum used by PNG chunks
+///
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+///
+/// Computes the Adler-32 checksum used by zlib streams
+///
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for chunk in data.chunks(5552) {
+        for &d in chunk {
+            a += d as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn encodes_ppm_without_alpha() {
+        let pixels = [Color4::from_rgba(1, 2, 3, 4), Color4::from_rgba(5, 6, 7, 8)];
+        let ppm = encode_ppm(2, 1, &pixels);
+        assert_eq!(ppm, b"P6\n2 1\n255\n\x01\x02\x03\x05\x06\x07".to_vec());
+    }
+
+    #[test]
+    fn encodes_png_that_stb_image_can_read() {
+        let (width, height) = (3u32, 2u32);
+        let pixels: Vec<Color4> = (0..width * height)
+            .map(|i| Color4::from_rgba(i as u8 * 40, 255 - i as u8, 7, 200))
+            .collect();
+        let png = encode_png(width, height, &pixels);
+
+        match stb_image::image::load_from_memory(&png) {
+            stb_image::image::LoadResult::ImageU8(img) => {
+                assert_eq!((img.width, img.height, img.depth), (3, 2, 4));
+                for (i, p) in pixels.iter().enumerate() {
+                    assert_eq!(&img.data[i * 4..i * 4 + 4], &[p.r, p.g, p.b, p.a]);
+                }
+            }
+            _ => panic!("stb_image failed to decode the encoded png")
+        }
+    }
+
+    #[test]
+    fn splits_large_images_into_multiple_deflate_blocks() {
+        let data = vec![0xABu8; 0x1_0000 + 10];
+        let z = zlib_stored(&data);
+        assert_eq!(z.len(), 2 + 5 + 0xFFFF + 5 + 11 + 4);
+        assert_eq!(z[2], 0);
+        assert_eq!(z[2 + 5 + 0xFFFF], 1);
+    }
+
+    #[test]
+    fn recognizes_image_extensions() {
+        assert_eq!(ImageFormat::from_path(Path::new("a/b.PNG")), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("out.ppm")), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("out.bmp")), None);
+    }
+}
@@ -1,58 +1,46 @@
-// external refs
-use winapi::um::d3d11;
-use winapi::shared::winerror;
+// local refs
+use crate::gfx::{Pod, ResourceHandle};
 
 ///
-/// A structure that wraps index buffer functionality
-pub struct IndexBuffer {
-    pub buff: *mut d3d11::ID3D11Buffer,
+/// Size of the indices in an index buffer
+///
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IndexFormat {
+    U16,
+    U32
 }
 
-impl IndexBuffer {
+impl IndexFormat {
     ///
-    /// Creates a new index buffer
+    /// Gets the size of one index in bytes
     ///
-    pub fn new(device: *mut d3d11::ID3D11Device, indices: &[u32]) -> Result<IndexBuffer, ()> {
-        if indices.len() == 0 {
-            return Err(());
+    pub fn get_size(self) -> usize {
+        match self {
+            IndexFormat::U16 => std::mem::size_of::<u16>(),
+            IndexFormat::U32 => std::mem::size_of::<u32>()
         }
+    }
+}
 
-        // TODO: add ability to use either u16 or u32
-        const INDEX_SIZE: usize = std::mem::size_of::<u32>();
-        let desc = d3d11::D3D11_BUFFER_DESC {
-            ByteWidth: (indices.len() * INDEX_SIZE) as _,
-            Usage: d3d11::D3D11_USAGE_IMMUTABLE,
-            BindFlags: d3d11::D3D11_BIND_INDEX_BUFFER,
-            CPUAccessFlags: 0,
-            MiscFlags: 0,
-            StructureByteStride: INDEX_SIZE as _
-        };
-        let init_desc = d3d11::D3D11_SUBRESOURCE_DATA {
-            pSysMem: indices.as_ptr() as _,
-            SysMemPitch: desc.ByteWidth,
-            SysMemSlicePitch: 0
-        };
-        let mut buff = std::ptr::null_mut::<d3d11::ID3D11Buffer>();
-        let hr = unsafe {
-            (*device).CreateBuffer(&desc, &init_desc, &mut buff as *mut *mut _)
-        };
+///
+/// Integer types that can be stored in an index buffer
+///
+pub trait Index: Pod {
+    const FORMAT: IndexFormat;
+}
 
-        if hr != winerror::S_OK {
-            Err(())
-        }
-        else {
-            Ok(IndexBuffer { buff })
-        }
-    }
+impl Index for u16 {
+    const FORMAT: IndexFormat = IndexFormat::U16;
 }
 
-impl Drop for IndexBuffer {
-    ///
-    /// Cleans up resources for the buffer
-    ///
-    fn drop(&mut self) {
-        if !self.buff.is_null() {
-            unsafe { (*self.buff).Release(); }
-        }
-    }
-}
\ No newline at end of file
+impl Index for u32 {
+    const FORMAT: IndexFormat = IndexFormat::U32;
+}
+
+///
+/// A structure that wraps index buffer functionality
+pub struct IndexBuffer {
+    pub format: IndexFormat,
+    pub num_indices: u32,
+    pub handle: ResourceHandle
+}
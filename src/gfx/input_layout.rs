@@ -1,17 +1,10 @@
-// external refs
-use winapi::um::d3d11;
+// local refs
+use crate::gfx::{VertexFormat, ResourceHandle};
 
 ///
 /// Holds the input layout that bridges the gap between a vertex buffer and a vertex shader
 ///
 pub struct InputLayout {
-    pub data: *mut d3d11::ID3D11InputLayout
+    pub format: VertexFormat,
+    pub handle: ResourceHandle
 }
-
-impl Drop for InputLayout {
-    fn drop(&mut self) {
-        if !self.data.is_null() {
-            unsafe { (*self.data).Release(); }
-        }
-    }
-}
\ No newline at end of file
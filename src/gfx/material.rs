@@ -3,7 +3,161 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 // local refs
+use crate::error::Error;
 use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// How the alpha of a material's base color is used. The object shader draws every material as
+/// opaque, so this is carried through for saving and caching models.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored and the surface is fully opaque
+    Opaque,
+    /// Pixels with alpha below the cutoff are discarded, the rest are opaque
+    Mask(f32),
+    /// The surface is blended with what's behind it
+    Blend
+}
+
+///
+/// Which lighting terms a material receives, following the MTL illumination models
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Illumination {
+    /// The surface shows its color without any lighting (`illum 0`)
+    Constant,
+    /// Ambient and diffuse lighting only (`illum 1`)
+    Diffuse,
+    /// Ambient and diffuse lighting with specular highlights (`illum 2` and up)
+    Specular
+}
+
+impl Illumination {
+    ///
+    /// Converts an MTL `illum` value. Models above 2 add reflection and refraction effects the
+    /// viewer doesn't render, so they're drawn as plain highlights.
+    ///
+    pub fn from_mtl(illum: u8) -> Self {
+        match illum {
+            0 => Illumination::Constant,
+            1 => Illumination::Diffuse,
+            _ => Illumination::Specular
+        }
+    }
+
+    ///
+    /// Converts to the MTL `illum` value that reads back as this model
+    ///
+    pub fn to_mtl(self) -> u8 {
+        match self {
+            Illumination::Constant => 0,
+            Illumination::Diffuse => 1,
+            Illumination::Specular => 2
+        }
+    }
+}
+
+///
+/// Authored surface parameters of a material. Defaults follow the glTF 2.0 specification where it
+/// defines the parameter.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialParams {
+    /// Linear color multiplied with the albedo map
+    pub base_color: Color4F,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Linear emitted color multiplied with the emissive map
+    pub emissive: Color3F,
+    pub alpha_mode: AlphaMode,
+    /// Whether back faces are visible. Both backends always cull back faces, so this is only
+    /// carried through for saving and caching models.
+    pub double_sided: bool,
+    /// Linear color multiplied with the scene's ambient light
+    pub ambient: Color3F,
+    /// Linear color of specular highlights
+    pub specular: Color3F,
+    /// Exponent controlling how tight specular highlights are
+    pub specular_power: f32,
+    pub illumination: Illumination
+}
+
+impl Default for MaterialParams {
+    fn default() -> Self {
+        Self {
+            base_color: Color4F::white(),
+            metallic: 1.0f32,
+            roughness: 1.0f32,
+            emissive: Color3F::black(),
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            ambient: Color3F::white(),
+            specular: Color3F::black(),
+            specular_power: 1.0f32,
+            illumination: Illumination::Specular
+        }
+    }
+}
+
+///
+/// Per-material constants, laid out to match the MaterialConstants cbuffer of the object shader
+///
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct MaterialConstants {
+    pub diffuse_color: Color4F,
+    pub ambient_color: Color3F,
+    pub specular_power: f32,
+    pub specular_color: Color3F,
+    /// 1 to light the surface, 0 to show its color unlit
+    pub lighting: f32,
+    pub emissive_color: Color3F,
+    _padding: f32
+}
+
+unsafe impl Pod for MaterialConstants {}
+
+impl MaterialConstants {
+    ///
+    /// Packs the authored parameters of a material for the shader
+    ///
+    pub fn from_params(params: &MaterialParams) -> Self {
+        let (lighting, specular_color) = match params.illumination {
+            Illumination::Constant => (0.0f32, Color3F::black()),
+            Illumination::Diffuse => (1.0f32, Color3F::black()),
+            Illumination::Specular => (1.0f32, params.specular)
+        };
+
+        Self {
+            diffuse_color: params.base_color,
+            ambient_color: params.ambient,
+            specular_power: params.specular_power,
+            specular_color,
+            lighting,
+            emissive_color: params.emissive,
+            _padding: 0.0f32
+        }
+    }
+
+    ///
+    /// Creates a constant buffer holding the parameters of a material
+    ///
+    pub fn create_buffer(gfx: &Graphics, params: &MaterialParams) -> Result<ShaderBuffer, Error> {
+        let buffer = gfx.create_constant_buffer(std::mem::size_of::<Self>())?;
+        gfx.map_and_set_buffer_data(&buffer, &Self::from_params(params));
+        Ok(buffer)
+    }
+}
+
+///
+/// Texture slots sampled by the object shader, with the texture bound when a material doesn't
+/// specify one
+///
+const OBJECT_TEXTURE_SLOTS: [(&str, u32, FallbackTexture); 1] = [
+    ("albedo_map", 0, FallbackTexture::White)
+];
 
 ///
 /// Data needed to load a material
@@ -12,7 +166,9 @@ pub struct MaterialInfo {
     pub shader_file: PathBuf,
     pub vert_format: VertexFormat,
     pub topology: PrimitiveTopology,
-    pub textures: HashMap<String, PathBuf>
+    /// Texture files by the shader slot they're bound to, e.g. "albedo_map" or "normal_map"
+    pub textures: HashMap<String, PathBuf>,
+    pub params: MaterialParams
 }
 
 ///
@@ -23,55 +179,79 @@ pub struct Material {
     ps: Shader,
     layout: InputLayout,
     topology: PrimitiveTopology,
-    textures: Vec<Texture>,
-    ps_inputs: Vec<(u32, ShaderInput)>
+    _textures: Vec<Texture>,
+    ps_inputs: Vec<(u32, ShaderInput)>,
+    constants: ShaderBuffer,
+    /// Why textures of the material couldn't be loaded, which were replaced with a fallback
+    texture_errors: Vec<Error>
 }
 
 impl Material {
     ///
-    /// Creates a new material
+    /// Creates a new material. The constants are bound to the pixel shader's slot 1 when the
+    /// material is selected.
     ///
     pub fn create(vs: Shader, ps: Shader, topology: PrimitiveTopology, layout: InputLayout,
-        textures: Vec<Texture>, ps_inputs: Vec<(u32, ShaderInput)>) -> Self {
+        textures: Vec<Texture>, ps_inputs: Vec<(u32, ShaderInput)>, constants: ShaderBuffer) -> Self {
 
-        Self { vs, ps, layout, topology, textures, ps_inputs }
+        Self { vs, ps, layout, topology, _textures: textures, ps_inputs, constants,
+            texture_errors: Vec::new() }
     }
 
     ///
-    /// Loads a new material from a shader file
+    /// Loads a new material from a shader file. Only the textures the shader has a slot for are
+    /// loaded, others are kept in the material info for saving. A texture that can't be loaded
+    /// doesn't stop the material from loading, it's replaced with a fallback and the error is kept
+    /// for the caller to report.
     ///
-    pub fn load(gfx: &Graphics, mat_info: &MaterialInfo) -> Result<Self, ()> {
+    pub fn load(gfx: &Graphics, mat_info: &MaterialInfo) -> Result<Self, Error> {
         let sc = ShaderCompiler::from_file(&mat_info.shader_file);
-        let vs_code = sc.compile("VSMain", ShaderType::Vertex)?;
-        let ps_code = sc.compile("PSMain", ShaderType::Pixel)?;
+        let vs_code = sc.compile(gfx, "VSMain", ShaderType::Vertex)?;
+        let ps_code = sc.compile(gfx, "PSMain", ShaderType::Pixel)?;
         let vs = gfx.create_vertex_shader(&vs_code)?;
         let ps = gfx.create_pixel_shader(&ps_code)?;
         let layout = gfx.create_input_layout(&mat_info.vert_format, &vs_code)?;
 
-        let mut textures = Vec::with_capacity(mat_info.textures.len());
-        let mut lookup = HashMap::<&Path, usize>::with_capacity(mat_info.textures.len());
-        for (name, path) in mat_info.textures.iter() {
-            if lookup.contains_key::<Path>(path) {
-                continue;
-            }
-            if let Ok(tex) = gfx.load_texture_from_file(path) {
-                lookup.insert(path, textures.len());
-                textures.push(tex);
-            }
-        }
-
+        let mut textures = Vec::with_capacity(OBJECT_TEXTURE_SLOTS.len());
+        let mut lookup = HashMap::<&Path, usize>::with_capacity(OBJECT_TEXTURE_SLOTS.len());
+        let mut texture_errors = Vec::new();
         let mut ps_inputs = Vec::<(u32, ShaderInput)>::new();
 
         // TODO: REFLECT THE SHADER!
-        if let Some(path) = mat_info.textures.get("albedo_map") {
-            let idx = lookup.get::<Path>(path).unwrap();
-            if let Ok(input) = gfx.create_texture_shader_input(&textures[*idx]) {
-                ps_inputs.push((0, input));
-            }
+        for (name, slot, fallback) in OBJECT_TEXTURE_SLOTS.iter() {
+            let idx = match mat_info.textures.get(*name) {
+                Some(path) => match lookup.get::<Path>(path) {
+                    Some(idx) => *idx,
+                    None => {
+                        let tex = gfx.load_texture_from_file(path).or_else(|e| {
+                            texture_errors.push(e);
+                            gfx.create_fallback_texture(FallbackTexture::Missing)
+                        })?;
+                        lookup.insert(path, textures.len());
+                        textures.push(tex);
+                        textures.len() - 1
+                    }
+                },
+                None => {
+                    textures.push(gfx.create_fallback_texture(*fallback)?);
+                    textures.len() - 1
+                }
+            };
+            ps_inputs.push((*slot, gfx.create_texture_shader_input(&textures[idx])?));
         }
-        // TODO: REFLECT THE SHADER!
 
-        Ok(Self::create(vs, ps, mat_info.topology, layout, textures, ps_inputs))
+        let constants = MaterialConstants::create_buffer(gfx, &mat_info.params)?;
+
+        let material = Self::create(vs, ps, mat_info.topology, layout, textures, ps_inputs,
+            constants);
+        Ok(Self { texture_errors, ..material })
+    }
+
+    ///
+    /// Gets why textures of the material couldn't be loaded
+    ///
+    pub fn get_texture_errors(&self) -> &[Error] {
+        &self.texture_errors
     }
 
     ///
@@ -86,6 +266,7 @@ impl Material {
         for (slot, input) in &self.ps_inputs {
             gfx.set_ps_shader_input(*slot, input);
         }
+        gfx.set_ps_constant_buffer(1, &self.constants);
     }
 
     ///
@@ -94,4 +275,55 @@ impl Material {
     pub fn set_primitive_topology(&mut self, topology: PrimitiveTopology) {
         self.topology = topology;
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constants_match_the_shader_layout() {
+        assert_eq!(std::mem::size_of::<MaterialConstants>(), 64);
+    }
+
+    #[test]
+    fn missing_textures_are_replaced() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let mut textures = HashMap::new();
+        textures.insert("albedo_map".to_string(), PathBuf::from("does_not_exist.png"));
+        // the shader has no slot for normal maps, so this one isn't even opened
+        textures.insert("normal_map".to_string(), PathBuf::from("no_normals.png"));
+        let info = MaterialInfo {
+            shader_file: ["data", "shaders", "object.hlsl"].iter().collect(),
+            vert_format: MeshVertex::get_format(),
+            topology: PrimitiveTopology::TriangleList,
+            textures,
+            params: MaterialParams::default()
+        };
+
+        let mat = Material::load(&gfx, &info).unwrap();
+        assert_eq!(mat.ps_inputs.len(), 1);
+        assert_eq!(mat._textures.len(), 1);
+        assert_eq!(mat._textures[0].width, 8);
+        assert_eq!(mat.get_texture_errors().len(), 1);
+    }
+
+    #[test]
+    fn illumination_selects_lighting_terms() {
+        let params = MaterialParams {
+            specular: Color3F::from_rgb(0.5f32, 0.5f32, 0.5f32),
+            illumination: Illumination::from_mtl(0),
+            ..Default::default()
+        };
+        let constants = MaterialConstants::from_params(&params);
+        assert_eq!(constants.lighting, 0.0f32);
+        assert_eq!(constants.specular_color, Color3F::black());
+
+        let constants = MaterialConstants::from_params(&MaterialParams {
+            illumination: Illumination::from_mtl(4),
+            ..params
+        });
+        assert_eq!(constants.lighting, 1.0f32);
+        assert_eq!(constants.specular_color, params.specular);
+    }
+}
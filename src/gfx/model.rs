@@ -1,19 +1,29 @@
+// external refs
+use std::ops::Range;
+
 // local refs
+use crate::error::Error;
 use crate::gfx::*;
 use crate::numerics::*;
 
 ///
 /// Vertex format used by meshes
 ///
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct MeshVertex {
     pub pos: Point3F,
     pub norm: Vector3F,
-    pub tangent: Vector3F,
+    /// Tangent in xyz, with the handedness of the bitangent `w * cross(norm, tangent)` in w
+    pub tangent: Vector4F,
+    /// Texture coordinate with v pointing down the image, as in glTF and Direct3D
     pub uv: Vector2F,
     pub color: Color4
 }
 
+// every field is 4 byte aligned plain data, so there's no padding
+unsafe impl Pod for MeshVertex {}
+
 impl MeshVertex {
     ///
     /// Gets the vertex format for this vertex type
@@ -24,7 +34,7 @@ impl MeshVertex {
                 InputClass::PerVertex, 0)
             .add_new_element(FormatType::R32G32B32Float, 12, SemanticType::Normal, 0, 0,
                 InputClass::PerVertex, 0)
-            .add_new_element(FormatType::R32G32B32Float, 12, SemanticType::Tangent, 0, 0,
+            .add_new_element(FormatType::R32G32B32A32Float, 16, SemanticType::Tangent, 0, 0,
                 InputClass::PerVertex, 0)
             .add_new_element(FormatType::R32G32Float, 8, SemanticType::TexCoord, 0, 0,
                 InputClass::PerVertex, 0)
@@ -56,6 +66,48 @@ pub struct DrawData {
     pub material_idx: u32
 }
 
+///
+/// A simplified version of a model's draws. It has a draw for each of the full model's draws, in
+/// the same order and with the same materials, so the parts still refer to the right ones.
+///
+#[derive(Clone)]
+pub struct ModelLod {
+    pub draws: Vec<DrawData>,
+    /// How far the simplified surface may be from the full model's, in model units
+    pub error: f32
+}
+
+impl ModelLod {
+    ///
+    /// Gets the number of triangles in every draw
+    ///
+    pub fn get_triangle_count(&self) -> usize {
+        self.draws.iter().map(|draw| draw.num_tris as usize).sum()
+    }
+}
+
+///
+/// A named part of a model, such as an OBJ object or a glTF node. Parts form a tree, with every
+/// part listed after its parent.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    /// The draws of the part's own geometry, not including its children's
+    pub draws: Range<usize>
+}
+
+///
+/// How a part of a model is currently displayed
+///
+#[derive(Debug, Clone, Copy)]
+struct NodeState {
+    visible: bool,
+    /// Transform applied on top of the parent's, in model space
+    transform: Matrix4F
+}
+
 ///
 /// Handles the rendering of a model (clustering of meshes + materials)
 ///
@@ -63,35 +115,513 @@ pub struct Model {
     vb: VertexBuffer,
     ib: IndexBuffer,
     draws: Vec<DrawData>,
-    mats: Vec<Material>
+    mats: Vec<Material>,
+    nodes: Vec<ModelNode>,
+    node_states: Vec<NodeState>,
+    /// Draws that don't belong to any part, which are always displayed untransformed
+    loose_draws: Vec<usize>,
+    /// Bounds of each draw's triangles in model space, if it has any
+    draw_bounds: Vec<Option<Bounds>>,
+    bounds: Option<Bounds>,
+    /// Simplified versions, from the most to the least detailed
+    lods: Vec<ModelLod>,
+    /// Largest error a simplified version may show on screen, as a fraction of its height
+    max_screen_error: f32
 }
 
 impl Model {
     ///
-    /// Constructs a model from the specified data
+    /// Constructs a model from the specified data. Every draw must refer to one of the materials
+    /// and every part to draws in the model. Indices are stored as 16 bit values when every vertex
+    /// can be addressed that way. The bounds of each draw and of the whole model are computed
+    /// from the vertices the draws use.
     ///
     pub fn new(gfx: &Graphics, verts: &[MeshVertex], indices: &[u32], draws: Vec<DrawData>,
-               mats: Vec<Material>) -> Result<Self, ()> {
+               mats: Vec<Material>, nodes: Vec<ModelNode>) -> Result<Self, Error> {
+
+        if let Some(draw) = draws.iter().find(|d| d.material_idx as usize >= mats.len()) {
+            return Err(Error::InvalidArgument(format!("draw uses material {} but the model has {}",
+                draw.material_idx, mats.len())));
+        }
+        for (idx, node) in nodes.iter().enumerate() {
+            if node.draws.end > draws.len() || node.parent.is_some_and(|parent| parent >= idx) {
+                return Err(Error::InvalidArgument(format!("part {} ({}) must use draws in the \
+                    model and come after its parent", idx, node.name)));
+            }
+        }
 
         let vfmt = MeshVertex::get_format();
         let vb = gfx.create_vertex_buffer(&vfmt, verts)?;
-        let ib = gfx.create_index_buffer(indices)?;
+        let ib = if verts.len() <= u16::MAX as usize + 1 {
+            let narrow: Vec<u16> = indices.iter().map(|&idx| idx as u16).collect();
+            gfx.create_index_buffer(&narrow)?
+        } else {
+            gfx.create_index_buffer(indices)?
+        };
+
+        let loose_draws = (0..draws.len())
+            .filter(|draw| !nodes.iter().any(|node| node.draws.contains(draw)))
+            .collect();
+        let node_states = vec![NodeState { visible: true, transform: Matrix4F::identity() };
+            nodes.len()];
+
+        let draw_points = |draw: &DrawData| {
+            let start = draw.start_index as usize;
+            let end = (start + draw.num_tris as usize * 3).min(indices.len());
+            indices.get(start..end).unwrap_or_default().iter()
+                .filter_map(|&idx| verts.get(idx as usize).map(|v| v.pos))
+                .collect::<Vec<_>>()
+        };
+        let draw_bounds = draws.iter()
+            .map(|draw| Bounds::from_points(&draw_points(draw)))
+            .collect();
+        let bounds = Bounds::from_points(&draws.iter().flat_map(draw_points).collect::<Vec<_>>());
+
+        Ok(Self { vb, ib, draws, mats, nodes, node_states, loose_draws, draw_bounds, bounds,
+            lods: Vec::new(), max_screen_error: 0.001f32 })
+    }
+
+    ///
+    /// Adds simplified versions of the model, whose triangles must already be in its index
+    /// buffer. Each must have a draw for every draw of the full model, with the same material.
+    ///
+    pub fn with_lods(mut self, lods: Vec<ModelLod>) -> Result<Self, Error> {
+        for (idx, lod) in lods.iter().enumerate() {
+            let matches = lod.draws.len() == self.draws.len() && lod.draws.iter().zip(&self.draws)
+                .all(|(simplified, full)| simplified.material_idx == full.material_idx);
+            if !matches {
+                return Err(Error::InvalidArgument(format!("level of detail {} must have a draw \
+                    with the same material for each of the model's {} draws", idx + 1,
+                    self.draws.len())));
+            }
+        }
+        self.lods = lods;
+        Ok(self)
+    }
+
+    ///
+    /// Gets the simplified versions of the model, from the most to the least detailed
+    ///
+    pub fn get_lods(&self) -> &[ModelLod] {
+        &self.lods
+    }
+
+    ///
+    /// Sets the largest error a simplified version may show on screen, as a fraction of the
+    /// screen's height. The default of 0.001 is about a pixel.
+    ///
+    pub fn set_max_screen_error(&mut self, max_screen_error: f32) {
+        self.max_screen_error = max_screen_error;
+    }
+
+    ///
+    /// Selects the least detailed version whose error, projected at the nearest point of the
+    /// model's bounding sphere, stays within the maximum screen error
+    ///
+    /// # Returns
+    /// 0 for the full model, or `i` for the simplified version `get_lods()[i - 1]`
+    ///
+    pub fn select_lod(&self, world: &Matrix4F, view_proj: &Matrix4F) -> usize {
+        let Some(bounds) = &self.bounds else {
+            return 0;
+        };
+        let sphere = bounds.sphere.transform(world);
+        let scale = if bounds.sphere.radius > 0.0f32 {
+            sphere.radius / bounds.sphere.radius
+        } else {
+            1.0f32
+        };
+
+        // w is the distance along the view direction for perspective projections and 1 for
+        // orthographic ones, and the y row scales view space heights into the -1..1 range
+        let row = |idx: usize| view_proj.row(idx).transpose();
+        let w = row(3).dot(&sphere.center.to_homogeneous()) - sphere.radius * row(3).xyz().norm();
+        if w <= 0.0f32 {
+            return 0;
+        }
+        let screen_scale = scale * row(1).xyz().norm() * 0.5f32 / w;
+
+        self.lods.iter()
+            .rposition(|lod| lod.error * screen_scale <= self.max_screen_error)
+            .map_or(0, |idx| idx + 1)
+    }
+
+    ///
+    /// Gets why textures of the model's materials couldn't be loaded
+    ///
+    pub fn get_texture_errors(&self) -> impl Iterator<Item = &Error> + '_ {
+        self.mats.iter().flat_map(Material::get_texture_errors)
+    }
+
+    ///
+    /// Gets the parts of the model, with every part listed after its parent
+    ///
+    pub fn get_nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    ///
+    /// Finds the first part with the specified name
+    ///
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.name == name)
+    }
+
+    ///
+    /// Gets the parts whose parent is the specified part
+    ///
+    pub fn get_children(&self, node: usize) -> impl Iterator<Item = usize> + '_ {
+        self.nodes.iter().enumerate()
+            .filter(move |(_, n)| n.parent == Some(node))
+            .map(|(idx, _)| idx)
+    }
+
+    ///
+    /// Gets whether a part's own geometry is displayed
+    ///
+    pub fn is_visible(&self, node: usize) -> bool {
+        self.node_states[node].visible
+    }
+
+    ///
+    /// Shows or hides a part along with all of its descendants
+    ///
+    pub fn set_visible(&mut self, node: usize, visible: bool) {
+        self.node_states[node].visible = visible;
+        // descendants always come after their parents
+        for idx in node + 1..self.nodes.len() {
+            if self.is_descendant(idx, node) {
+                self.node_states[idx].visible = visible;
+            }
+        }
+    }
+
+    ///
+    /// Hides everything except the part and its descendants
+    ///
+    pub fn isolate(&mut self, node: usize) {
+        for idx in 0..self.nodes.len() {
+            self.node_states[idx].visible = idx == node || self.is_descendant(idx, node);
+        }
+    }
+
+    ///
+    /// Shows every part
+    ///
+    pub fn show_all(&mut self) {
+        for state in &mut self.node_states {
+            state.visible = true;
+        }
+    }
 
-        Ok(Self { vb, ib, draws: draws, mats })
+    ///
+    /// Gets the transform applied to a part on top of its parent's
+    ///
+    pub fn get_node_transform(&self, node: usize) -> &Matrix4F {
+        &self.node_states[node].transform
     }
 
     ///
-    /// Draws the model
+    /// Sets the transform applied to a part on top of its parent's. The geometry is already in
+    /// model space, so the identity leaves the part where it was loaded.
+    ///
+    pub fn set_node_transform(&mut self, node: usize, transform: Matrix4F) {
+        self.node_states[node].transform = transform;
+    }
+
+    ///
+    /// Checks whether a part is below another in the tree
+    ///
+    fn is_descendant(&self, node: usize, ancestor: usize) -> bool {
+        let mut parent = self.nodes[node].parent;
+        while let Some(idx) = parent {
+            if idx == ancestor {
+                return true;
+            }
+            parent = self.nodes[idx].parent;
+        }
+        false
+    }
+
+    ///
+    /// Gets the bounds of every draw in model space, as loaded and without the parts' transforms
+    ///
+    /// # Returns
+    /// The bounds, or `None` if the model has no triangles
+    ///
+    pub fn get_bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    ///
+    /// Gets the number of draws in the model
+    ///
+    pub fn get_draw_count(&self) -> usize {
+        self.draws.len()
+    }
+
+    ///
+    /// Gets the bounds of a draw's triangles in model space, or `None` if it has none
+    ///
+    pub fn get_draw_bounds(&self, draw: usize) -> Option<&Bounds> {
+        self.draw_bounds[draw].as_ref()
+    }
+
+    ///
+    /// Gets the size of the indices the model's triangles are stored with
+    ///
+    pub fn get_index_format(&self) -> IndexFormat {
+        self.ib.format
+    }
+
+    ///
+    /// Draws the visible parts of the model, ignoring their transforms
     ///
     pub fn draw(&self, gfx: &Graphics) {
+        self.draw_with_transforms(gfx, |_| {});
+    }
+
+    ///
+    /// Draws the visible parts of the model. Before drawing a part whose transform differs from
+    /// the last one, the callback is given the part's transform in model space so the caller can
+    /// combine it with the world matrix in its constants.
+    ///
+    pub fn draw_with_transforms(&self, gfx: &Graphics, set_transform: impl FnMut(&Matrix4F)) {
+        self.draw_filtered(gfx, &self.draws, set_transform, |_, _| true);
+    }
+
+    ///
+    /// Draws the visible parts of the model like `draw_with_transforms`, using the simplified
+    /// version `select_lod` picks for the view and skipping draws whose bounds are outside it
+    ///
+    /// # Returns
+    /// The number of draws that were skipped
+    ///
+    pub fn draw_culled(&self, gfx: &Graphics, world: &Matrix4F, view_proj: &Matrix4F,
+        set_transform: impl FnMut(&Matrix4F)) -> usize {
+
+        let draws = match self.select_lod(world, view_proj) {
+            0 => &self.draws,
+            lod => &self.lods[lod - 1].draws
+        };
+
+        // simplified draws only use vertices of the full ones, so they fit in the same bounds
+        let frustum = Frustum::from_matrix(view_proj);
+        let mut culled = 0;
+        self.draw_filtered(gfx, draws, set_transform, |draw, part| {
+            let visible = self.draw_bounds[draw]
+                .is_some_and(|bounds| frustum.intersects_bounds(&bounds, &(world * part)));
+            culled += !visible as usize;
+            visible
+        });
+        culled
+    }
+
+    ///
+    /// Draws the visible parts of the model that pass the filter, which is given each draw along
+    /// with its part's transform in model space. The draws are those of the full model or of one
+    /// of the simplified versions.
+    ///
+    fn draw_filtered(&self, gfx: &Graphics, draws: &[DrawData],
+        mut set_transform: impl FnMut(&Matrix4F),
+        mut filter: impl FnMut(usize, &Matrix4F) -> bool) {
+
         gfx.set_vertex_buffer(&self.vb, 0);
         gfx.set_index_buffer(&self.ib);
 
-        for draw in &self.draws {
-            let mat = &self.mats[draw.material_idx as usize];
-            mat.select(gfx);
+        let identity = Matrix4F::identity();
+        let mut current = None;
+        let mut draw_part = |part_draws: &mut dyn Iterator<Item = usize>, transform: &Matrix4F| {
+            for draw in part_draws.filter(|&draw| filter(draw, transform)) {
+                if current.as_ref() != Some(transform) {
+                    set_transform(transform);
+                    current = Some(*transform);
+                }
+                self.draw_one(gfx, &draws[draw]);
+            }
+        };
 
-            gfx.draw_indexed(draw.num_tris * 3, draw.start_index);
+        draw_part(&mut self.loose_draws.iter().copied(), &identity);
+
+        // parents come first, so their combined transforms are ready when the children need them
+        let mut combined = Vec::with_capacity(self.nodes.len());
+        for (node, state) in self.nodes.iter().zip(&self.node_states) {
+            let transform = node.parent.map_or(identity, |parent| combined[parent]) * state.transform;
+            combined.push(transform);
+
+            if state.visible {
+                draw_part(&mut node.draws.clone(), &transform);
+            }
         }
     }
-}
\ No newline at end of file
+
+    ///
+    /// Selects a draw's material and issues it
+    ///
+    fn draw_one(&self, gfx: &Graphics, draw: &DrawData) {
+        self.mats[draw.material_idx as usize].select(gfx);
+        gfx.draw_indexed(draw.num_tris * 3, draw.start_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfx::test_fixtures::*;
+    use std::path::Path;
+
+    ///
+    /// Builds tests/assets/parts.obj: a unit quad at z = 0 split across two draws in the body part,
+    /// and a triangle at z = 1 in the wheel part
+    ///
+    fn load_parts(gfx: &Graphics) -> Model {
+        let path = Path::new("tests").join("assets").join("parts.obj");
+        ModelBuilder::load_from_obj(&path).unwrap().build(gfx).unwrap()
+    }
+
+    #[test]
+    fn computes_the_bounds_of_draws_and_the_model() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let model = load_parts(&gfx);
+
+        let point = Point3F::new;
+        assert_eq!(model.get_draw_count(), 3);
+        assert_eq!(model.get_draw_bounds(0).unwrap().aabb,
+            Aabb::new(point(0.0f32, 0.0f32, 0.0f32), point(1.0f32, 1.0f32, 0.0f32)));
+        assert_eq!(model.get_draw_bounds(2).unwrap().aabb,
+            Aabb::new(point(0.0f32, 0.0f32, 1.0f32), point(1.0f32, 1.0f32, 1.0f32)));
+
+        let bounds = model.get_bounds().unwrap();
+        assert_eq!(bounds.aabb, Aabb::new(Point3F::origin(), point(1.0f32, 1.0f32, 1.0f32)));
+        assert!((bounds.sphere.center - point(0.5f32, 0.5f32, 0.5f32)).norm() < 1e-5f32);
+        assert!((bounds.sphere.radius - 3.0f32.sqrt() * 0.5f32).abs() < 1e-5f32);
+    }
+
+    #[test]
+    fn culls_draws_outside_the_view() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let mut model = load_parts(&gfx);
+        let view = Matrix4F::look_at_rh(&Point3F::new(0.5f32, 0.5f32, 5.0f32),
+            &Point3F::new(0.5f32, 0.5f32, 0.0f32), &Vector3F::y());
+        let view_proj = Matrix4F::new_perspective(1.0f32, deg_to_rad(45.0f32), 0.1f32, 100.0f32)
+            * view;
+        let world = Matrix4F::identity();
+
+        assert_eq!(model.draw_culled(&gfx, &world, &view_proj, |_| {}), 0);
+
+        let wheel = model.find_node("wheel").unwrap();
+        model.set_node_transform(wheel, Matrix4F::new_translation(&Vector3F::new(50.0f32,
+            0.0f32, 0.0f32)));
+        let mut transforms = Vec::new();
+        assert_eq!(model.draw_culled(&gfx, &world, &view_proj, |t| transforms.push(*t)), 1);
+        assert_eq!(transforms, vec![Matrix4F::identity()]);
+
+        let behind = Matrix4F::new_translation(&Vector3F::new(0.0f32, 0.0f32, 10.0f32));
+        assert_eq!(model.draw_culled(&gfx, &behind, &view_proj, |_| {}), 3);
+    }
+
+    #[test]
+    fn selects_levels_of_detail_by_projected_error() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let model = load_parts(&gfx);
+        let draws = model.draws.clone();
+        let lod = |error: f32| ModelLod { draws: draws.clone(), error };
+        let mut model = model.with_lods(vec![lod(0.01f32), lod(0.1f32)]).unwrap();
+
+        // the fov makes the projected error about 1.2 times the error over the distance
+        let proj = Matrix4F::new_perspective(1.0f32, deg_to_rad(45.0f32), 0.1f32, 1000.0f32);
+        let select = |model: &Model, distance: f32| {
+            let view = Matrix4F::look_at_rh(&Point3F::new(0.5f32, 0.5f32, distance),
+                &Point3F::new(0.5f32, 0.5f32, 0.0f32), &Vector3F::y());
+            model.select_lod(&Matrix4F::identity(), &(proj * view))
+        };
+        assert_eq!(select(&model, 0.5f32), 0);
+        assert_eq!(select(&model, 5.0f32), 0);
+        assert_eq!(select(&model, 50.0f32), 1);
+        assert_eq!(select(&model, 500.0f32), 2);
+        model.set_max_screen_error(0.01f32);
+        assert_eq!(select(&model, 50.0f32), 2);
+
+        let mut wrong_material = lod(0.1f32);
+        wrong_material.draws[0].material_idx += 1;
+        let model = load_parts(&gfx);
+        assert!(model.with_lods(vec![wrong_material]).is_err());
+    }
+
+    #[test]
+    fn index_format_follows_vertex_count() {
+        let render = |verts: &[MeshVertex], indices: &[u32]| {
+            let (gfx, rt_state, _cbuff) = setup();
+            let draws = vec![DrawData { start_index: 0, num_tris: 1, material_idx: 0 }];
+            let model = Model::new(&gfx, verts, indices, draws, vec![object_material(&gfx)],
+                Vec::new()).unwrap();
+            rt_state.begin(&gfx);
+            model.draw(&gfx);
+            rt_state.end(&gfx);
+            (gfx.read_render_target(rt_state.get_render_target()).unwrap(),
+                model.get_index_format())
+        };
+        let tri = [
+            vert(-1.0f32, -1.0f32, 0.5f32, Color4::green()),
+            vert(-1.0f32, 3.0f32, 0.5f32, Color4::green()),
+            vert(3.0f32, -1.0f32, 0.5f32, Color4::green())
+        ];
+        let (pixels, format) = render(&tri, &[0, 1, 2]);
+        assert_eq!(format, IndexFormat::U16);
+        assert!(pixels.iter().all(|p| *p == Color4::green()));
+
+        // the triangle's vertices sit past the range 16 bit indices can address
+        let mut verts = vec![vert(0.0f32, 0.0f32, 0.5f32, Color4::red()); 1 << 16];
+        verts.extend_from_slice(&tri);
+        let (pixels, format) = render(&verts, &[1 << 16, (1 << 16) + 1, (1 << 16) + 2]);
+        assert_eq!(format, IndexFormat::U32);
+        assert!(pixels.iter().all(|p| *p == Color4::green()));
+    }
+
+    #[test]
+    fn parts_can_be_hidden_and_transformed() {
+        let tri = |z: f32, color: Color4| vec![
+            vert(-1.0f32, -1.0f32, z, color),
+            vert(-1.0f32, 3.0f32, z, color),
+            vert(3.0f32, -1.0f32, z, color)
+        ];
+        let mut verts = tri(0.75f32, Color4::green());
+        verts.extend(tri(0.25f32, Color4::red()));
+        let draws = vec![
+            DrawData { start_index: 0, num_tris: 1, material_idx: 0 },
+            DrawData { start_index: 3, num_tris: 1, material_idx: 0 }
+        ];
+        let nodes = vec![
+            ModelNode { name: "back".to_string(), parent: None, draws: 0..1 },
+            ModelNode { name: "front".to_string(), parent: Some(0), draws: 1..2 }
+        ];
+
+        let (gfx, rt_state, _cbuff) = setup();
+        let mut model = Model::new(&gfx, &verts, &[0, 1, 2, 3, 4, 5], draws,
+            vec![object_material(&gfx)], nodes).unwrap();
+        let (back, front) = (model.find_node("back").unwrap(), model.find_node("front").unwrap());
+        assert_eq!(model.get_children(back).collect::<Vec<_>>(), vec![front]);
+
+        let render = |model: &Model| {
+            let mut transforms = Vec::new();
+            rt_state.begin(&gfx);
+            model.draw_with_transforms(&gfx, |t| transforms.push(*t));
+            rt_state.end(&gfx);
+            (gfx.read_render_target(rt_state.get_render_target()).unwrap()[0], transforms)
+        };
+
+        model.set_visible(front, false);
+        assert_eq!(render(&model).0, Color4::green());
+        model.isolate(front);
+        assert!(!model.is_visible(back));
+        assert_eq!(render(&model).0, Color4::red());
+
+        // hiding a part hides its children, and children inherit their parent's transform
+        model.set_visible(back, false);
+        assert_eq!(render(&model).0, Color4::black());
+        model.show_all();
+        let moved = Matrix4F::new_translation(&Vector3F::x());
+        model.set_node_transform(back, moved);
+        assert_eq!(render(&model), (Color4::red(), vec![moved]));
+    }
+}
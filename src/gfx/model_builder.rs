@@ -1,74 +1,363 @@
 // external refs
 use tobj;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
 use std::path::{Path, PathBuf};
 
 // local refs
+use crate::error::Error;
 use crate::gfx::*;
 use crate::numerics::*;
 
+mod analyze;
+mod cache;
+mod gltf_loader;
+mod gltf_writer;
+mod normals;
+mod obj_writer;
+mod optimize;
+mod ply_loader;
+mod simplify;
+mod stl_loader;
+mod tangents;
+#[cfg(test)]
+mod test_files;
+
+pub use self::analyze::{DrawReport, MeshReport};
+pub use self::cache::ModelCache;
+pub use self::normals::NormalGeneration;
+pub use self::optimize::OptimizationStats;
+pub use self::simplify::LodTarget;
+
+///
+/// Finds the standard object shader in the resolver's search roots
+///
+fn object_shader(resolver: &AssetResolver) -> PathBuf {
+    // the software backend only needs the name, so a missing file isn't an error here
+    resolver.resolve("shaders/object.hlsl", &[])
+        .unwrap_or_else(|| ["data", "shaders", "object.hlsl"].iter().collect())
+}
+
+///
+/// Creates the description of a material drawn with the standard object shader
+///
+fn object_material(shader_file: &Path, textures: HashMap<String, PathBuf>, params: MaterialParams)
+    -> MaterialInfo {
+
+    MaterialInfo {
+        shader_file: shader_file.to_path_buf(),
+        vert_format: MeshVertex::get_format(),
+        topology: PrimitiveTopology::TriangleList,
+        textures,
+        params
+    }
+}
+
+///
+/// Converts a material read from an MTL file, using the callback to turn texture references into
+/// paths
+///
+fn convert_mtl_material(mat: &tobj::Material, shader_file: &Path,
+    mut resolve_texture: impl FnMut(&str) -> PathBuf) -> MaterialInfo {
+
+    // tobj reads map_Ns into normal_texture and leaves the bump and normal map statements with the
+    // unrecognised parameters
+    let unknown = |key: &str| mat.unknown_param.get(key).map(String::as_str).unwrap_or("");
+    let slots = [
+        ("albedo_map", mat.diffuse_texture.as_str()),
+        ("ambient_map", mat.ambient_texture.as_str()),
+        ("specular_map", mat.specular_texture.as_str()),
+        ("specular_power_map", mat.normal_texture.as_str()),
+        ("alpha_map", mat.dissolve_texture.as_str()),
+        ("normal_map", unknown("norm")),
+        ("bump_map", if unknown("map_bump").is_empty() { unknown("bump") } else { unknown("map_bump") })
+    ];
+
+    let mut textures = HashMap::new();
+    for (slot, value) in slots.iter() {
+        let file = mtl_texture_file(value);
+        if !file.is_empty() {
+            textures.insert(slot.to_string(), resolve_texture(file));
+        }
+    }
+
+    // Tr is the inverse of d and only used when d isn't
+    let transparency = unknown("Tr").parse::<f32>().ok();
+    let alpha = match transparency {
+        Some(tr) if mat.dissolve == 1.0f32 => 1.0f32 - tr,
+        _ => mat.dissolve
+    };
+
+    // some exporters write a black Kd next to map_Kd, meaning the map replaces the color
+    let mut diffuse = mat.diffuse;
+    if diffuse == [0.0f32; 3] && !mat.diffuse_texture.is_empty() {
+        diffuse = [1.0f32; 3];
+    }
+
+    let emissive = unknown("Ke").split_whitespace()
+        .map(|v| v.parse::<f32>().unwrap_or(0.0f32))
+        .chain(std::iter::repeat(0.0f32))
+        .take(3)
+        .collect::<Vec<_>>();
+
+    let [ar, ag, ab] = mat.ambient;
+    let [sr, sg, sb] = mat.specular;
+    let params = MaterialParams {
+        base_color: Color4F::from_rgba(diffuse[0], diffuse[1], diffuse[2], alpha),
+        emissive: Color3F::from_rgb(emissive[0], emissive[1], emissive[2]),
+        alpha_mode: if alpha < 1.0f32 { AlphaMode::Blend } else { AlphaMode::Opaque },
+        ambient: Color3F::from_rgb(ar, ag, ab),
+        specular: Color3F::from_rgb(sr, sg, sb),
+        // an exponent below 1 would light the whole surface with the highlight color
+        specular_power: mat.shininess.max(1.0f32),
+        illumination: mat.illumination_model.map(Illumination::from_mtl)
+            .unwrap_or(Illumination::Specular),
+        ..Default::default()
+    };
+
+    object_material(shader_file, textures, params)
+}
+
+///
+/// Gets the file name from the arguments of an MTL texture statement, skipping any options such
+/// as `-bm 0.5` that come before it
+///
+fn mtl_texture_file(value: &str) -> &str {
+    if value.starts_with('-') {
+        value.split_whitespace().last().unwrap_or("")
+    } else {
+        value.trim()
+    }
+}
+
+///
+/// Checks that every face of an obj file refers to vertex data defined before it, which the
+/// parser would otherwise index out of bounds with. Line elements are blanked out, as the parser
+/// would mix them in with the triangles.
+///
+/// # Returns
+/// The text to parse
+///
+fn check_obj_faces(path: &Path, text: &str) -> Result<String, Error> {
+    // positions, texture coordinates and normals defined so far
+    let mut counts = [0isize; 3];
+    let mut checked = String::with_capacity(text.len());
+
+    for (idx, line) in text.lines().enumerate() {
+        let error = |message: &str| Error::parse(path, Some(idx + 1), message);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => counts[0] += 1,
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("l") => {
+                checked.push('\n');
+                continue;
+            }
+            Some("f") => {
+                let corners: Vec<&str> = words.collect();
+                if corners.len() < 3 {
+                    return Err(error("face needs at least three vertices"));
+                }
+                for corner in corners {
+                    let refs: Vec<&str> = corner.split('/').collect();
+                    if refs.len() > 3 || refs[0].is_empty() {
+                        return Err(error("face vertex must be v, v/vt, v//vn or v/vt/vn"));
+                    }
+                    for (reference, &count) in refs.iter().zip(&counts) {
+                        let valid = match reference.parse::<isize>() {
+                            Ok(i) if i < 0 => i + count >= 0,
+                            Ok(i) => i >= 1 && i <= count,
+                            Err(_) => reference.is_empty()
+                        };
+                        if !valid {
+                            return Err(error(&format!("face refers to missing vertex data {}",
+                                corner)));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        checked.push_str(line);
+        checked.push('\n');
+    }
+
+    Ok(checked)
+}
+
+///
+/// Vertices made from each original vertex by changing some of its attributes
+///
+type VertexVariants = HashMap<u32, Vec<u32>>;
+
 ///
 /// Utility to build or load a model
 ///
+#[derive(Default)]
 pub struct ModelBuilder {
     verts: Vec<MeshVertex>,
     indices: Vec<u32>,
     draws: Vec<DrawData>,
-    materials: Vec<MaterialInfo>
+    materials: Vec<MaterialInfo>,
+    nodes: Vec<ModelNode>,
+    unresolved: Vec<UnresolvedAsset>,
+    /// Files the model was loaded from, which a cache of it depends on
+    sources: Vec<PathBuf>,
+    /// How the mesh changed when it was optimized, if it has been
+    optimization: Option<OptimizationStats>,
+    /// Simplified versions of the model, whose triangles follow the full model's in `indices`
+    lods: Vec<ModelLod>
 }
 
 impl ModelBuilder {
     ///
-    /// Loads a model from an obj file
+    /// Loads a model from an obj, glTF, PLY or STL file, finding its assets with the default
+    /// resolver
     ///
-    pub fn load_from_obj(path: &Path) -> Result<Self, ()> {
-        let (models, materials_in) = tobj::load_obj(path)
-            .map_err(|e|{ println!("Failed to load {:?}. Error: {}", path, e); })?;
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Self::load_with_resolver(path, &AssetResolver::default())
+    }
+
+    ///
+    /// Loads a model, choosing the format from the first bytes of the file when they identify it
+    /// and from the extension otherwise
+    ///
+    pub fn load_with_resolver(path: &Path, resolver: &AssetResolver) -> Result<Self, Error> {
+        let mut magic = Vec::with_capacity(4);
+        fs::File::open(path)
+            .and_then(|file| file.take(4).read_to_end(&mut magic))
+            .map_err(|e| Error::io(path, e))?;
+        if magic.starts_with(b"ply") {
+            return Self::load_from_ply_with_resolver(path, resolver);
+        }
+        if magic.starts_with(b"glTF") {
+            return Self::load_from_gltf_with_resolver(path, resolver);
+        }
 
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("obj") => Self::load_from_obj_with_resolver(path, resolver),
+            Some("gltf") | Some("glb") => Self::load_from_gltf_with_resolver(path, resolver),
+            Some("ply") => Self::load_from_ply_with_resolver(path, resolver),
+            Some("stl") => Self::load_from_stl_with_resolver(path, resolver),
+            _ => Err(Error::UnsupportedFormat { what: "model format".to_string(),
+                path: Some(path.to_path_buf()) })
+        }
+    }
+
+    ///
+    /// Saves the model to an obj, glTF or binary glTF file, choosing the format from the extension
+    ///
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("obj") => self.save_obj(path),
+            Some("gltf") => self.save_gltf(path),
+            Some("glb") => self.save_glb(path),
+            _ => Err(Error::UnsupportedFormat { what: "format to save models in".to_string(),
+                path: Some(path.to_path_buf()) })
+        }
+    }
+
+    ///
+    /// Loads a model from an obj file, finding its materials and textures with the default
+    /// resolver
+    ///
+    pub fn load_from_obj(path: &Path) -> Result<Self, Error> {
+        Self::load_from_obj_with_resolver(path, &AssetResolver::default())
+    }
+
+    ///
+    /// Loads a model from an obj file. Material libraries are looked for next to the obj file and
+    /// textures next to the material library that uses them, then next to the obj file, then in
+    /// the resolver's search roots. Files that can't be found are listed by
+    /// `get_unresolved_assets` and the model is loaded without them.
+    ///
+    pub fn load_from_obj_with_resolver(path: &Path, resolver: &AssetResolver)
+        -> Result<Self, Error> {
+
+        let obj_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let data = fs::read(path).map_err(|e| Error::io(path, e))?;
+        let text = check_obj_faces(path, &String::from_utf8_lossy(&data))?;
+        let unresolved = RefCell::new(Vec::new());
+        // each material library's path, with the number of materials it added
+        let libraries = RefCell::new(Vec::<(PathBuf, usize)>::new());
+
+        let (models, materials_in) = tobj::load_obj_buf(&mut text.as_bytes(), |mtl_ref| {
+            let reference = mtl_ref.to_string_lossy();
+            match resolver.resolve(&reference, &[obj_dir]) {
+                Some(mtl_path) => {
+                    let (mats, names) = tobj::load_mtl(&mtl_path)?;
+                    libraries.borrow_mut().push((mtl_path, mats.len()));
+                    Ok((mats, names))
+                }
+                None => {
+                    unresolved.borrow_mut().push(UnresolvedAsset {
+                        reference: reference.into_owned(),
+                        referenced_by: path.to_path_buf()
+                    });
+                    Ok((Vec::new(), HashMap::new()))
+                }
+            }
+        }).map_err(|e| Error::parse(path, None, e.to_string()))?;
+
+        let mut unresolved = unresolved.into_inner();
+        let libraries = libraries.into_inner();
         let mut verts = Vec::new();
         let mut indices = Vec::new();
         let mut draws = Vec::with_capacity(models.len());
         let mut materials = Vec::with_capacity(materials_in.len());
 
-        for mat in materials_in {
-            let mut textures = HashMap::with_capacity(1);
-            let tex_path: PathBuf = ["data\\objects", &mat.diffuse_texture].iter().collect();
-            textures.insert("albedo_map".to_string(), tex_path);
-            let mat_info = MaterialInfo {
-                shader_file: PathBuf::from("data\\shaders\\object.hlsl"),
-                vert_format: MeshVertex::get_format(),
-                topology: PrimitiveTopology::TriangleList,
-                textures,
-            };
-            materials.push(mat_info);
+        let shader_file = object_shader(resolver);
+        let mtl_paths = libraries.iter()
+            .flat_map(|(mtl_path, count)| std::iter::repeat_n(mtl_path, *count));
+        for (mat, mtl_path) in materials_in.iter().zip(mtl_paths) {
+            let mtl_dir = mtl_path.parent().unwrap_or_else(|| Path::new(""));
+            materials.push(convert_mtl_material(mat, &shader_file, |file| {
+                resolver.resolve_or_record(file, &[mtl_dir, obj_dir], mtl_path, &mut unresolved)
+            }));
         }
+        let mut default_material = None;
+        let mut missing_normals = Vec::new();
+        let mut textured = Vec::new();
+        let mut nodes = Vec::<ModelNode>::new();
 
         for model in models {
             let idx_offs = indices.len() as u32;
+            let base_vertex = verts.len() as u32;
             let num_verts = model.mesh.positions.len() / 3;
             let num_tris = model.mesh.indices.len() / 3;
+            // faces that leave out normals or texture coordinates leave the others without them
+            let has_normals = model.mesh.normals.len() == num_verts * 3;
+            let has_uvs = model.mesh.texcoords.len() == num_verts * 2;
 
             indices.reserve(model.mesh.indices.len());
             verts.reserve(num_verts);
 
             for i in 0..num_verts {
-                let mut v = MeshVertex::default();
-                v.pos = Point3::new(
-                    model.mesh.positions[i * 3 + 0],
-                    model.mesh.positions[i * 3 + 1],
-                    model.mesh.positions[i * 3 + 2]
-                );
-                if !model.mesh.normals.is_empty() {
+                let mut v = MeshVertex {
+                    pos: Point3::new(
+                        model.mesh.positions[i * 3],
+                        model.mesh.positions[i * 3 + 1],
+                        model.mesh.positions[i * 3 + 2]
+                    ),
+                    ..Default::default()
+                };
+                if has_normals {
                     v.norm = Vector3::new(
-                        model.mesh.normals[i * 3 + 0],
+                        model.mesh.normals[i * 3],
                         model.mesh.normals[i * 3 + 1],
                         model.mesh.normals[i * 3 + 2]
                     );
                 }
-                if !model.mesh.texcoords.is_empty() {
+                if has_uvs {
+                    // obj's v points up the image
                     v.uv = Vector2::new(
-                        model.mesh.texcoords[i * 2 + 0],
-                        model.mesh.texcoords[i * 2 + 1]
+                        model.mesh.texcoords[i * 2],
+                        1.0f32 - model.mesh.texcoords[i * 2 + 1]
                     );
                 }
 
@@ -78,32 +367,351 @@ impl ModelBuilder {
             // wind the triangles differently to support our front-facing winding
             for i in 0..num_tris {
                 let tri = &model.mesh.indices[(i * 3)..(i * 3 + 3)];
-                indices.push(tri[0]);
-                indices.push(tri[2]);
-                indices.push(tri[1]);
+                indices.push(base_vertex + tri[0]);
+                indices.push(base_vertex + tri[2]);
+                indices.push(base_vertex + tri[1]);
             }
 
+            // meshes without a usemtl share a material with default parameters
+            let material_idx = match model.mesh.material_id {
+                Some(idx) if idx < materials_in.len() => idx,
+                _ => *default_material.get_or_insert_with(|| {
+                    materials.push(object_material(&shader_file, HashMap::new(),
+                        MaterialParams::default()));
+                    materials.len() - 1
+                })
+            };
+            if !has_normals {
+                missing_normals.push(draws.len());
+            }
+            if has_uvs {
+                textured.push(draws.len());
+            }
             let draw = DrawData {
                 start_index: idx_offs,
                 num_tris: (model.mesh.indices.len() / 3) as u32,
-                material_idx: model.mesh.material_id.unwrap_or(0xFFFFFFFF) as u32
+                material_idx: material_idx as u32
             };
             draws.push(draw);
+
+            // tobj splits an object into one model per material, so they're joined back together
+            match nodes.last_mut() {
+                Some(node) if node.name == model.name => node.draws.end = draws.len(),
+                _ => nodes.push(ModelNode {
+                    name: model.name,
+                    parent: None,
+                    draws: draws.len() - 1..draws.len()
+                })
+            }
+        }
+
+        let sources = std::iter::once(path.to_path_buf())
+            .chain(libraries.into_iter().map(|(mtl_path, _)| mtl_path))
+            .collect();
+        let mut builder = Self { verts, indices, draws, materials, nodes, unresolved, sources,
+            optimization: None, lods: Vec::new() };
+        builder.generate_normals_for_draws(&missing_normals, NormalGeneration::default());
+        builder.generate_tangents_for_draws(&textured);
+        Ok(builder)
+    }
+
+    ///
+    /// Creates an empty model for a file in a format without materials, with a single material
+    /// using default parameters
+    ///
+    fn with_default_material(path: &Path, resolver: &AssetResolver) -> Self {
+        let material = object_material(&object_shader(resolver), HashMap::new(),
+            MaterialParams::default());
+        Self { materials: vec![material], sources: vec![path.to_path_buf()], ..Default::default() }
+    }
+
+    ///
+    /// Appends a mesh as a named part drawn with the first material. Triangles are given
+    /// counter-clockwise, as most formats store them, and are flipped to our clockwise winding.
+    ///
+    /// # Returns
+    /// The index of the mesh's draw
+    ///
+    fn add_mesh(&mut self, name: String, mut verts: Vec<MeshVertex>, tris: &[[u32; 3]]) -> usize {
+        let base_vertex = self.verts.len() as u32;
+        let start_index = self.indices.len() as u32;
+        for t in tris {
+            self.indices.extend_from_slice(&[base_vertex + t[0], base_vertex + t[2],
+                base_vertex + t[1]]);
+        }
+        self.verts.append(&mut verts);
+
+        self.draws.push(DrawData { start_index, num_tris: tris.len() as u32, material_idx: 0 });
+        let draw = self.draws.len() - 1;
+        self.nodes.push(ModelNode { name, parent: None, draws: draw..draw + 1 });
+        draw
+    }
+
+    ///
+    /// Gets the vertices used by a draw, in their original order, and its triangles indexing
+    /// them, wound counter-clockwise as files store them
+    ///
+    fn get_draw_mesh(&self, draw: &DrawData) -> (Vec<u32>, Vec<[u32; 3]>) {
+        let start = draw.start_index as usize;
+        let indices = &self.indices[start..start + draw.num_tris as usize * 3];
+        let mut used: Vec<u32> = indices.to_vec();
+        used.sort_unstable();
+        used.dedup();
+
+        let local = |idx: u32| used.binary_search(&idx).unwrap_or_default() as u32;
+        let tris = indices.chunks_exact(3)
+            .map(|t| [local(t[0]), local(t[2]), local(t[1])])
+            .collect();
+        (used, tris)
+    }
+
+    ///
+    /// Gets the parts to write to a file. Draws that don't belong to any part are put in extra
+    /// parts named after the file, so saving doesn't lose them.
+    ///
+    fn get_parts_for_saving(&self, path: &Path) -> Vec<ModelNode> {
+        let mut parts = self.nodes.clone();
+        let mut owned = vec![false; self.draws.len()];
+        for node in &self.nodes {
+            owned[node.draws.clone()].iter_mut().for_each(|o| *o = true);
+        }
+
+        // consecutive loose draws share a part
+        let name = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let mut loose: Option<usize> = None;
+        for draw in (0..self.draws.len()).filter(|&draw| !owned[draw]) {
+            match loose {
+                Some(part) if parts[part].draws.end == draw => parts[part].draws.end = draw + 1,
+                _ => {
+                    let draws = draw..draw + 1;
+                    parts.push(ModelNode { name: name.clone(), parent: None, draws });
+                    loose = Some(parts.len() - 1);
+                }
+            }
         }
+        parts
+    }
 
-        Ok(Self { verts, indices, draws, materials })
+    ///
+    /// Overrides the shader used by every material in the model
+    ///
+    pub fn with_shader(mut self, shader_file: &Path) -> Self {
+        for mat in &mut self.materials {
+            mat.shader_file = shader_file.to_path_buf();
+        }
+        self
+    }
+
+    ///
+    /// Gets a vertex that's a copy of the original with some attributes changed. The first
+    /// variant made replaces the original, later ones reuse an earlier variant that `matches` or
+    /// are appended as new vertices.
+    ///
+    fn vertex_variant(&mut self, variants: &mut VertexVariants, original: u32,
+        matches: impl Fn(&MeshVertex) -> bool, apply: impl FnOnce(&mut MeshVertex)) -> u32 {
+
+        let made = variants.entry(original).or_default();
+        if let Some(idx) = made.iter().find(|&&idx| matches(&self.verts[idx as usize])) {
+            return *idx;
+        }
+
+        let idx = if made.is_empty() {
+            original
+        } else {
+            self.verts.push(self.verts[original as usize]);
+            (self.verts.len() - 1) as u32
+        };
+        apply(&mut self.verts[idx as usize]);
+        made.push(idx);
+        idx
+    }
+
+    ///
+    /// Gets the named parts of the model
+    ///
+    pub fn get_nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    ///
+    /// Gets the files the model refers to that couldn't be found when it was loaded
+    ///
+    pub fn get_unresolved_assets(&self) -> &[UnresolvedAsset] {
+        &self.unresolved
+    }
+
+    ///
+    /// Gets the minimum and maximum corners of the box enclosing every vertex
+    ///
+    pub fn get_bounds(&self) -> Option<(Point3F, Point3F)> {
+        let first = self.verts.first()?.pos;
+        Some(self.verts.iter().fold((first, first), |(min, max), v| {
+            (Point3F::from(min.coords.zip_map(&v.pos.coords, f32::min)),
+                Point3F::from(max.coords.zip_map(&v.pos.coords, f32::max)))
+        }))
     }
 
     ///
     /// Finalizes and builds the model
     ///
-    pub fn build(self, gfx: &Graphics) -> Result<Model, ()> {
+    pub fn build(self, gfx: &Graphics) -> Result<Model, Error> {
+        self.build_with_stats(gfx).map(|(model, _)| model)
+    }
+
+    ///
+    /// Optimizes the mesh for rendering, unless that's already been done, then builds the model
+    ///
+    /// # Returns
+    /// The model, and how the optimization changed its vertex count and cache efficiency
+    ///
+    pub fn build_with_stats(mut self, gfx: &Graphics) -> Result<(Model, OptimizationStats), Error> {
+        let stats = match self.optimization {
+            Some(stats) => stats,
+            None => self.optimize()
+        };
+
         // TODO: stop hardcoding material shader & get from obj data
-        let vfmt = MeshVertex::get_format();
         let mut mats = Vec::with_capacity(self.materials.len());
         for mat in self.materials {
             mats.push(Material::load(gfx, &mat)?);
         }
-        Model::new(gfx, &self.verts, &self.indices, self.draws, mats)
+        let model = Model::new(gfx, &self.verts, &self.indices, self.draws, mats, self.nodes)?
+            .with_lods(self.lods)?;
+        Ok((model, stats))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(mat: &tobj::Material) -> MaterialInfo {
+        convert_mtl_material(mat, Path::new("object.hlsl"), AssetResolver::normalize)
+    }
+
+    #[test]
+    fn converts_mtl_parameters() {
+        let mut mat = tobj::Material::empty();
+        mat.diffuse = [0.5f32, 0.25f32, 1.0f32];
+        mat.ambient = [0.1f32; 3];
+        mat.specular = [0.8f32; 3];
+        mat.shininess = 64.0f32;
+        mat.illumination_model = Some(1);
+        mat.diffuse_texture = "albedo.png".to_string();
+        mat.unknown_param.insert("Tr".to_string(), "0.25".to_string());
+        mat.unknown_param.insert("map_bump".to_string(), "-bm 0.5 bump.png".to_string());
+
+        let info = convert(&mat);
+        let params = &info.params;
+        assert_eq!(params.base_color, Color4F::from_rgba(0.5f32, 0.25f32, 1.0f32, 0.75f32));
+        assert_eq!(params.ambient, Color3F::from_rgb(0.1f32, 0.1f32, 0.1f32));
+        assert_eq!(params.specular, Color3F::from_rgb(0.8f32, 0.8f32, 0.8f32));
+        assert_eq!(params.specular_power, 64.0f32);
+        assert_eq!(params.illumination, Illumination::Diffuse);
+        assert_eq!(params.alpha_mode, AlphaMode::Blend);
+
+        let texture = |slot: &str| info.textures.get(slot).and_then(|p| p.file_name())
+            .and_then(|f| f.to_str());
+        assert_eq!(texture("albedo_map"), Some("albedo.png"));
+        assert_eq!(texture("bump_map"), Some("bump.png"));
+        assert_eq!(info.textures.len(), 2);
+    }
+
+    #[test]
+    fn textured_black_diffuse_becomes_white() {
+        let mut mat = tobj::Material::empty();
+        mat.diffuse_texture = "brick.tga".to_string();
+        assert_eq!(convert(&mat).params.base_color, Color4F::white());
+
+        mat.diffuse_texture.clear();
+        assert_eq!(convert(&mat).params.base_color,
+            Color4F::from_rgba(0.0f32, 0.0f32, 0.0f32, 1.0f32));
+    }
+
+    #[test]
+    fn meshes_without_materials_share_a_default() {
+        let path = Path::new("tests").join("assets").join("no_material.obj");
+        let builder = ModelBuilder::load_from_obj(&path).unwrap();
+        assert_eq!(builder.materials.len(), 1);
+        assert_eq!(builder.materials[0].params, MaterialParams::default());
+        assert!(builder.draws.iter().all(|d| d.material_idx == 0));
+
+        // the second object's triangle refers to its own vertices
+        assert_eq!(builder.verts.len(), 6);
+        assert_eq!(&builder.indices[3..], &[3, 5, 4]);
+
+        // the file has no normals, so they're generated facing the way the triangles are wound
+        assert!(builder.verts.iter().all(|v| v.norm == Vector3F::z()));
+
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        assert!(builder.build(&gfx).is_ok());
+    }
+
+    #[test]
+    fn objects_become_named_parts() {
+        let path = Path::new("tests").join("assets").join("parts.obj");
+        let builder = ModelBuilder::load_from_obj(&path).unwrap();
+        assert_eq!(builder.draws.len(), 3);
+
+        // the body's two materials make two draws, but it's still one part
+        let parts: Vec<_> = builder.get_nodes().iter()
+            .map(|node| (node.name.as_str(), node.parent, node.draws.clone()))
+            .collect();
+        assert_eq!(parts, vec![("body", None, 0..2), ("wheel", None, 2..3)]);
+    }
+
+    #[test]
+    fn resolves_and_reports_referenced_files() {
+        let assets = Path::new("tests").join("assets");
+        let resolver = AssetResolver::new().with_search_root(Path::new("data").join("objects"));
+        let builder = ModelBuilder::load_from_obj_with_resolver(&assets.join("library.obj"),
+            &resolver).unwrap();
+
+        // the library is found despite the Windows separator and the texture by its file name
+        let textures = &builder.materials[0].textures;
+        assert_eq!(textures["albedo_map"], Path::new("data").join("objects").join("brick.tga"));
+        assert_eq!(textures["bump_map"], assets.join("materials").join("missing.png"));
+
+        let mtl_path = assets.join("materials").join("library.mtl");
+        let unresolved = builder.get_unresolved_assets().iter()
+            .map(|a| (a.reference.as_str(), a.referenced_by.as_path()))
+            .collect::<Vec<_>>();
+        assert_eq!(unresolved, vec![("nowhere.mtl", assets.join("library.obj").as_path()),
+            ("missing.png", mtl_path.as_path())]);
+    }
+
+    #[test]
+    fn load_picks_the_format() {
+        let assets = Path::new("tests").join("assets");
+        assert_eq!(ModelBuilder::load(&assets.join("parts.obj")).unwrap().draws.len(), 3);
+        assert_eq!(ModelBuilder::load(&assets.join("quad.gltf")).unwrap().get_nodes().len(), 2);
+
+        // the PLY magic identifies the file whatever its extension
+        let copy = test_files::scratch_path("colored.dat");
+        fs::copy(assets.join("colored.ply"), &copy).unwrap();
+        let builder = ModelBuilder::load(&copy);
+        fs::remove_file(&copy).unwrap();
+        let builder = builder.unwrap();
+        assert_eq!(builder.indices.len(), 6 * 3);
+        assert!(builder.verts.iter().any(|v| v.color == Color4::from_rgb(0, 0, 255)));
+
+        let unknown = ModelBuilder::load(&assets.join("parts.mtl"));
+        assert!(matches!(unknown, Err(Error::UnsupportedFormat { .. })));
+    }
+
+    #[test]
+    fn checks_obj_face_references() {
+        let path = Path::new("bad.obj");
+        let text = "v 0 0 0\nv 1 0 0\nvt 0 0\nf 1/1 2/1 -2/-1\nl 1 2\nf 1 2 3\n";
+        assert!(matches!(check_obj_faces(path, text), Err(Error::Parse { line: Some(6), .. })));
+
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nl 1 2\nf 1//1 2 3\n";
+        assert!(matches!(check_obj_faces(path, text), Err(Error::Parse { line: Some(5), .. })));
+
+        // lines are dropped so the parser doesn't mix them in with the triangles
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nl 1 2\nf -3 -2 -1\n";
+        assert_eq!(check_obj_faces(path, text).unwrap(),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf -3 -2 -1\n");
+        assert!(matches!(check_obj_faces(path, "f 1 2 3\n"), Err(Error::Parse { .. })));
+    }
+}
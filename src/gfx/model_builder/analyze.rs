@@ -0,0 +1,305 @@
+// external refs
+use std::collections::HashMap;
+use std::fmt;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Counts and problems found in a single draw
+///
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct DrawReport {
+    pub material_idx: u32,
+    /// Distinct vertices used by the draw's triangles
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    /// Triangles with two corners at the same position, usually from using a vertex twice
+    pub degenerate_triangles: usize,
+    /// Triangles with corners at three different positions that still have no area, because the
+    /// corners lie on a line
+    pub zero_area_triangles: usize,
+    /// Indices past the end of the vertices, including any the draw expects past the end of the
+    /// indices
+    pub out_of_range_indices: usize
+}
+
+///
+/// Statistics and problems found in a model's mesh. Edges are matched by position, so the seams
+/// where vertices are split for differing normals or texture coordinates aren't boundaries.
+///
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshReport {
+    pub vertex_count: usize,
+    pub triangle_count: usize,
+    pub draws: Vec<DrawReport>,
+    /// Vertices that no triangle uses
+    pub unused_vertices: usize,
+    /// Vertices with a NaN or infinite position, normal, tangent or texture coordinate
+    pub non_finite_vertices: usize,
+    /// The smallest and largest texture coordinates the triangles use, if any are finite
+    pub uv_range: Option<(Vector2F, Vector2F)>,
+    /// Edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// Edges used by a single triangle
+    pub boundary_edges: usize,
+    /// Connected chains of boundary edges, each one a hole or the open border of a surface
+    pub boundary_loops: usize,
+    /// Edges between two triangles that run along it in the same direction, so one of them faces
+    /// the opposite way to the other
+    pub inconsistent_winding_edges: usize
+}
+
+impl MeshReport {
+    ///
+    /// Checks whether anything was found that could draw incorrectly or fail to load. Boundaries
+    /// aren't counted, as plenty of models are open surfaces on purpose.
+    ///
+    pub fn has_problems(&self) -> bool {
+        self.unused_vertices > 0 || self.non_finite_vertices > 0 || self.non_manifold_edges > 0
+            || self.inconsistent_winding_edges > 0
+            || self.draws.iter().any(|d| d.degenerate_triangles > 0 || d.zero_area_triangles > 0
+                || d.out_of_range_indices > 0)
+    }
+}
+
+impl fmt::Display for MeshReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} vertices, {} triangles in {} draws", self.vertex_count,
+            self.triangle_count, self.draws.len())?;
+        for (idx, draw) in self.draws.iter().enumerate() {
+            write!(f, "  draw {} (material {}): {} vertices, {} triangles", idx,
+                draw.material_idx, draw.vertex_count, draw.triangle_count)?;
+            let problems = [(draw.degenerate_triangles, "degenerate triangles"),
+                (draw.zero_area_triangles, "zero-area triangles"),
+                (draw.out_of_range_indices, "out of range indices")];
+            for (count, what) in problems.iter().filter(|(count, _)| *count > 0) {
+                write!(f, ", {} {}", count, what)?;
+            }
+            writeln!(f)?;
+        }
+
+        match self.uv_range {
+            Some((min, max)) => writeln!(f, "UV range: ({}, {}) to ({}, {})", min.x, min.y, max.x,
+                max.y)?,
+            None => writeln!(f, "UV range: none")?
+        }
+        writeln!(f, "Unused vertices: {}", self.unused_vertices)?;
+        writeln!(f, "Non-finite vertices: {}", self.non_finite_vertices)?;
+        writeln!(f, "Non-manifold edges: {}", self.non_manifold_edges)?;
+        writeln!(f, "Boundary edges: {} in {} loops", self.boundary_edges, self.boundary_loops)?;
+        write!(f, "Inconsistently wound edges: {}", self.inconsistent_winding_edges)
+    }
+}
+
+///
+/// Finds the root of an element in a disjoint set forest, halving the path on the way
+///
+fn find_root(parents: &mut [usize], mut idx: usize) -> usize {
+    while parents[idx] != idx {
+        parents[idx] = parents[parents[idx]];
+        idx = parents[idx];
+    }
+    idx
+}
+
+impl ModelBuilder {
+    ///
+    /// Numbers the distinct vertex positions, joining the vertices split along seams. -0 and 0
+    /// are treated as the same coordinate.
+    ///
+    /// # Returns
+    /// The position of each vertex, and the number of positions
+    ///
+    pub(super) fn get_position_ids(&self) -> (Vec<usize>, usize) {
+        let mut positions = HashMap::<[u32; 3], usize>::new();
+        let ids = self.verts.iter().map(|v| {
+            let key = [v.pos.x + 0.0f32, v.pos.y + 0.0f32, v.pos.z + 0.0f32].map(f32::to_bits);
+            let next = positions.len();
+            *positions.entry(key).or_insert(next)
+        }).collect();
+        (ids, positions.len())
+    }
+
+    ///
+    /// Counts the vertices and triangles of the model and checks it for problems that show up as
+    /// rendering glitches, such as invalid indices, degenerate triangles and broken topology
+    ///
+    pub fn analyze(&self) -> MeshReport {
+        let mut report = MeshReport {
+            vertex_count: self.verts.len(),
+            ..Default::default()
+        };
+        report.non_finite_vertices = self.verts.iter().filter(|v| {
+            !(v.pos.coords.iter().chain(v.norm.iter()).chain(v.tangent.iter()).chain(v.uv.iter())
+                .all(|c| c.is_finite()))
+        }).count();
+
+        let (position_ids, num_positions) = self.get_position_ids();
+
+        // how often each edge is used from its lower to its higher position, and the other way
+        let mut edges = HashMap::<(usize, usize), (usize, usize)>::new();
+        let mut used = vec![false; self.verts.len()];
+        let mut uv_range: Option<(Vector2F, Vector2F)> = None;
+
+        for draw in &self.draws {
+            let start = (draw.start_index as usize).min(self.indices.len());
+            let end = (start + draw.num_tris as usize * 3).min(self.indices.len());
+            let mut draw_report = DrawReport {
+                material_idx: draw.material_idx,
+                triangle_count: draw.num_tris as usize,
+                out_of_range_indices: draw.num_tris as usize * 3 - (end - start),
+                ..Default::default()
+            };
+            let mut draw_verts = Vec::new();
+
+            for tri in self.indices[start..end].chunks_exact(3) {
+                let in_range = tri.iter().filter(|&&idx| (idx as usize) < self.verts.len()).count();
+                draw_report.out_of_range_indices += 3 - in_range;
+                if in_range < 3 {
+                    continue;
+                }
+                for &idx in tri {
+                    let v = &self.verts[idx as usize];
+                    used[idx as usize] = true;
+                    draw_verts.push(idx);
+                    if v.uv.iter().all(|c| c.is_finite()) {
+                        uv_range = Some(uv_range.map_or((v.uv, v.uv), |(min, max)| {
+                            (min.zip_map(&v.uv, f32::min), max.zip_map(&v.uv, f32::max))
+                        }));
+                    }
+                }
+
+                // loaders split vertices by normal, so repeated corners are found by position
+                let ids = [tri[0], tri[1], tri[2]].map(|idx| position_ids[idx as usize]);
+                if ids[0] == ids[1] || ids[1] == ids[2] || ids[2] == ids[0] {
+                    draw_report.degenerate_triangles += 1;
+                    continue;
+                }
+                let [a, b, c] = [tri[0], tri[1], tri[2]].map(|idx| self.verts[idx as usize].pos);
+                let longest = (b - a).norm_squared().max((c - b).norm_squared())
+                    .max((a - c).norm_squared());
+                if (b - a).cross(&(c - a)).norm() <= f32::EPSILON * longest {
+                    draw_report.zero_area_triangles += 1;
+                    continue;
+                }
+
+                for corner in 0..3 {
+                    let (from, to) = (ids[corner], ids[(corner + 1) % 3]);
+                    let uses = edges.entry((from.min(to), from.max(to))).or_insert((0, 0));
+                    if from < to { uses.0 += 1 } else { uses.1 += 1 }
+                }
+            }
+
+            draw_verts.sort_unstable();
+            draw_verts.dedup();
+            draw_report.vertex_count = draw_verts.len();
+            report.triangle_count += draw_report.triangle_count;
+            report.draws.push(draw_report);
+        }
+
+        report.unused_vertices = used.iter().filter(|&&used| !used).count();
+        report.uv_range = uv_range;
+
+        let mut loop_parents: Vec<usize> = (0..num_positions).collect();
+        let mut boundary_positions = Vec::new();
+        for (&(a, b), &(forward, backward)) in &edges {
+            match forward + backward {
+                1 => {
+                    report.boundary_edges += 1;
+                    let (root_a, root_b) = (find_root(&mut loop_parents, a),
+                        find_root(&mut loop_parents, b));
+                    loop_parents[root_a] = root_b;
+                    boundary_positions.push(a);
+                }
+                2 if forward != backward => report.inconsistent_winding_edges += 1,
+                2 => {}
+                _ => report.non_manifold_edges += 1
+            }
+        }
+        let mut loops: Vec<usize> = boundary_positions.into_iter()
+            .map(|p| find_root(&mut loop_parents, p))
+            .collect();
+        loops.sort_unstable();
+        loops.dedup();
+        report.boundary_loops = loops.len();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::object_material;
+    use std::path::Path;
+
+    ///
+    /// Builds a model from positions and triangles in a single draw, without touching the winding
+    ///
+    fn builder(positions: &[[f32; 3]], indices: &[u32]) -> ModelBuilder {
+        ModelBuilder {
+            verts: positions.iter().map(|p| MeshVertex {
+                pos: Point3F::new(p[0], p[1], p[2]),
+                ..Default::default()
+            }).collect(),
+            indices: indices.to_vec(),
+            draws: vec![DrawData { start_index: 0, num_tris: indices.len() as u32 / 3,
+                material_idx: 0 }],
+            materials: vec![object_material(Path::new("object.hlsl"), HashMap::new(),
+                MaterialParams::default())],
+            ..Default::default()
+        }
+    }
+
+    const TETRAHEDRON: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0]];
+
+    #[test]
+    fn closed_mesh_has_no_problems() {
+        let report = builder(&TETRAHEDRON, &[0, 1, 2, 0, 3, 1, 1, 3, 2, 2, 3, 0]).analyze();
+        assert_eq!(report.triangle_count, 4);
+        assert_eq!(report.draws[0].vertex_count, 4);
+        assert_eq!((report.boundary_edges, report.boundary_loops), (0, 0));
+        assert_eq!(report.non_manifold_edges, 0);
+        assert_eq!(report.inconsistent_winding_edges, 0);
+        assert_eq!(report.uv_range, Some((Vector2F::zeros(), Vector2F::zeros())));
+        assert!(!report.has_problems(), "{}", report);
+    }
+
+    #[test]
+    fn finds_boundaries_and_flipped_triangles() {
+        // the tetrahedron without its base, then with one side flipped
+        let open = builder(&TETRAHEDRON, &[0, 3, 1, 1, 3, 2, 2, 3, 0]).analyze();
+        assert_eq!((open.boundary_edges, open.boundary_loops), (3, 1));
+        assert!(!open.has_problems());
+
+        let flipped = builder(&TETRAHEDRON, &[0, 1, 2, 0, 1, 3, 1, 3, 2, 2, 3, 0]).analyze();
+        assert_eq!(flipped.inconsistent_winding_edges, 3);
+
+        // three triangles on one edge
+        let fin = builder(&[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, -1.0, 0.0],
+            [0.0, 0.0, 1.0]], &[0, 1, 2, 1, 0, 3, 0, 1, 4]).analyze();
+        assert_eq!(fin.non_manifold_edges, 1);
+    }
+
+    #[test]
+    fn counts_bad_triangles_and_vertices() {
+        let mut positions = TETRAHEDRON.to_vec();
+        positions.push([2.0, 0.0, 0.0]);
+        positions.push([f32::NAN, 0.0, 0.0]);
+        let mut bad = builder(&positions, &[0, 1, 2, 0, 0, 1, 0, 1, 4, 0, 1, 9]);
+        bad.draws[0].num_tris += 1;
+        let report = bad.analyze();
+
+        let draw = report.draws[0];
+        assert_eq!(draw.degenerate_triangles, 1);
+        assert_eq!(draw.zero_area_triangles, 1);
+        assert_eq!(draw.out_of_range_indices, 4);
+        assert_eq!(report.unused_vertices, 2);
+        assert_eq!(report.non_finite_vertices, 1);
+        assert!(report.has_problems());
+        assert!(report.to_string().contains("1 degenerate triangles"), "{}", report);
+    }
+}
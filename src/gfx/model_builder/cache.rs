@@ -0,0 +1,523 @@
+// external refs
+use crc32fast;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Identifies a model cache file
+///
+const CACHE_MAGIC: [u8; 4] = *b"MVMC";
+
+///
+/// Version of the cache layout, to be bumped whenever it or the meaning of `MeshVertex` changes
+///
+const CACHE_VERSION: u32 = 3;
+
+///
+/// Written in native byte order, so caches made on a machine with the other order are rebuilt
+///
+const BYTE_ORDER_MARK: u32 = 0x0102_0304;
+
+///
+/// Vertex and index data start on a multiple of this many bytes from the start of the file
+///
+const DATA_ALIGNMENT: usize = 16;
+
+///
+/// Gets the CRC-32 of a file's contents
+///
+fn checksum_file(path: &Path) -> Result<u32, Error> {
+    let data = fs::read(path).map_err(|e| Error::io(path, e))?;
+    Ok(crc32fast::hash(&data))
+}
+
+///
+/// Appends the fields of a cache file in native byte order
+///
+#[derive(Default)]
+struct CacheWriter {
+    data: Vec<u8>
+}
+
+impl CacheWriter {
+    fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.data.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for &value in values {
+            self.f32(value);
+        }
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.data.extend_from_slice(value.as_bytes());
+    }
+
+    fn path(&mut self, value: &Path) {
+        self.str(&value.to_string_lossy());
+    }
+
+    ///
+    /// Pads the data so the next field starts at an aligned offset
+    ///
+    fn align(&mut self) {
+        let padding = self.data.len().next_multiple_of(DATA_ALIGNMENT) - self.data.len();
+        self.data.extend(std::iter::repeat_n(0u8, padding));
+    }
+
+    fn params(&mut self, params: &MaterialParams) {
+        let c = params.base_color;
+        self.f32s(&[c.r, c.g, c.b, c.a, params.metallic, params.roughness]);
+        for c in [params.emissive, params.ambient, params.specular].iter() {
+            self.f32s(&[c.r, c.g, c.b]);
+        }
+        let (mode, cutoff) = match params.alpha_mode {
+            AlphaMode::Opaque => (0, 0.0f32),
+            AlphaMode::Mask(cutoff) => (1, cutoff),
+            AlphaMode::Blend => (2, 0.0f32)
+        };
+        self.u32(mode);
+        self.f32(cutoff);
+        self.u32(params.double_sided as u32);
+        self.f32(params.specular_power);
+        self.u32(match params.illumination {
+            Illumination::Constant => 0,
+            Illumination::Diffuse => 1,
+            Illumination::Specular => 2
+        });
+    }
+}
+
+///
+/// Reads the fields of a cache file, failing on data that runs past the end
+///
+struct CacheReader<'a> {
+    path: &'a Path,
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> CacheReader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len())
+            .ok_or_else(|| Error::parse(self.path, None, "model cache is truncated"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn f32(&mut self) -> Result<f32, Error> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn color3(&mut self) -> Result<Color3F, Error> {
+        Ok(Color3F::from_rgb(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn point3(&mut self) -> Result<Point3F, Error> {
+        Ok(Point3F::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn str(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| Error::parse(self.path, None, "model cache has a malformed string"))
+    }
+
+    fn path(&mut self) -> Result<PathBuf, Error> {
+        self.str().map(PathBuf::from)
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(DATA_ALIGNMENT);
+    }
+
+    ///
+    /// Reads a count followed by that many items. Counts are checked against the bytes left, so a
+    /// corrupt count can't make the reader allocate more than the file could hold.
+    ///
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, Error>)
+        -> Result<Vec<T>, Error> {
+
+        let count = self.u32()? as usize;
+        if count > self.data.len() - self.pos {
+            return Err(Error::parse(self.path, None, "model cache is truncated"));
+        }
+        (0..count).map(|_| item(self)).collect()
+    }
+
+    fn params(&mut self) -> Result<MaterialParams, Error> {
+        let base_color = Color4F::from_rgba(self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+        let (metallic, roughness) = (self.f32()?, self.f32()?);
+        let (emissive, ambient, specular) = (self.color3()?, self.color3()?, self.color3()?);
+        let alpha_mode = match (self.u32()?, self.f32()?) {
+            (0, _) => AlphaMode::Opaque,
+            (1, cutoff) => AlphaMode::Mask(cutoff),
+            _ => AlphaMode::Blend
+        };
+        let double_sided = self.u32()? != 0;
+        let specular_power = self.f32()?;
+        let illumination = match self.u32()? {
+            0 => Illumination::Constant,
+            1 => Illumination::Diffuse,
+            _ => Illumination::Specular
+        };
+
+        Ok(MaterialParams { base_color, metallic, roughness, emissive, alpha_mode, double_sided,
+            ambient, specular, specular_power, illumination })
+    }
+
+    ///
+    /// Gets the range of bytes taken by a list of plain data items, stored as a count followed by
+    /// the items at an aligned offset
+    ///
+    fn data_range<T>(&mut self) -> Result<Range<usize>, Error> {
+        let count = self.u32()? as usize;
+        self.align();
+        let start = self.pos;
+        let len = count.checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(|| Error::parse(self.path, None, "model cache is truncated"))?;
+        self.bytes(len)?;
+        Ok(start..self.pos)
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Writes the model to a cache file that `ModelCache` can load without repeating the parsing
+    /// and processing. The cache records checksums of the files the model was loaded from and the
+    /// resolver's search roots, and is ignored if either has changed when it's opened.
+    ///
+    pub fn write_cache(&self, path: &Path, resolver: &AssetResolver) -> Result<(), Error> {
+        let mut w = CacheWriter::default();
+        w.data.extend_from_slice(&CACHE_MAGIC);
+        w.u32(CACHE_VERSION);
+        w.u32(BYTE_ORDER_MARK);
+
+        w.u32(self.sources.len() as u32);
+        for source in &self.sources {
+            w.path(source);
+            w.u32(checksum_file(source)?);
+        }
+        w.u32(resolver.get_search_roots().len() as u32);
+        for root in resolver.get_search_roots() {
+            w.path(root);
+        }
+
+        w.u32(self.materials.len() as u32);
+        for mat in &self.materials {
+            w.path(&mat.shader_file);
+            w.u32(mat.topology as u32);
+            // sorted so the same model always writes the same bytes
+            let mut textures: Vec<_> = mat.textures.iter().collect();
+            textures.sort();
+            w.u32(textures.len() as u32);
+            for (slot, file) in textures {
+                w.str(slot);
+                w.path(file);
+            }
+            w.params(&mat.params);
+        }
+
+        let write_draws = |w: &mut CacheWriter, draws: &[DrawData]| {
+            w.u32(draws.len() as u32);
+            for draw in draws {
+                w.u32(draw.start_index);
+                w.u32(draw.num_tris);
+                w.u32(draw.material_idx);
+            }
+        };
+        write_draws(&mut w, &self.draws);
+        w.u32(self.lods.len() as u32);
+        for lod in &self.lods {
+            w.f32(lod.error);
+            write_draws(&mut w, &lod.draws);
+        }
+        w.u32(self.nodes.len() as u32);
+        for node in &self.nodes {
+            w.str(&node.name);
+            w.u32(node.parent.map_or(u32::MAX, |parent| parent as u32));
+            w.u32(node.draws.start as u32);
+            w.u32(node.draws.end as u32);
+        }
+        w.u32(self.unresolved.len() as u32);
+        for asset in &self.unresolved {
+            w.str(&asset.reference);
+            w.path(&asset.referenced_by);
+        }
+        match self.get_bounds() {
+            Some((min, max)) => {
+                w.u32(1);
+                w.f32s(&[min.x, min.y, min.z, max.x, max.y, max.z]);
+            }
+            None => w.u32(0)
+        }
+
+        w.u32(self.verts.len() as u32);
+        w.align();
+        w.data.extend_from_slice(slice_as_bytes(&self.verts));
+        w.u32(self.indices.len() as u32);
+        w.align();
+        w.data.extend_from_slice(slice_as_bytes(&self.indices));
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::io(dir, e))?;
+        }
+        fs::write(path, &w.data).map_err(|e| Error::io(path, e))
+    }
+}
+
+///
+/// A processed model read back from a cache file. The vertex and index data stay in the mapped
+/// file and are uploaded from there when the model is built.
+///
+pub struct ModelCache {
+    map: Mmap,
+    verts: Range<usize>,
+    indices: Range<usize>,
+    draws: Vec<DrawData>,
+    lods: Vec<ModelLod>,
+    materials: Vec<MaterialInfo>,
+    nodes: Vec<ModelNode>,
+    unresolved: Vec<UnresolvedAsset>,
+    bounds: Option<(Point3F, Point3F)>
+}
+
+impl ModelCache {
+    ///
+    /// Opens a cache file written by `ModelBuilder::write_cache`
+    ///
+    /// # Returns
+    /// The cached model, or `None` if there's no cache file or it's out of date because it was
+    /// written by another version, the model's files have changed or the search roots differ
+    ///
+    pub fn open(path: &Path, resolver: &AssetResolver) -> Result<Option<Self>, Error> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(_) => return Ok(None)
+        };
+        // the cache is only ever replaced as a whole, so the mapping isn't expected to change
+        // while it's in use
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| Error::io(path, e))?;
+
+        let mut r = CacheReader { path, data: &map, pos: 0 };
+        if r.bytes(CACHE_MAGIC.len())? != CACHE_MAGIC {
+            return Err(Error::parse(path, None, "not a model cache"));
+        }
+        if r.u32()? != CACHE_VERSION || r.u32()? != BYTE_ORDER_MARK {
+            return Ok(None);
+        }
+
+        let sources = r.list(|r| Ok((r.path()?, r.u32()?)))?;
+        for (source, checksum) in sources {
+            if checksum_file(&source).ok() != Some(checksum) {
+                return Ok(None);
+            }
+        }
+        if r.list(CacheReader::path)? != resolver.get_search_roots() {
+            return Ok(None);
+        }
+
+        let materials = r.list(|r| {
+            let shader_file = r.path()?;
+            let topology = match r.u32()? {
+                0 => PrimitiveTopology::PointList,
+                1 => PrimitiveTopology::LineList,
+                2 => PrimitiveTopology::LineStrip,
+                3 => PrimitiveTopology::TriangleList,
+                _ => PrimitiveTopology::TriangleStrip
+            };
+            let textures = r.list(|r| Ok((r.str()?, r.path()?)))?
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            let params = r.params()?;
+            Ok(MaterialInfo { shader_file, vert_format: MeshVertex::get_format(), topology,
+                textures, params })
+        })?;
+        let read_draws = |r: &mut CacheReader| r.list(|r| Ok(DrawData {
+            start_index: r.u32()?,
+            num_tris: r.u32()?,
+            material_idx: r.u32()?
+        }));
+        let draws = read_draws(&mut r)?;
+        let lods = r.list(|r| Ok(ModelLod { error: r.f32()?, draws: read_draws(r)? }))?;
+        let nodes = r.list(|r| Ok(ModelNode {
+            name: r.str()?,
+            parent: Some(r.u32()?).filter(|&parent| parent != u32::MAX).map(|p| p as usize),
+            draws: r.u32()? as usize..r.u32()? as usize
+        }))?;
+        let unresolved = r.list(|r| Ok(UnresolvedAsset {
+            reference: r.str()?,
+            referenced_by: r.path()?
+        }))?;
+        let bounds = match r.u32()? {
+            0 => None,
+            _ => Some((r.point3()?, r.point3()?))
+        };
+
+        let verts = r.data_range::<MeshVertex>()?;
+        let indices = r.data_range::<u32>()?;
+
+        Ok(Some(Self { map, verts, indices, draws, lods, materials, nodes, unresolved, bounds }))
+    }
+
+    ///
+    /// Gets the minimum and maximum corners of the box enclosing every vertex
+    ///
+    pub fn get_bounds(&self) -> Option<(Point3F, Point3F)> {
+        self.bounds
+    }
+
+    ///
+    /// Gets the files the model refers to that couldn't be found when it was loaded
+    ///
+    pub fn get_unresolved_assets(&self) -> &[UnresolvedAsset] {
+        &self.unresolved
+    }
+
+    ///
+    /// Gets the named parts of the model
+    ///
+    pub fn get_nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    ///
+    /// Overrides the shader used by every material in the model
+    ///
+    pub fn with_shader(mut self, shader_file: &Path) -> Self {
+        for mat in &mut self.materials {
+            mat.shader_file = shader_file.to_path_buf();
+        }
+        self
+    }
+
+    ///
+    /// Gets the vertices, straight from the mapped file
+    ///
+    fn get_vertices(&self) -> &[MeshVertex] {
+        let bytes = &self.map[self.verts.clone()];
+        // the data is at an aligned offset in a page aligned mapping, and every bit pattern is a
+        // valid vertex
+        unsafe {
+            std::slice::from_raw_parts(bytes.as_ptr() as *const MeshVertex,
+                bytes.len() / std::mem::size_of::<MeshVertex>())
+        }
+    }
+
+    ///
+    /// Gets the indices, straight from the mapped file
+    ///
+    fn get_indices(&self) -> &[u32] {
+        let bytes = &self.map[self.indices.clone()];
+        unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const u32, bytes.len() / 4) }
+    }
+
+    ///
+    /// Builds the model
+    ///
+    pub fn build(self, gfx: &Graphics) -> Result<Model, Error> {
+        let mut mats = Vec::with_capacity(self.materials.len());
+        for mat in &self.materials {
+            mats.push(Material::load(gfx, mat)?);
+        }
+        Model::new(gfx, self.get_vertices(), self.get_indices(), self.draws.clone(), mats,
+            self.nodes.clone())?.with_lods(self.lods.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_files::*;
+
+    #[test]
+    fn round_trips_a_processed_model() {
+        let (dir, path) = scratch_copy("cache-round-trip", "no_material.obj");
+        let cache_path = dir.join("cache").join("no_material.mvc");
+        let resolver = AssetResolver::new();
+        let mut builder = ModelBuilder::load_from_obj_with_resolver(&path, &resolver).unwrap();
+        builder.optimize();
+        builder.generate_lods(&[LodTarget::Ratio(0.5f32)]);
+        builder.write_cache(&cache_path, &resolver).unwrap();
+
+        let cache = ModelCache::open(&cache_path, &resolver).unwrap().unwrap();
+        assert_eq!(cache.get_vertices().len(), builder.verts.len());
+        assert!(cache.get_vertices().iter().zip(&builder.verts)
+            .all(|(a, b)| slice_as_bytes(&[*a]) == slice_as_bytes(&[*b])));
+        assert_eq!(cache.get_indices(), &builder.indices[..]);
+        assert_eq!(cache.get_nodes(), builder.get_nodes());
+        assert_eq!(cache.get_bounds(), builder.get_bounds());
+        assert_eq!(cache.materials[0].params, builder.materials[0].params);
+        assert_eq!(cache.materials[0].shader_file, builder.materials[0].shader_file);
+        let lods = |lods: &[ModelLod]| lods.iter()
+            .map(|lod| (lod.error, lod.draws.iter()
+                .map(|d| (d.start_index, d.num_tris, d.material_idx))
+                .collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(lods(&cache.lods), lods(builder.get_lods()));
+
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let model = cache.build(&gfx);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(model.unwrap().get_lods().len(), 1);
+    }
+
+    #[test]
+    fn changed_sources_invalidate_the_cache() {
+        let (dir, path) = scratch_copy("cache-invalidate", "no_material.obj");
+        let cache_path = dir.join("no_material.mvc");
+        let resolver = AssetResolver::new();
+        let builder = ModelBuilder::load_from_obj_with_resolver(&path, &resolver).unwrap();
+        builder.write_cache(&cache_path, &resolver).unwrap();
+        assert!(ModelCache::open(&cache_path, &resolver).unwrap().is_some());
+
+        // other search roots could resolve the textures differently
+        let other = AssetResolver::new().with_search_root("data");
+        assert!(ModelCache::open(&cache_path, &other).unwrap().is_none());
+
+        let mut obj = fs::read_to_string(&path).unwrap();
+        obj.push_str("f 1 3 2\n");
+        fs::write(&path, obj).unwrap();
+        let stale = ModelCache::open(&cache_path, &resolver).unwrap();
+        let missing = ModelCache::open(&dir.join("missing.mvc"), &resolver).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(stale.is_none());
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_caches() {
+        let (dir, path) = scratch_copy("cache-truncated", "no_material.obj");
+        let cache_path = dir.join("no_material.mvc");
+        let resolver = AssetResolver::new();
+        let builder = ModelBuilder::load_from_obj_with_resolver(&path, &resolver).unwrap();
+        builder.write_cache(&cache_path, &resolver).unwrap();
+
+        let data = fs::read(&cache_path).unwrap();
+        fs::write(&cache_path, &data[..data.len() - 8]).unwrap();
+        let result = ModelCache::open(&cache_path, &resolver);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(Error::Parse { .. })));
+    }
+}
@@ -0,0 +1,531 @@
+// external refs
+use gltf;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+use super::{object_material, object_shader};
+
+impl ModelBuilder {
+    ///
+    /// Loads a model from a glTF 2.0 file, either a .gltf with external or embedded buffers or a
+    /// binary .glb. Every mesh instanced by the default scene is flattened into the model with
+    /// its node's world transform applied, and the node tree is kept as the model's parts.
+    /// Images are found with the default resolver.
+    ///
+    pub fn load_from_gltf(path: &Path) -> Result<Self, Error> {
+        Self::load_from_gltf_with_resolver(path, &AssetResolver::default())
+    }
+
+    ///
+    /// Loads a model from a glTF 2.0 file. External buffers and images are looked for relative to
+    /// the glTF file, then in the resolver's search roots. Images that can't be found or are
+    /// embedded in the file are listed by `get_unresolved_assets`, while missing buffers and
+    /// primitives that aren't triangles fail the load.
+    ///
+    pub fn load_from_gltf_with_resolver(path: &Path, resolver: &AssetResolver)
+        -> Result<Self, Error> {
+
+        let gltf::Gltf { document, blob } = gltf::Gltf::open(path)
+            .map_err(|e| convert_error(path, e))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        let mut sources = vec![path.to_path_buf()];
+        let buffers = document.buffers()
+            .map(|buffer| load_buffer(path, base_dir, resolver, &buffer, blob.as_deref(),
+                &mut sources))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let shader_file = object_shader(resolver);
+        let mut unresolved = Vec::new();
+        let materials = document.materials()
+            .map(|mat| convert_material(&mat, &shader_file, |image| match image.source() {
+                gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                    Some(resolver.resolve_or_record(&decode_uri_path(uri), &[base_dir], path,
+                        &mut unresolved))
+                }
+                _ => {
+                    unresolved.push(UnresolvedAsset {
+                        reference: format!("image {} (embedded images aren't supported)",
+                            image.index()),
+                        referenced_by: path.to_path_buf()
+                    });
+                    None
+                }
+            }))
+            .collect();
+
+        let mut builder = Self { materials, unresolved, sources, ..Default::default() };
+        let mut default_material = None;
+
+        // files without scenes still get displayed, by treating every parentless node as a root
+        let roots: Vec<gltf::Node> = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().collect(),
+            None => {
+                let children: HashSet<usize> = document.nodes()
+                    .flat_map(|node| node.children().map(|child| child.index()))
+                    .collect();
+                document.nodes().filter(|node| !children.contains(&node.index())).collect()
+            }
+        };
+
+        // walk the hierarchy depth first, keeping the document's order
+        let mut stack: Vec<(gltf::Node, Matrix4F, Option<usize>)> = roots.into_iter().rev()
+            .map(|node| (node, Matrix4F::identity(), None))
+            .collect();
+        while let Some((node, parent_world, parent)) = stack.pop() {
+            let world = parent_world * node_matrix(&node);
+            let first_draw = builder.draws.len();
+            if let Some(mesh) = node.mesh() {
+                for prim in mesh.primitives() {
+                    let material_idx = match prim.material().index() {
+                        Some(idx) => idx,
+                        None => *default_material.get_or_insert_with(|| {
+                            builder.materials.push(object_material(&shader_file, HashMap::new(),
+                                MaterialParams::default()));
+                            builder.materials.len() - 1
+                        })
+                    };
+                    builder.add_gltf_primitive(path, &prim, &buffers, &world, material_idx as u32)?;
+                }
+            }
+
+            // unnamed nodes are often just holders for a named mesh
+            let name = node.name()
+                .or_else(|| node.mesh().and_then(|mesh| mesh.name()))
+                .map_or_else(|| format!("node {}", node.index()), str::to_string);
+            builder.nodes.push(ModelNode { name, parent, draws: first_draw..builder.draws.len() });
+
+            let idx = Some(builder.nodes.len() - 1);
+            let children: Vec<gltf::Node> = node.children().collect();
+            stack.extend(children.into_iter().rev().map(|child| (child, world, idx)));
+        }
+
+        Ok(builder)
+    }
+
+    ///
+    /// Appends a mesh primitive's vertices, transformed into model space, and a draw for its
+    /// triangles
+    ///
+    fn add_gltf_primitive(&mut self, path: &Path, prim: &gltf::Primitive, buffers: &[Vec<u8>],
+        world: &Matrix4F, material_idx: u32) -> Result<(), Error> {
+
+        let reader = prim.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions = reader.read_positions()
+            .ok_or_else(|| Error::parse(path, None, "mesh primitive has no POSITION attribute"))?;
+
+        let base_vertex = self.verts.len() as u32;
+        let linear = world.fixed_slice::<U3, U3>(0, 0).into_owned();
+        let normal_matrix = linear.try_inverse().map_or(linear, |inv| inv.transpose());
+        // directions are only renormalized when transformed, so untransformed files load exactly
+        let transformed = *world != Matrix4F::identity();
+        let direction = |m: &Matrix3F, v: Vector3F| {
+            let d = m * v;
+            if transformed && d.norm_squared() > 0.0f32 { d.normalize() } else { d }
+        };
+
+        let mut verts: Vec<MeshVertex> = positions
+            .map(|p| {
+                let pos = world * Vector4F::new(p[0], p[1], p[2], 1.0f32);
+                MeshVertex { pos: Point3F::new(pos.x, pos.y, pos.z), ..Default::default() }
+            })
+            .collect();
+        let has_normals = match reader.read_normals() {
+            Some(normals) => {
+                for (v, n) in verts.iter_mut().zip(normals) {
+                    v.norm = direction(&normal_matrix, Vector3F::new(n[0], n[1], n[2]));
+                }
+                true
+            }
+            None => false
+        };
+        // a mirroring transform flips the bitangent relative to the normal and tangent
+        let mirrored = linear.determinant() < 0.0f32;
+        let has_tangents = match reader.read_tangents() {
+            Some(tangents) => {
+                let handedness = if mirrored { -1.0f32 } else { 1.0f32 };
+                for (v, t) in verts.iter_mut().zip(tangents) {
+                    let d = direction(&linear, Vector3F::new(t[0], t[1], t[2]));
+                    v.tangent = Vector4F::new(d.x, d.y, d.z, t[3] * handedness);
+                }
+                true
+            }
+            None => false
+        };
+        let has_uvs = reader.read_tex_coords(0).is_some();
+        if let Some(uvs) = reader.read_tex_coords(0) {
+            for (v, uv) in verts.iter_mut().zip(uvs.into_f32()) {
+                v.uv = Vector2F::new(uv[0], uv[1]);
+            }
+        }
+        if let Some(colors) = reader.read_colors(0) {
+            for (v, c) in verts.iter_mut().zip(colors.into_rgba_u8()) {
+                v.color = Color4::from_rgba(c[0], c[1], c[2], c[3]);
+            }
+        }
+
+        let num_verts = verts.len() as u32;
+        let source: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..num_verts).collect()
+        };
+        if let Some(bad) = source.iter().find(|&&i| i >= num_verts) {
+            return Err(Error::parse(path, None, format!("index {} is out of range for a \
+                primitive with {} vertices", bad, num_verts)));
+        }
+
+        let tris: Vec<[u32; 3]> = match prim.mode() {
+            gltf::mesh::Mode::Triangles => {
+                source.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect()
+            }
+            gltf::mesh::Mode::TriangleStrip => {
+                // every other triangle in a strip has its winding reversed
+                source.windows(3).enumerate()
+                    .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                    .collect()
+            }
+            gltf::mesh::Mode::TriangleFan => {
+                source.windows(2).skip(1).map(|t| [source[0], t[0], t[1]]).collect()
+            }
+            mode => {
+                return Err(Error::UnsupportedFormat {
+                    what: format!("{:?} primitive, only triangles are supported", mode),
+                    path: Some(path.to_path_buf())
+                });
+            }
+        };
+
+        // glTF faces are counter-clockwise, so swap two corners to match our clockwise front
+        // faces, unless a mirroring transform has already reversed them
+        let start_index = self.indices.len() as u32;
+        for t in &tris {
+            let (b, c) = if mirrored { (t[1], t[2]) } else { (t[2], t[1]) };
+            self.indices.extend_from_slice(&[base_vertex + t[0], base_vertex + b, base_vertex + c]);
+        }
+
+        self.verts.append(&mut verts);
+        self.draws.push(DrawData { start_index, num_tris: tris.len() as u32, material_idx });
+
+        // the specification asks for flat normals when a primitive doesn't provide any, and
+        // MikkTSpace tangents
+        let draw = [self.draws.len() - 1];
+        if !has_normals {
+            self.generate_normals_for_draws(&draw, NormalGeneration::Flat);
+        }
+        if !has_tangents && has_uvs {
+            self.generate_tangents_for_draws(&draw);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Converts an error from the glTF reader, keeping the line number of JSON syntax errors
+///
+fn convert_error(path: &Path, e: gltf::Error) -> Error {
+    match e {
+        gltf::Error::Io(e) => Error::io(path, e),
+        gltf::Error::Deserialize(e) => {
+            Error::parse(path, Some(e.line()).filter(|&line| line > 0), e.to_string())
+        }
+        e => Error::parse(path, None, e.to_string())
+    }
+}
+
+///
+/// Gets a node's local transform as a matrix
+///
+fn node_matrix(node: &gltf::Node) -> Matrix4F {
+    let cols = node.transform().matrix();
+    Matrix4F::from_fn(|row, col| cols[col][row])
+}
+
+///
+/// Reads the contents of a buffer from the GLB binary chunk, a data URI or a file next to the
+/// glTF file, adding any file read to the sources
+///
+fn load_buffer(path: &Path, base_dir: &Path, resolver: &AssetResolver, buffer: &gltf::Buffer,
+    blob: Option<&[u8]>, sources: &mut Vec<PathBuf>) -> Result<Vec<u8>, Error> {
+
+    let data = match buffer.source() {
+        gltf::buffer::Source::Bin => blob.map(<[u8]>::to_vec).ok_or_else(|| Error::MissingResource {
+            what: "GLB binary chunk".to_string(),
+            path: Some(path.to_path_buf())
+        })?,
+        gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => decode_data_uri(uri)
+            .ok_or_else(|| Error::parse(path, None, "buffer has a malformed data URI"))?,
+        gltf::buffer::Source::Uri(uri) => {
+            let reference = decode_uri_path(uri);
+            let file = resolver.resolve(&reference, &[base_dir])
+                .unwrap_or_else(|| base_dir.join(AssetResolver::normalize(&reference)));
+            let data = fs::read(&file).map_err(|e| Error::io(&file, e))?;
+            sources.push(file);
+            data
+        }
+    };
+
+    if data.len() < buffer.length() {
+        return Err(Error::parse(path, None, format!("buffer {} holds {} bytes but declares {}",
+            buffer.index(), data.len(), buffer.length())));
+    }
+
+    Ok(data)
+}
+
+///
+/// Converts a glTF material into a material drawn with the standard object shader, using the
+/// callback to find the files of its images. Textures whose image has no file are left out.
+///
+fn convert_material(mat: &gltf::Material, shader_file: &Path,
+    mut resolve_image: impl FnMut(&gltf::Image) -> Option<PathBuf>) -> MaterialInfo {
+
+    let pbr = mat.pbr_metallic_roughness();
+    let slots = vec![
+        ("albedo_map", pbr.base_color_texture().map(|info| info.texture())),
+        ("metallic_roughness_map", pbr.metallic_roughness_texture().map(|info| info.texture())),
+        ("normal_map", mat.normal_texture().map(|info| info.texture())),
+        ("occlusion_map", mat.occlusion_texture().map(|info| info.texture())),
+        ("emissive_map", mat.emissive_texture().map(|info| info.texture()))
+    ];
+
+    let mut textures = HashMap::new();
+    for (slot, texture) in slots {
+        if let Some(file) = texture.and_then(|texture| resolve_image(&texture.source())) {
+            textures.insert(slot.to_string(), file);
+        }
+    }
+
+    let [r, g, b, a] = pbr.base_color_factor();
+    let [er, eg, eb] = mat.emissive_factor();
+    let metallic = pbr.metallic_factor();
+    let roughness = pbr.roughness_factor();
+
+    // approximate the metal-rough model with Blinn-Phong highlights: dielectrics reflect 4% and
+    // metals reflect their base color, with the exponent matching the GGX roughness
+    let specular = |c: f32| 0.04f32 + (c - 0.04f32) * metallic;
+    let alpha = (roughness * roughness).max(1e-3f32);
+    let params = MaterialParams {
+        base_color: Color4F::from_rgba(r, g, b, a),
+        metallic,
+        roughness,
+        emissive: Color3F::from_rgb(er, eg, eb),
+        alpha_mode: match mat.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask(mat.alpha_cutoff().unwrap_or(0.5f32)),
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend
+        },
+        double_sided: mat.double_sided(),
+        specular: Color3F::from_rgb(specular(r), specular(g), specular(b)),
+        specular_power: (2.0f32 / (alpha * alpha) - 2.0f32).max(1.0f32),
+        ..Default::default()
+    };
+
+    object_material(shader_file, textures, params)
+}
+
+///
+/// Turns a relative URI reference into a path reference, decoding percent-escaped characters
+///
+fn decode_uri_path(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(c)) => {
+                decoded.push(c);
+                i += 3;
+            }
+            (c, _) => {
+                decoded.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+///
+/// Decodes the payload of a base64 data URI such as `data:application/octet-stream;base64,...`
+///
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let (header, payload) = uri.split_at(uri.find(',')?);
+    if !header.ends_with(";base64") {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(payload.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in payload[1..].bytes().take_while(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            decoded.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_files::*;
+
+    fn asset(name: &str) -> PathBuf {
+        Path::new("tests").join("assets").join(name)
+    }
+
+    fn near(a: &Vector3F, b: &Vector3F) -> bool {
+        (a - b).norm() < 1e-5f32
+    }
+
+    #[test]
+    fn loads_hierarchy_attributes_and_pbr_material() {
+        let builder = ModelBuilder::load_from_gltf(&asset("quad.gltf")).unwrap();
+        assert_eq!(builder.verts.len(), 4);
+        assert_eq!(builder.indices, vec![0, 2, 1, 0, 3, 2]);
+        assert_eq!(builder.draws.len(), 1);
+        assert_eq!((builder.draws[0].num_tris, builder.draws[0].material_idx), (2, 0));
+
+        // the root moves up by one and the quad node is scaled by 2 and turned 90 degrees about y
+        let v = &builder.verts[1];
+        assert!(near(&v.pos.coords, &Vector3F::new(0.0f32, 0.0f32, -1.0f32)));
+        assert!(near(&v.norm, &Vector3F::new(1.0f32, 0.0f32, 0.0f32)));
+        assert!(near(&v.tangent.xyz(), &Vector3F::new(0.0f32, 0.0f32, -1.0f32)));
+        assert_eq!(v.tangent.w, 1.0f32);
+        assert_eq!(v.uv, Vector2F::new(1.0f32, 1.0f32));
+        assert_eq!(v.color, Color4::from_rgba(0, 255, 0, 255));
+        assert_eq!(builder.verts[3].color, Color4::from_rgba(255, 255, 255, 128));
+
+        let mat = &builder.materials[0];
+        assert_eq!(mat.params.base_color, Color4F::from_rgba(1.0f32, 0.5f32, 0.25f32, 1.0f32));
+        assert_eq!((mat.params.metallic, mat.params.roughness), (0.0f32, 0.5f32));
+        assert_eq!(mat.params.emissive, Color3F::from_rgb(0.1f32, 0.2f32, 0.3f32));
+        assert_eq!(mat.params.alpha_mode, AlphaMode::Mask(0.3f32));
+        assert!(mat.params.double_sided);
+        let textures = asset("textures");
+        assert_eq!(mat.textures["albedo_map"], textures.join("quad_albedo.png"));
+        assert_eq!(mat.textures["metallic_roughness_map"], textures.join("quad orm.png"));
+        assert_eq!(mat.textures["normal_map"], textures.join("quad_normal.png"));
+        assert!(!mat.textures.contains_key("emissive_map"));
+        assert_eq!(builder.get_unresolved_assets().len(), 3);
+
+        assert_eq!(builder.get_nodes(), &[
+            ModelNode { name: "root".to_string(), parent: None, draws: 0..0 },
+            ModelNode { name: "quad".to_string(), parent: Some(0), draws: 0..1 }
+        ]);
+    }
+
+    #[test]
+    fn loads_binary_with_instancing_and_mirroring() {
+        let builder = ModelBuilder::load_from_gltf(&asset("mirrored.glb")).unwrap();
+        assert_eq!(builder.verts.len(), 6);
+        assert_eq!(builder.draws.len(), 2);
+        assert_eq!(builder.draws[1].start_index, 3);
+
+        // both instances share the default material added for primitives without one
+        assert_eq!(builder.materials.len(), 1);
+        assert!(builder.draws.iter().all(|d| d.material_idx == 0));
+        assert_eq!(builder.materials[0].params, MaterialParams::default());
+
+        // the mirrored instance already has reversed winding, so its indices are kept in order
+        assert_eq!(builder.indices, vec![0, 2, 1, 3, 4, 5]);
+        assert!(near(&builder.verts[1].pos.coords, &Vector3F::new(0.0f32, 0.0f32, 0.0f32)));
+        assert!(near(&builder.verts[4].pos.coords, &Vector3F::new(0.0f32, 0.0f32, 0.0f32)));
+        assert!(near(&builder.verts[5].pos.coords, &Vector3F::new(1.0f32, 1.0f32, 0.0f32)));
+
+        // the file has no normals, so flat ones are generated with the mirroring taken into account
+        assert!(builder.verts.iter().all(|v| near(&v.norm, &Vector3F::z())));
+
+        // each instance of the shared mesh is a separate part
+        let parts: Vec<_> = builder.get_nodes().iter()
+            .map(|node| (node.name.as_str(), node.draws.clone()))
+            .collect();
+        assert_eq!(parts, vec![("left", 0..1), ("mirrored", 1..2)]);
+    }
+
+    #[test]
+    fn reports_missing_buffer_file() {
+        let (dir, path) = scratch_copy("gltf-missing-buffer", "quad.gltf");
+
+        let result = ModelBuilder::load_from_gltf(&path);
+        fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(Error::Io { path, .. }) => assert_eq!(path, dir.join("quad.bin")),
+            _ => panic!("Expected an I/O error for the missing buffer")
+        }
+    }
+
+    ///
+    /// Writes a triangle in the specified primitive mode to a scratch glTF file, with a material
+    /// whose base color image is embedded
+    ///
+    fn scratch_triangle(name: &str, mode: u32) -> PathBuf {
+        // positions (0, 0, 0), (1, 0, 0) and (0, 1, 0)
+        let positions = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA";
+        let json = format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,{}" }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+            "accessors": [{{ "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0] }}],
+            "images": [{{ "uri": "data:image/png;base64,AAAA" }}],
+            "textures": [{{ "source": 0 }}],
+            "materials": [{{ "pbrMetallicRoughness": {{ "baseColorTexture": {{ "index": 0 }} }} }}],
+            "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "material": 0,
+                "mode": {} }}] }}],
+            "nodes": [{{ "mesh": 0 }}],
+            "scenes": [{{ "nodes": [0] }}]
+        }}"#, positions, mode);
+        write_scratch_file(&format!("{}.gltf", name), json)
+    }
+
+    #[test]
+    fn reports_embedded_images_and_other_primitives() {
+        let path = scratch_triangle("embedded", 4);
+        let builder = ModelBuilder::load_from_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        let builder = builder.unwrap();
+        assert_eq!(builder.draws.len(), 1);
+        assert!(builder.materials[0].textures.is_empty());
+        assert_eq!(builder.get_unresolved_assets(), &[UnresolvedAsset {
+            reference: "image 0 (embedded images aren't supported)".to_string(),
+            referenced_by: path
+        }]);
+
+        let path = scratch_triangle("lines", 1);
+        let result = ModelBuilder::load_from_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::UnsupportedFormat { .. })));
+    }
+
+    #[test]
+    fn decodes_uris() {
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,AAECAw=="),
+            Some(vec![0, 1, 2, 3]));
+        assert_eq!(decode_data_uri("data:text/plain,hello"), None);
+        assert_eq!(decode_uri_path("a%20b/c%2"), "a b/c%2");
+    }
+}
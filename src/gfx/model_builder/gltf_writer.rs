@@ -0,0 +1,472 @@
+// external refs
+use gltf::json;
+use gltf::json::validation::Checked::Valid;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Builds the JSON document and binary buffer of a glTF file
+///
+struct GltfWriter<'a> {
+    root: json::Root,
+    bin: Vec<u8>,
+    /// Directory image references are written relative to
+    base_dir: &'a Path,
+    textures: HashMap<PathBuf, json::Index<json::Texture>>
+}
+
+impl GltfWriter<'_> {
+    ///
+    /// Appends data to the binary buffer with a view of it
+    ///
+    fn push_view(&mut self, data: &[u8], target: json::buffer::Target)
+        -> json::Index<json::buffer::View> {
+
+        // every component type we write is at most 4 bytes, which views have to be aligned to
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+        let offset = self.bin.len();
+        self.bin.extend_from_slice(data);
+
+        self.root.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: data.len().into(),
+            byte_offset: Some(offset.into()),
+            byte_stride: None,
+            name: None,
+            target: Some(Valid(target)),
+            extensions: None,
+            extras: Default::default()
+        })
+    }
+
+    ///
+    /// Appends an accessor for tightly packed vertex attributes
+    ///
+    fn push_attribute(&mut self, data: &[u8], count: usize,
+        component: json::accessor::ComponentType, ty: json::accessor::Type,
+        bounds: Option<(json::Value, json::Value)>) -> json::Index<json::Accessor> {
+
+        let view = self.push_view(data, json::buffer::Target::ArrayBuffer);
+        let normalized = component != json::accessor::ComponentType::F32;
+        let (min, max) = bounds.unzip();
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: count.into(),
+            component_type: Valid(json::accessor::GenericComponentType(component)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Valid(ty),
+            min,
+            max,
+            name: None,
+            normalized,
+            sparse: None
+        })
+    }
+
+    ///
+    /// Gets the texture of an image file, adding it the first time it's used
+    ///
+    fn texture(&mut self, file: &Path) -> json::Index<json::Texture> {
+        if let Some(&texture) = self.textures.get(file) {
+            return texture;
+        }
+
+        let uri = encode_uri_path(&AssetResolver::make_reference(file, self.base_dir));
+        let image = self.root.push(json::Image {
+            buffer_view: None,
+            mime_type: None,
+            name: None,
+            uri: Some(uri),
+            extensions: None,
+            extras: Default::default()
+        });
+        let texture = self.root.push(json::Texture {
+            name: None,
+            sampler: None,
+            source: image,
+            extensions: None,
+            extras: Default::default()
+        });
+        self.textures.insert(file.to_path_buf(), texture);
+        texture
+    }
+
+    ///
+    /// Gets a reference to the texture in a material's slot, if it has one
+    ///
+    fn texture_info(&mut self, mat: &MaterialInfo, slot: &str) -> Option<json::texture::Info> {
+        let file = mat.textures.get(slot)?;
+        Some(json::texture::Info {
+            index: self.texture(file),
+            tex_coord: 0,
+            extensions: None,
+            extras: Default::default()
+        })
+    }
+
+    ///
+    /// Appends a material. The slots and parameters of the metal-rough model are written, while
+    /// those only MTL files have are left out.
+    ///
+    fn push_material(&mut self, mat: &MaterialInfo) {
+        let p = &mat.params;
+        let c = p.base_color;
+        let pbr = json::material::PbrMetallicRoughness {
+            base_color_factor: json::material::PbrBaseColorFactor([c.r, c.g, c.b, c.a]),
+            base_color_texture: self.texture_info(mat, "albedo_map"),
+            metallic_factor: json::material::StrengthFactor(p.metallic),
+            roughness_factor: json::material::StrengthFactor(p.roughness),
+            metallic_roughness_texture: self.texture_info(mat, "metallic_roughness_map"),
+            extensions: None,
+            extras: Default::default()
+        };
+        let normal_texture = self.texture_info(mat, "normal_map")
+            .map(|info| json::material::NormalTexture {
+                index: info.index,
+                scale: 1.0f32,
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default()
+            });
+        let occlusion_texture = self.texture_info(mat, "occlusion_map")
+            .map(|info| json::material::OcclusionTexture {
+                index: info.index,
+                strength: json::material::StrengthFactor(1.0f32),
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default()
+            });
+
+        let (alpha_mode, alpha_cutoff) = match p.alpha_mode {
+            AlphaMode::Opaque => (json::material::AlphaMode::Opaque, None),
+            AlphaMode::Mask(cutoff) => {
+                (json::material::AlphaMode::Mask, Some(json::material::AlphaCutoff(cutoff)))
+            }
+            AlphaMode::Blend => (json::material::AlphaMode::Blend, None)
+        };
+
+        let material = json::Material {
+            alpha_cutoff,
+            alpha_mode: Valid(alpha_mode),
+            double_sided: p.double_sided,
+            pbr_metallic_roughness: pbr,
+            normal_texture,
+            occlusion_texture,
+            emissive_texture: self.texture_info(mat, "emissive_map"),
+            emissive_factor: json::material::EmissiveFactor([p.emissive.r, p.emissive.g,
+                p.emissive.b]),
+            ..Default::default()
+        };
+        self.root.push(material);
+    }
+}
+
+///
+/// Converts floats to the little endian bytes glTF buffers hold
+///
+fn float_bytes(values: impl Iterator<Item = f32>) -> Vec<u8> {
+    values.flat_map(f32::to_le_bytes).collect()
+}
+
+///
+/// Escapes a path reference for use as a URI, the reverse of the loader's decoding
+///
+fn encode_uri_path(reference: &str) -> String {
+    let mut encoded = String::with_capacity(reference.len());
+    for &c in reference.as_bytes() {
+        if c.is_ascii_alphanumeric() || b"-._~/".contains(&c) {
+            encoded.push(c as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", c));
+        }
+    }
+    encoded
+}
+
+impl ModelBuilder {
+    ///
+    /// Saves the model to a glTF file, with its vertex data in a .bin file of the same name next
+    /// to it. Parts become nodes, keeping their hierarchy, and each draw becomes a primitive of
+    /// its part's mesh. Images are referenced where they are rather than copied. Materials are
+    /// stored with the metal-rough model, so the ambient and specular colors of MTL materials
+    /// are left out.
+    ///
+    pub fn save_gltf(&self, path: &Path) -> Result<(), Error> {
+        let bin_path = path.with_extension("bin");
+        let bin_name = bin_path.file_name().map(|name| encode_uri_path(&name.to_string_lossy()));
+        let (root, bin) = self.to_gltf(path, bin_name);
+
+        let json = root.to_vec_pretty().expect("Serializing a glTF document failed");
+        fs::write(path, json).map_err(|e| Error::io(path, e))?;
+        fs::write(&bin_path, bin).map_err(|e| Error::io(&bin_path, e))
+    }
+
+    ///
+    /// Saves the model to a binary glTF file, with its vertex data embedded
+    ///
+    pub fn save_glb(&self, path: &Path) -> Result<(), Error> {
+        let (root, bin) = self.to_gltf(path, None);
+        let json = root.to_vec().expect("Serializing a glTF document failed");
+        let glb = gltf::binary::Glb {
+            header: gltf::binary::Header { magic: *b"glTF", version: 2, length: 0 },
+            json: Cow::Owned(json),
+            bin: if bin.is_empty() { None } else { Some(Cow::Owned(bin)) }
+        };
+        let data = glb.to_vec().expect("Serializing a GLB file failed");
+        fs::write(path, data).map_err(|e| Error::io(path, e))
+    }
+
+    ///
+    /// Creates the glTF document and its binary buffer, which is either in the file named by the
+    /// URI or in the GLB binary chunk
+    ///
+    fn to_gltf(&self, path: &Path, bin_uri: Option<String>) -> (json::Root, Vec<u8>) {
+        use gltf::json::accessor::{ComponentType, Type};
+
+        let mut writer = GltfWriter {
+            root: json::Root::default(),
+            bin: Vec::new(),
+            base_dir: path.parent().unwrap_or_else(|| Path::new("")),
+            textures: HashMap::new()
+        };
+        writer.root.asset.generator = Some(format!("modelviewer {}", env!("CARGO_PKG_VERSION")));
+        for mat in &self.materials {
+            writer.push_material(mat);
+        }
+
+        let parts = self.get_parts_for_saving(path);
+        for part in &parts {
+            let mut primitives = Vec::with_capacity(part.draws.len());
+            // accessors can't be empty, so draws without triangles are left out
+            for draw in self.draws[part.draws.clone()].iter().filter(|d| d.num_tris > 0) {
+                let (used, tris) = self.get_draw_mesh(draw);
+                let verts: Vec<&MeshVertex> = used.iter()
+                    .map(|&idx| &self.verts[idx as usize])
+                    .collect();
+                let count = verts.len();
+                let mut attributes = BTreeMap::new();
+
+                let (min, max) = verts.iter().fold(
+                    ([f32::MAX; 3], [f32::MIN; 3]),
+                    |(min, max), v| {
+                        let p = [v.pos.x, v.pos.y, v.pos.z];
+                        ([0, 1, 2].map(|i| min[i].min(p[i])), [0, 1, 2].map(|i| max[i].max(p[i])))
+                    });
+                let positions = float_bytes(verts.iter().flat_map(|v| [v.pos.x, v.pos.y, v.pos.z]));
+                let bounds = (json::Value::from(min.to_vec()), json::Value::from(max.to_vec()));
+                attributes.insert(Valid(json::mesh::Semantic::Positions), writer.push_attribute(
+                    &positions, count, ComponentType::F32, Type::Vec3, Some(bounds)));
+
+                let normals = float_bytes(verts.iter()
+                    .flat_map(|v| [v.norm.x, v.norm.y, v.norm.z]));
+                attributes.insert(Valid(json::mesh::Semantic::Normals), writer.push_attribute(
+                    &normals, count, ComponentType::F32, Type::Vec3, None));
+
+                // tangents that were never generated would be invalid, so the loader makes them
+                if verts.iter().all(|v| v.tangent.w != 0.0f32) {
+                    let tangents = float_bytes(verts.iter()
+                        .flat_map(|v| [v.tangent.x, v.tangent.y, v.tangent.z, v.tangent.w]));
+                    attributes.insert(Valid(json::mesh::Semantic::Tangents), writer.push_attribute(
+                        &tangents, count, ComponentType::F32, Type::Vec4, None));
+                }
+                if verts.iter().any(|v| v.uv != Vector2F::zeros()) {
+                    let uvs = float_bytes(verts.iter().flat_map(|v| [v.uv.x, v.uv.y]));
+                    attributes.insert(Valid(json::mesh::Semantic::TexCoords(0)),
+                        writer.push_attribute(&uvs, count, ComponentType::F32, Type::Vec2, None));
+                }
+                if verts.iter().any(|v| v.color != Color4::white()) {
+                    let colors: Vec<u8> = verts.iter()
+                        .flat_map(|v| [v.color.r, v.color.g, v.color.b, v.color.a])
+                        .collect();
+                    attributes.insert(Valid(json::mesh::Semantic::Colors(0)),
+                        writer.push_attribute(&colors, count, ComponentType::U8, Type::Vec4, None));
+                }
+
+                let indices: Vec<u8> = tris.iter().flatten()
+                    .flat_map(|i| i.to_le_bytes())
+                    .collect();
+                let view = writer.push_view(&indices, json::buffer::Target::ElementArrayBuffer);
+                let indices = writer.root.push(json::Accessor {
+                    buffer_view: Some(view),
+                    byte_offset: None,
+                    count: (tris.len() * 3).into(),
+                    component_type: Valid(json::accessor::GenericComponentType(ComponentType::U32)),
+                    extensions: None,
+                    extras: Default::default(),
+                    type_: Valid(Type::Scalar),
+                    min: None,
+                    max: None,
+                    name: None,
+                    normalized: false,
+                    sparse: None
+                });
+
+                primitives.push(json::mesh::Primitive {
+                    attributes,
+                    extensions: None,
+                    extras: Default::default(),
+                    indices: Some(indices),
+                    material: Some(json::Index::new(draw.material_idx)),
+                    mode: Valid(json::mesh::Mode::Triangles),
+                    targets: None
+                });
+            }
+
+            let mesh = if primitives.is_empty() {
+                None
+            } else {
+                Some(writer.root.push(json::Mesh {
+                    extensions: None,
+                    extras: Default::default(),
+                    name: None,
+                    primitives,
+                    weights: None
+                }))
+            };
+            writer.root.push(json::Node {
+                mesh,
+                name: Some(part.name.clone()),
+                ..Default::default()
+            });
+        }
+
+        // vertices are already in model space, so the nodes only keep the hierarchy
+        let mut roots = Vec::new();
+        for (idx, part) in parts.iter().enumerate() {
+            let node = json::Index::new(idx as u32);
+            match part.parent {
+                Some(parent) => writer.root.nodes[parent].children.get_or_insert_with(Vec::new)
+                    .push(node),
+                None => roots.push(node)
+            }
+        }
+        let scene = writer.root.push(json::Scene {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            nodes: roots
+        });
+        writer.root.scene = Some(scene);
+
+        let mut bin = writer.bin;
+        if !bin.is_empty() {
+            bin.resize(bin.len().next_multiple_of(4), 0);
+            writer.root.push(json::Buffer {
+                byte_length: bin.len().into(),
+                name: None,
+                uri: bin_uri,
+                extensions: None,
+                extras: Default::default()
+            });
+        }
+        (writer.root, bin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_files::*;
+
+    ///
+    /// Gets every vertex attribute the loaders read, for comparing models
+    ///
+    fn attributes(builder: &ModelBuilder) -> Vec<(Point3F, Vector3F, Vector4F, Vector2F, Color4)> {
+        builder.verts.iter().map(|v| (v.pos, v.norm, v.tangent, v.uv, v.color)).collect()
+    }
+
+    #[test]
+    fn gltf_round_trip_is_lossless() {
+        let original = ModelBuilder::load_from_gltf(&Path::new("tests").join("assets")
+            .join("quad.gltf")).unwrap();
+        let path = scratch_path("quad.gltf");
+        original.save_gltf(&path).unwrap();
+        let loaded = ModelBuilder::load_from_gltf(&path);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("bin")).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.nodes, original.nodes);
+        assert_eq!(loaded.indices, original.indices);
+        assert_eq!(attributes(&loaded), attributes(&original));
+
+        let materials = |b: &ModelBuilder| b.materials.iter()
+            .map(|m| {
+                let mut textures: Vec<_> = m.textures.iter()
+                    .map(|(slot, file)| (slot.clone(), file.file_name().map(|n| n.to_owned())))
+                    .collect();
+                textures.sort();
+                (m.params, textures)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(materials(&loaded), materials(&original));
+    }
+
+    #[test]
+    fn glb_keeps_vertex_colors_and_loose_draws() {
+        let mut original = ModelBuilder::load_from_ply(&Path::new("tests").join("assets")
+            .join("colored.ply")).unwrap();
+        original.nodes.clear();
+        let path = scratch_path("colored.glb");
+        original.save_glb(&path).unwrap();
+        let loaded = ModelBuilder::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(attributes(&loaded), attributes(&original));
+        assert_eq!(loaded.indices, original.indices);
+        let expected = format!("modelviewer-{}-colored", std::process::id());
+        assert_eq!(loaded.nodes, vec![ModelNode { name: expected, parent: None, draws: 0..1 }]);
+    }
+
+    #[test]
+    fn uvs_keep_their_orientation_across_formats() {
+        // obj's v points up the image while glTF's points down
+        let obj_path = write_scratch_file("uvs.obj", "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+            vt 0.125 0.25\nvt 0.5 0\nvt 1 0.75\nf 1/1 2/2 3/3\n");
+        let from_obj = ModelBuilder::load_from_obj(&obj_path);
+        fs::remove_file(&obj_path).unwrap();
+        let from_obj = from_obj.unwrap();
+        let uvs = |b: &ModelBuilder| b.verts.iter().map(|v| v.uv).collect::<Vec<_>>();
+        let mut expected = vec![Vector2F::new(0.125f32, 0.75f32), Vector2F::new(0.5f32, 1.0f32),
+            Vector2F::new(1.0f32, 0.25f32)];
+        expected.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        let mut loaded_uvs = uvs(&from_obj);
+        loaded_uvs.sort_by(|a, b| a.as_slice().partial_cmp(b.as_slice()).unwrap());
+        assert_eq!(loaded_uvs, expected);
+
+        // the glTF loader reads coordinates as stored, so they were written flipped
+        let gltf_path = scratch_path("uvs.gltf");
+        from_obj.save_gltf(&gltf_path).unwrap();
+        let from_gltf = ModelBuilder::load_from_gltf(&gltf_path);
+        fs::remove_file(&gltf_path).unwrap();
+        fs::remove_file(gltf_path.with_extension("bin")).unwrap();
+        let from_gltf = from_gltf.unwrap();
+        assert_eq!(uvs(&from_gltf), uvs(&from_obj));
+
+        // and flipped back when saved as obj again
+        from_gltf.save_obj(&obj_path).unwrap();
+        let written = fs::read_to_string(&obj_path);
+        fs::remove_file(&obj_path).unwrap();
+        fs::remove_file(obj_path.with_extension("mtl")).unwrap();
+        let written = written.unwrap();
+        for vt in ["vt 0.125 0.25", "vt 0.5 0", "vt 1 0.75"] {
+            assert!(written.contains(vt), "{}", written);
+        }
+    }
+
+    #[test]
+    fn uris_are_escaped() {
+        assert_eq!(encode_uri_path("../maps/quad orm.png"), "../maps/quad%20orm.png");
+        assert_eq!(encode_uri_path("C:/textures/50%.png"), "C%3A/textures/50%25.png");
+    }
+}
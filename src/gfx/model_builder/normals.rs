@@ -0,0 +1,198 @@
+// external refs
+use std::collections::HashMap;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+use super::VertexVariants;
+
+///
+/// How vertex normals are generated
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalGeneration {
+    /// Every triangle gets its own face normal
+    Flat,
+    /// Normals are averaged over the triangles sharing a position, weighted by the angle of each
+    /// triangle's corner there. Triangles meeting at more than the crease angle, in radians, keep
+    /// a hard edge between them.
+    Smooth { crease_angle: f32 }
+}
+
+impl Default for NormalGeneration {
+    fn default() -> Self {
+        NormalGeneration::Smooth { crease_angle: deg_to_rad(60.0f32) }
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Replaces the normals of every vertex with generated ones. Vertices are duplicated where
+    /// the triangles using them need different normals.
+    ///
+    pub fn generate_normals(&mut self, mode: NormalGeneration) {
+        let draws: Vec<usize> = (0..self.draws.len()).collect();
+        self.generate_normals_for_draws(&draws, mode);
+    }
+
+    ///
+    /// Generates normals for the vertices used by the specified draws. Triangles are only
+    /// smoothed with others in the same draw.
+    ///
+    pub(super) fn generate_normals_for_draws(&mut self, draws: &[usize], mode: NormalGeneration) {
+        // corners whose normals are this close share a vertex
+        const SAME_NORMAL_COS: f32 = 0.9999f32;
+
+        let mut variants = VertexVariants::new();
+        for &draw_idx in draws {
+            let draw = self.draws[draw_idx];
+            let start = draw.start_index as usize;
+            let end = start + draw.num_tris as usize * 3;
+            let normals = self.corner_normals(start, end, mode);
+
+            for (corner, normal) in (start..end).zip(normals) {
+                self.indices[corner] = self.vertex_variant(&mut variants, self.indices[corner],
+                    |v| v.norm.dot(&normal) >= SAME_NORMAL_COS, |v| v.norm = normal);
+            }
+        }
+    }
+
+    ///
+    /// Computes the normal each triangle corner in the index range should have
+    ///
+    fn corner_normals(&self, start: usize, end: usize, mode: NormalGeneration) -> Vec<Vector3F> {
+        let tris = &self.indices[start..end];
+        let pos = |corner: usize| self.verts[tris[corner] as usize].pos.coords;
+
+        // triangles are wound clockwise, so the face normal comes from the reversed edge order
+        let faces: Vec<Vector3F> = tris.chunks(3).enumerate().map(|(t, _)| {
+            let n = (pos(t * 3 + 2) - pos(t * 3)).cross(&(pos(t * 3 + 1) - pos(t * 3)));
+            n.try_normalize(0.0f32).unwrap_or_else(zero)
+        }).collect();
+
+        let crease_cos = match mode {
+            NormalGeneration::Flat => return (0..tris.len()).map(|c| faces[c / 3]).collect(),
+            NormalGeneration::Smooth { crease_angle } => crease_angle.cos()
+        };
+
+        let corner_angle = |corner: usize| {
+            let tri = corner - corner % 3;
+            let p = pos(corner);
+            let a = pos(tri + (corner + 1) % 3) - p;
+            let b = pos(tri + (corner + 2) % 3) - p;
+            match (a.try_normalize(0.0f32), b.try_normalize(0.0f32)) {
+                (Some(a), Some(b)) => a.dot(&b).clamp(-1.0f32, 1.0f32).acos(),
+                _ => 0.0f32
+            }
+        };
+
+        // vertices are often split for texture seams, so corners are matched by position
+        let mut by_position = HashMap::<[u32; 3], Vec<usize>>::new();
+        for corner in 0..tris.len() {
+            let p = pos(corner);
+            by_position.entry([p.x.to_bits(), p.y.to_bits(), p.z.to_bits()])
+                .or_default()
+                .push(corner);
+        }
+        let angles: Vec<f32> = (0..tris.len()).map(corner_angle).collect();
+
+        let mut normals = Vec::with_capacity(tris.len());
+        for corner in 0..tris.len() {
+            let p = pos(corner);
+            let face = faces[corner / 3];
+            let shared = &by_position[&[p.x.to_bits(), p.y.to_bits(), p.z.to_bits()]];
+            let sum = shared.iter()
+                .filter(|&&other| faces[other / 3].dot(&face) >= crease_cos)
+                .fold(Vector3F::zeros(), |sum, &other| sum + faces[other / 3] * angles[other]);
+
+            // degenerate triangles have no direction of their own to fall back to
+            normals.push(sum.try_normalize(0.0f32).unwrap_or(face));
+        }
+
+        normals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///
+    /// Builds a unit cube with one vertex per corner, wound clockwise when seen from outside
+    ///
+    fn shared_cube() -> ModelBuilder {
+        let verts = (0..8).map(|i| MeshVertex {
+            pos: Point3F::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32),
+            ..Default::default()
+        }).collect();
+
+        // counter-clockwise quads, flipped to clockwise triangles below
+        let quads = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2],
+            [1, 3, 7, 5]];
+        let indices: Vec<u32> = quads.iter()
+            .flat_map(|q| vec![q[0], q[2], q[1], q[0], q[3], q[2]])
+            .collect();
+
+        ModelBuilder {
+            verts,
+            indices,
+            draws: vec![DrawData { start_index: 0, num_tris: 12, material_idx: 0 }],
+            ..Default::default()
+        }
+    }
+
+    fn near(a: &Vector3F, b: &Vector3F) -> bool {
+        (a - b).norm() < 1e-5f32
+    }
+
+    ///
+    /// Checks that every corner's normal is the outward axis of the cube face it belongs to
+    ///
+    fn has_face_normals(builder: &ModelBuilder) -> bool {
+        builder.indices.chunks(3).all(|tri| {
+            let pos = |i: u32| builder.verts[i as usize].pos.coords;
+            let centre = tri.iter().fold(Vector3F::zeros(), |c, &i| c + pos(i)) / 3.0f32
+                - Vector3F::repeat(0.5f32);
+            let axis = centre.map(|c| if c.abs() > 0.49f32 { c.signum() } else { 0.0f32 });
+            tri.iter().all(|&i| near(&builder.verts[i as usize].norm, &axis))
+        })
+    }
+
+    #[test]
+    fn flat_normals_split_every_corner() {
+        let mut cube = shared_cube();
+        cube.generate_normals(NormalGeneration::Flat);
+        assert_eq!(cube.verts.len(), 24);
+        assert!(has_face_normals(&cube));
+    }
+
+    #[test]
+    fn smooth_normals_keep_hard_edges_past_the_crease_angle() {
+        let mut cube = shared_cube();
+        cube.generate_normals(NormalGeneration::default());
+        assert_eq!(cube.verts.len(), 24);
+        assert!(has_face_normals(&cube));
+    }
+
+    #[test]
+    fn smooth_normals_are_angle_weighted() {
+        // the corners of a cube touch either one or two triangles of each face, but angle
+        // weighting makes every face count the same
+        let mut cube = shared_cube();
+        cube.generate_normals(NormalGeneration::Smooth { crease_angle: deg_to_rad(180.0f32) });
+        assert_eq!(cube.verts.len(), 8);
+        for v in &cube.verts {
+            let outward = (v.pos.coords - Vector3F::repeat(0.5f32)).normalize();
+            assert!(near(&v.norm, &outward));
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_stay_finite() {
+        let mut builder = shared_cube();
+        builder.indices.extend_from_slice(&[0, 0, 1]);
+        builder.draws[0].num_tris += 1;
+        builder.generate_normals(NormalGeneration::default());
+        assert!(builder.verts.iter().all(|v| v.norm.iter().all(|c| c.is_finite())));
+    }
+}
@@ -0,0 +1,182 @@
+// external refs
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// MTL statements for the texture slots the obj loader reads back
+///
+const MTL_TEXTURE_STATEMENTS: [(&str, &str); 7] = [
+    ("albedo_map", "map_Kd"),
+    ("ambient_map", "map_Ka"),
+    ("specular_map", "map_Ks"),
+    ("specular_power_map", "map_Ns"),
+    ("alpha_map", "map_d"),
+    ("normal_map", "norm"),
+    ("bump_map", "bump")
+];
+
+///
+/// Creates a file and writes it with the callback
+///
+fn write_file(path: &Path, write: impl FnOnce(&mut BufWriter<fs::File>) -> io::Result<()>)
+    -> Result<(), Error> {
+
+    let file = fs::File::create(path).map_err(|e| Error::io(path, e))?;
+    let mut out = BufWriter::new(file);
+    write(&mut out).and_then(|_| out.flush()).map_err(|e| Error::io(path, e))
+}
+
+impl ModelBuilder {
+    ///
+    /// Saves the model to an obj file, with its materials in an MTL file of the same name next to
+    /// it. Each part becomes an object. The part hierarchy, tangents, vertex alpha and the
+    /// metal-rough parameters and textures of glTF materials can't be stored and are left out.
+    ///
+    pub fn save_obj(&self, path: &Path) -> Result<(), Error> {
+        let mtl_path = path.with_extension("mtl");
+        let mtl_name = mtl_path.file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let mtl_dir = mtl_path.parent().unwrap_or_else(|| Path::new(""));
+
+        write_file(path, |out| self.write_obj(out, path, &mtl_name))?;
+        write_file(&mtl_path, |out| self.write_mtl(out, mtl_dir))
+    }
+
+    ///
+    /// Writes the geometry of the obj file, with each draw's vertices written before its faces
+    ///
+    fn write_obj(&self, out: &mut impl Write, path: &Path, mtl_name: &str) -> io::Result<()> {
+        writeln!(out, "# {} vertices, {} triangles", self.verts.len(), self.indices.len() / 3)?;
+        writeln!(out, "mtllib {}", mtl_name)?;
+
+        // colors follow the position, as an extension many tools understand
+        let has_uvs = self.verts.iter().any(|v| v.uv != Vector2F::zeros());
+        let has_colors = self.verts.iter().any(|v| v.color != Color4::white());
+        let mut base_vertex = 1;
+
+        for part in self.get_parts_for_saving(path) {
+            if part.draws.is_empty() {
+                continue;
+            }
+            writeln!(out, "o {}", part.name)?;
+
+            for draw in &self.draws[part.draws] {
+                let (used, tris) = self.get_draw_mesh(draw);
+                let verts = || used.iter().map(|&idx| &self.verts[idx as usize]);
+                for v in verts() {
+                    write!(out, "v {} {} {}", v.pos.x, v.pos.y, v.pos.z)?;
+                    if has_colors {
+                        let channel = |c: u8| c as f32 / 255.0f32;
+                        write!(out, " {} {} {}", channel(v.color.r), channel(v.color.g),
+                            channel(v.color.b))?;
+                    }
+                    writeln!(out)?;
+                }
+                if has_uvs {
+                    for v in verts() {
+                        writeln!(out, "vt {} {}", v.uv.x, 1.0f32 - v.uv.y)?;
+                    }
+                }
+                for v in verts() {
+                    writeln!(out, "vn {} {} {}", v.norm.x, v.norm.y, v.norm.z)?;
+                }
+
+                writeln!(out, "usemtl material{}", draw.material_idx)?;
+                for t in &tris {
+                    let [a, b, c] = t.map(|idx| idx as usize + base_vertex);
+                    if has_uvs {
+                        writeln!(out, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+                    } else {
+                        writeln!(out, "f {0}//{0} {1}//{1} {2}//{2}", a, b, c)?;
+                    }
+                }
+                base_vertex += used.len();
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Writes the material library, with texture paths relative to its directory
+    ///
+    fn write_mtl(&self, out: &mut impl Write, mtl_dir: &Path) -> io::Result<()> {
+        for (idx, mat) in self.materials.iter().enumerate() {
+            let p = &mat.params;
+            writeln!(out, "newmtl material{}", idx)?;
+            writeln!(out, "Ka {} {} {}", p.ambient.r, p.ambient.g, p.ambient.b)?;
+            writeln!(out, "Kd {} {} {}", p.base_color.r, p.base_color.g, p.base_color.b)?;
+            writeln!(out, "Ks {} {} {}", p.specular.r, p.specular.g, p.specular.b)?;
+            writeln!(out, "Ke {} {} {}", p.emissive.r, p.emissive.g, p.emissive.b)?;
+            writeln!(out, "Ns {}", p.specular_power)?;
+            writeln!(out, "d {}", p.base_color.a)?;
+            writeln!(out, "illum {}", p.illumination.to_mtl())?;
+
+            for (slot, statement) in MTL_TEXTURE_STATEMENTS.iter() {
+                if let Some(file) = mat.textures.get(*slot) {
+                    let reference = AssetResolver::make_reference(file, mtl_dir);
+                    writeln!(out, "{} {}", statement, reference)?;
+                }
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::object_material;
+    use super::super::test_files::*;
+
+    #[test]
+    fn obj_round_trip_keeps_parts_and_materials() {
+        let original = ModelBuilder::load_from_obj(&Path::new("tests").join("assets")
+            .join("parts.obj")).unwrap();
+        let path = scratch_path("parts.obj");
+        original.save_obj(&path).unwrap();
+        let loaded = ModelBuilder::load_from_obj(&path);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("mtl")).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.nodes, original.nodes);
+        let draws = |b: &ModelBuilder| b.draws.iter()
+            .map(|d| (d.start_index, d.num_tris, d.material_idx))
+            .collect::<Vec<_>>();
+        assert_eq!(draws(&loaded), draws(&original));
+        assert_eq!(loaded.indices, original.indices);
+        let attributes = |b: &ModelBuilder| b.verts.iter()
+            .map(|v| (v.pos, v.norm, v.uv))
+            .collect::<Vec<_>>();
+        assert_eq!(attributes(&loaded), attributes(&original));
+
+        let params = |b: &ModelBuilder| b.materials.iter().map(|m| m.params).collect::<Vec<_>>();
+        assert_eq!(params(&loaded), params(&original));
+    }
+
+    #[test]
+    fn texture_references_are_relative_to_the_library() {
+        let mut builder = ModelBuilder::default();
+        let mut textures = std::collections::HashMap::new();
+        textures.insert("albedo_map".to_string(), scratch_path("maps").join("wood oak.png"));
+        textures.insert("metallic_roughness_map".to_string(), scratch_path("orm.png"));
+        builder.materials.push(object_material(Path::new("object.hlsl"), textures,
+            MaterialParams::default()));
+
+        let mut mtl = Vec::new();
+        builder.write_mtl(&mut mtl, &std::env::temp_dir()).unwrap();
+        let mtl = String::from_utf8(mtl).unwrap();
+        let expected = format!("map_Kd modelviewer-{}-maps/wood oak.png\n", std::process::id());
+        assert!(mtl.contains(&expected), "{}", mtl);
+        assert!(!mtl.contains("orm.png"));
+    }
+}
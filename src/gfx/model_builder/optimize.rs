@@ -0,0 +1,341 @@
+// external refs
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+// local refs
+use crate::gfx::*;
+
+///
+/// Number of entries in the simulated post-transform vertex cache
+///
+const VERTEX_CACHE_SIZE: usize = 16;
+
+///
+/// Vertex counts and cache efficiency of a model before and after optimization
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptimizationStats {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    /// Average cache miss ratio, the number of vertices transformed per triangle
+    pub acmr_before: f32,
+    pub acmr_after: f32
+}
+
+impl fmt::Display for OptimizationStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {} vertices, ACMR {:.3} -> {:.3}", self.vertices_before,
+            self.vertices_after, self.acmr_before, self.acmr_after)
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Welds identical vertices, reorders each draw's triangles so the vertices they share stay
+    /// in the post-transform cache, then orders the vertices by first use
+    ///
+    pub fn optimize(&mut self) -> OptimizationStats {
+        let vertices_before = self.verts.len();
+        let acmr_before = self.get_acmr();
+
+        self.weld_vertices();
+        self.optimize_vertex_cache();
+        self.optimize_vertex_fetch();
+
+        let stats = OptimizationStats {
+            vertices_before,
+            vertices_after: self.verts.len(),
+            acmr_before,
+            acmr_after: self.get_acmr()
+        };
+        self.optimization = Some(stats);
+        stats
+    }
+
+    ///
+    /// Gets the average cache miss ratio of the draws, simulating a FIFO vertex cache
+    ///
+    pub fn get_acmr(&self) -> f32 {
+        let mut cache = VecDeque::with_capacity(VERTEX_CACHE_SIZE);
+        let mut misses = 0;
+        let mut num_tris = 0;
+        for draw in &self.draws {
+            let start = draw.start_index as usize;
+            for &idx in &self.indices[start..start + draw.num_tris as usize * 3] {
+                if !cache.contains(&idx) {
+                    if cache.len() == VERTEX_CACHE_SIZE {
+                        cache.pop_front();
+                    }
+                    cache.push_back(idx);
+                    misses += 1;
+                }
+            }
+            num_tris += draw.num_tris;
+        }
+
+        if num_tris == 0 { 0.0f32 } else { misses as f32 / num_tris as f32 }
+    }
+
+    ///
+    /// Merges vertices whose attributes are all identical
+    ///
+    fn weld_vertices(&mut self) {
+        let mut unique = HashMap::<[u32; 13], u32>::with_capacity(self.verts.len());
+        let mut welded = Vec::with_capacity(self.verts.len());
+        let remap: Vec<u32> = self.verts.iter().map(|v| {
+            let attribs = [v.pos.x, v.pos.y, v.pos.z, v.norm.x, v.norm.y, v.norm.z, v.tangent.x,
+                v.tangent.y, v.tangent.z, v.tangent.w, v.uv.x, v.uv.y];
+            let mut key = [0u32; 13];
+            for (bits, attrib) in key.iter_mut().zip(attribs.iter()) {
+                *bits = attrib.to_bits();
+            }
+            key[12] = u32::from_le_bytes([v.color.r, v.color.g, v.color.b, v.color.a]);
+
+            *unique.entry(key).or_insert_with(|| {
+                welded.push(*v);
+                (welded.len() - 1) as u32
+            })
+        }).collect();
+
+        for idx in &mut self.indices {
+            *idx = remap[*idx as usize];
+        }
+        self.verts = welded;
+    }
+
+    ///
+    /// Reorders the triangles of every draw, including those of the simplified versions
+    ///
+    fn optimize_vertex_cache(&mut self) {
+        let draws: Vec<DrawData> = self.draws.iter()
+            .chain(self.lods.iter().flat_map(|lod| &lod.draws))
+            .cloned()
+            .collect();
+        for draw in &draws {
+            self.optimize_draw_cache(draw);
+        }
+    }
+
+    ///
+    /// Reorders the triangles of a draw with Tipsify (Sander et al. 2007)
+    ///
+    pub(super) fn optimize_draw_cache(&mut self, draw: &DrawData) {
+        let start = draw.start_index as usize;
+        let range = start..start + draw.num_tris as usize * 3;
+        let ordered = tipsify(&self.indices[range.clone()], VERTEX_CACHE_SIZE);
+        self.indices[range].copy_from_slice(&ordered);
+    }
+
+    ///
+    /// Orders the vertices by their first use in the index buffer, dropping unused ones
+    ///
+    fn optimize_vertex_fetch(&mut self) {
+        const UNUSED: u32 = u32::MAX;
+
+        let mut remap = vec![UNUSED; self.verts.len()];
+        let mut ordered = Vec::with_capacity(self.verts.len());
+        for idx in &mut self.indices {
+            if remap[*idx as usize] == UNUSED {
+                remap[*idx as usize] = ordered.len() as u32;
+                ordered.push(self.verts[*idx as usize]);
+            }
+            *idx = remap[*idx as usize];
+        }
+        self.verts = ordered;
+    }
+}
+
+///
+/// Orders triangles so that each vertex's triangles are emitted while it's still in a FIFO cache
+/// of the specified size
+///
+/// # Returns
+/// The indices of the reordered triangles
+///
+fn tipsify(indices: &[u32], cache_size: usize) -> Vec<u32> {
+    // work with vertices numbered from 0 within the draw
+    let mut local = HashMap::<u32, usize>::new();
+    let verts: Vec<usize> = indices.iter()
+        .map(|idx| {
+            let next = local.len();
+            *local.entry(*idx).or_insert(next)
+        })
+        .collect();
+    let num_verts = local.len();
+    let num_tris = indices.len() / 3;
+
+    // triangles using each vertex
+    let mut live = vec![0usize; num_verts];
+    for &v in &verts {
+        live[v] += 1;
+    }
+    let mut offsets = vec![0usize; num_verts + 1];
+    for v in 0..num_verts {
+        offsets[v + 1] = offsets[v] + live[v];
+    }
+    let mut fill = offsets.clone();
+    let mut adjacency = vec![0usize; verts.len()];
+    for (corner, &v) in verts.iter().enumerate() {
+        adjacency[fill[v]] = corner / 3;
+        fill[v] += 1;
+    }
+
+    let mut cache_time = vec![0usize; num_verts];
+    let mut time = cache_size + 1;
+    let mut emitted = vec![false; num_tris];
+    let mut dead_end = Vec::<usize>::new();
+    let mut cursor = 0;
+    let mut output = Vec::with_capacity(indices.len());
+
+    let mut fanning = if num_verts > 0 { Some(0) } else { None };
+    while let Some(f) = fanning {
+        let mut candidates = Vec::new();
+        for &tri in &adjacency[offsets[f]..offsets[f + 1]] {
+            if emitted[tri] {
+                continue;
+            }
+            emitted[tri] = true;
+            for corner in tri * 3..tri * 3 + 3 {
+                let v = verts[corner];
+                output.push(indices[corner]);
+                dead_end.push(v);
+                candidates.push(v);
+                live[v] -= 1;
+                if time - cache_time[v] > cache_size {
+                    cache_time[v] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        // prefer the candidate that stays in the cache long enough to emit its triangles and
+        // has been there longest, otherwise fall back to recently used then unused vertices
+        let mut best = None;
+        let mut best_priority = 0;
+        for &v in &candidates {
+            if live[v] == 0 {
+                continue;
+            }
+            let age = time - cache_time[v];
+            let priority = if age + 2 * live[v] <= cache_size { age } else { 0 };
+            if best.is_none() || priority > best_priority {
+                best = Some(v);
+                best_priority = priority;
+            }
+        }
+
+        fanning = best.or_else(|| {
+            while let Some(v) = dead_end.pop() {
+                if live[v] > 0 {
+                    return Some(v);
+                }
+            }
+            while cursor < num_verts {
+                cursor += 1;
+                if live[cursor - 1] > 0 {
+                    return Some(cursor - 1);
+                }
+            }
+            None
+        });
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::numerics::*;
+
+    ///
+    /// Builds a grid of quads as an unindexed triangle soup, with rows of triangles emitted in
+    /// an order that defeats the vertex cache
+    ///
+    fn grid_soup(size: usize) -> ModelBuilder {
+        let vert = |x: usize, y: usize| MeshVertex {
+            pos: Point3F::new(x as f32, y as f32, 0.0f32),
+            norm: Vector3F::z(),
+            ..Default::default()
+        };
+
+        let mut verts = Vec::new();
+        for x in 0..size {
+            for y in 0..size {
+                verts.extend_from_slice(&[vert(x, y), vert(x, y + 1), vert(x + 1, y + 1),
+                    vert(x, y), vert(x + 1, y + 1), vert(x + 1, y)]);
+            }
+        }
+        let indices: Vec<u32> = (0..verts.len() as u32).collect();
+        let num_tris = (indices.len() / 3) as u32;
+
+        ModelBuilder {
+            verts,
+            indices,
+            draws: vec![DrawData { start_index: 0, num_tris, material_idx: 0 }],
+            ..Default::default()
+        }
+    }
+
+    ///
+    /// Collects every triangle's corner positions, rotated to start at the smallest index so
+    /// the result doesn't depend on which corner comes first
+    ///
+    fn triangles(builder: &ModelBuilder, draw: usize) -> Vec<[(u32, u32); 3]> {
+        let key = |i: u32| {
+            let p = builder.verts[i as usize].pos;
+            (p.x as u32, p.y as u32)
+        };
+        let start = builder.draws[draw].start_index as usize;
+        let end = start + builder.draws[draw].num_tris as usize * 3;
+        let mut tris: Vec<[(u32, u32); 3]> = builder.indices[start..end].chunks(3).map(|t| {
+            let corners = [key(t[0]), key(t[1]), key(t[2])];
+            let first = (0..3).min_by_key(|&i| corners[i]).unwrap();
+            [corners[first], corners[(first + 1) % 3], corners[(first + 2) % 3]]
+        }).collect();
+        tris.sort();
+        tris
+    }
+
+    #[test]
+    fn welds_and_improves_cache_use() {
+        let mut builder = grid_soup(16);
+        let before = triangles(&builder, 0);
+        let stats = builder.optimize();
+
+        assert_eq!(stats.vertices_before, 16 * 16 * 6);
+        assert_eq!(stats.vertices_after, 17 * 17);
+        assert_eq!(stats.acmr_before, 3.0f32);
+        assert!(stats.acmr_after < 1.0f32, "ACMR {}", stats.acmr_after);
+
+        // the same triangles are drawn, with their winding intact
+        assert_eq!(triangles(&builder, 0), before);
+    }
+
+    #[test]
+    fn vertices_are_ordered_by_first_use() {
+        let mut builder = grid_soup(4);
+        builder.optimize();
+
+        let mut next = 0;
+        for &idx in &builder.indices {
+            assert!(idx <= next);
+            if idx == next {
+                next += 1;
+            }
+        }
+        assert_eq!(next as usize, builder.verts.len());
+    }
+
+    #[test]
+    fn draws_keep_their_own_triangles() {
+        let mut builder = grid_soup(4);
+        builder.draws = vec![
+            DrawData { start_index: 0, num_tris: 12, material_idx: 0 },
+            DrawData { start_index: 36, num_tris: 20, material_idx: 1 }
+        ];
+        let before = [triangles(&builder, 0), triangles(&builder, 1)];
+        builder.optimize();
+        assert_eq!([triangles(&builder, 0), triangles(&builder, 1)], before);
+    }
+}
@@ -0,0 +1,455 @@
+// external refs
+use std::fs;
+use std::path::Path;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+
+///
+/// Scalar types of PLY properties
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64
+}
+
+impl PlyType {
+    ///
+    /// Converts a type name from a property statement, accepting both the original names and
+    /// the sized ones
+    ///
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(PlyType::I8),
+            "uchar" | "uint8" => Some(PlyType::U8),
+            "short" | "int16" => Some(PlyType::I16),
+            "ushort" | "uint16" => Some(PlyType::U16),
+            "int" | "int32" => Some(PlyType::I32),
+            "uint" | "uint32" => Some(PlyType::U32),
+            "float" | "float32" => Some(PlyType::F32),
+            "double" | "float64" => Some(PlyType::F64),
+            _ => None
+        }
+    }
+
+    ///
+    /// Gets the size of a value in binary files
+    ///
+    fn get_size(self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8
+        }
+    }
+}
+
+///
+/// A property of each record of an element, either a single value or a list with its length
+/// stored first
+///
+#[derive(Debug)]
+struct PlyProperty {
+    name: String,
+    ty: PlyType,
+    list_count: Option<PlyType>
+}
+
+///
+/// A block of records declared by the header, such as the vertices or faces
+///
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    props: Vec<PlyProperty>
+}
+
+///
+/// How the records after the header are stored
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian
+}
+
+///
+/// Reads values from the records after the header
+///
+struct PlyBody<'a> {
+    path: &'a Path,
+    format: PlyFormat,
+    data: &'a [u8],
+    pos: usize
+}
+
+impl PlyBody<'_> {
+    fn truncated(&self) -> Error {
+        Error::parse(self.path, None, "file ends before all the elements the header declares")
+    }
+
+    ///
+    /// Reads the next value as the specified type
+    ///
+    fn read(&mut self, ty: PlyType) -> Result<f64, Error> {
+        if self.format == PlyFormat::Ascii {
+            let start = self.pos + self.data[self.pos..].iter()
+                .position(|b| !b.is_ascii_whitespace())
+                .ok_or_else(|| self.truncated())?;
+            let len = self.data[start..].iter()
+                .position(|b| b.is_ascii_whitespace())
+                .unwrap_or(self.data.len() - start);
+            self.pos = start + len;
+
+            let token = String::from_utf8_lossy(&self.data[start..start + len]);
+            return token.parse::<f64>().map_err(|_| {
+                Error::parse(self.path, None, format!("expected a number but got {}", token))
+            });
+        }
+
+        let size = ty.get_size();
+        if self.pos + size > self.data.len() {
+            return Err(self.truncated());
+        }
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(&self.data[self.pos..self.pos + size]);
+        if self.format == PlyFormat::BinaryBigEndian {
+            b[..size].reverse();
+        }
+        self.pos += size;
+
+        Ok(match ty {
+            PlyType::I8 => b[0] as i8 as f64,
+            PlyType::U8 => b[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(b)
+        })
+    }
+
+    ///
+    /// Gets the most records of an element the rest of the body can hold, so a count in the header
+    /// that's larger than the file doesn't reserve memory the records could never fill
+    ///
+    fn get_max_records(&self, element: &PlyElement) -> usize {
+        let record_size: usize = element.props.iter()
+            .map(|p| match self.format {
+                // a value takes at least one character
+                PlyFormat::Ascii => 1,
+                // a list takes at least its count
+                _ => p.list_count.unwrap_or(p.ty).get_size()
+            })
+            .sum();
+        (self.data.len() - self.pos) / record_size.max(1)
+    }
+
+    ///
+    /// Reads every value of a property, which is a single value unless it's a list
+    ///
+    fn read_property(&mut self, prop: &PlyProperty, values: &mut Vec<f64>) -> Result<(), Error> {
+        values.clear();
+        let count = match prop.list_count {
+            Some(count_ty) => self.read(count_ty)? as usize,
+            None => 1
+        };
+        for _ in 0..count {
+            values.push(self.read(prop.ty)?);
+        }
+        Ok(())
+    }
+}
+
+///
+/// Parses the header, which is ASCII even in binary files
+///
+/// # Returns
+/// The body format, the elements and the offset the body starts at
+///
+fn parse_header(path: &Path, data: &[u8]) -> Result<(PlyFormat, Vec<PlyElement>, usize), Error> {
+    let mut format = None;
+    let mut elements = Vec::<PlyElement>::new();
+    let mut pos = 0;
+
+    for line_number in 1.. {
+        let len = data[pos..].iter().position(|&b| b == b'\n')
+            .ok_or_else(|| Error::parse(path, Some(line_number), "header has no end_header"))?;
+        let line = String::from_utf8_lossy(&data[pos..pos + len]);
+        pos += len + 1;
+
+        let error = |message: &str| Error::parse(path, Some(line_number), message);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["ply"] if line_number == 1 => {}
+            _ if line_number == 1 => return Err(error("not a PLY file")),
+            ["format", name, _version] => {
+                format = Some(match name {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return Err(error("unknown format"))
+                });
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| error("element count isn't a number"))?,
+                props: Vec::new()
+            }),
+            ["property", "list", count_ty, ty, name] => {
+                let prop = PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyType::from_name(ty).ok_or_else(|| error("unknown property type"))?,
+                    list_count: Some(PlyType::from_name(count_ty)
+                        .ok_or_else(|| error("unknown property type"))?)
+                };
+                elements.last_mut().ok_or_else(|| error("property before any element"))?
+                    .props.push(prop);
+            }
+            ["property", ty, name] => {
+                let prop = PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyType::from_name(ty).ok_or_else(|| error("unknown property type"))?,
+                    list_count: None
+                };
+                elements.last_mut().ok_or_else(|| error("property before any element"))?
+                    .props.push(prop);
+            }
+            ["end_header"] => break,
+            // comments, obj_info and blank lines carry nothing we use
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| Error::parse(path, None, "header has no format"))?;
+    Ok((format, elements, pos))
+}
+
+///
+/// Converts a color channel to 8 bits. Floating point channels range from 0 to 1.
+///
+fn color_channel(value: f64, ty: PlyType) -> u8 {
+    match ty {
+        PlyType::F32 | PlyType::F64 => (value * 255.0).round().clamp(0.0, 255.0) as u8,
+        PlyType::U16 => (value / 257.0).round() as u8,
+        _ => value.clamp(0.0, 255.0) as u8
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Loads a model from a PLY file, in ASCII or binary, using the default resolver to find the
+    /// object shader
+    ///
+    pub fn load_from_ply(path: &Path) -> Result<Self, Error> {
+        Self::load_from_ply_with_resolver(path, &AssetResolver::default())
+    }
+
+    ///
+    /// Loads a model from a PLY file. Vertex positions, normals, colors and texture coordinates are
+    /// read, and polygons are split into triangles. Normals are generated when the file doesn't
+    /// have them.
+    ///
+    pub fn load_from_ply_with_resolver(path: &Path, resolver: &AssetResolver)
+        -> Result<Self, Error> {
+
+        let data = fs::read(path).map_err(|e| Error::io(path, e))?;
+        let (format, elements, body_start) = parse_header(path, &data)?;
+        let mut body = PlyBody { path, format, data: &data[body_start..], pos: 0 };
+
+        let mut verts = Vec::new();
+        let mut tris = Vec::<[u32; 3]>::new();
+        let (mut has_normals, mut has_uvs) = (false, false);
+        let mut values = Vec::new();
+
+        for element in &elements {
+            match element.name.as_str() {
+                "vertex" => {
+                    has_normals = element.props.iter().any(|p| p.name == "nx");
+                    has_uvs = element.props.iter()
+                        .any(|p| matches!(p.name.as_str(), "u" | "s" | "texture_u" | "texture_s"));
+                    verts.reserve(element.count.min(body.get_max_records(element)));
+                }
+                "face" => tris.reserve(element.count.min(body.get_max_records(element))),
+                _ => {}
+            }
+
+            for _ in 0..element.count {
+                let mut v = MeshVertex::default();
+                for prop in &element.props {
+                    body.read_property(prop, &mut values)?;
+                    let value = values.first().copied().unwrap_or(0.0) as f32;
+                    let channel = || color_channel(values[0], prop.ty);
+
+                    match (element.name.as_str(), prop.name.as_str()) {
+                        ("vertex", "x") => v.pos.x = value,
+                        ("vertex", "y") => v.pos.y = value,
+                        ("vertex", "z") => v.pos.z = value,
+                        ("vertex", "nx") => v.norm.x = value,
+                        ("vertex", "ny") => v.norm.y = value,
+                        ("vertex", "nz") => v.norm.z = value,
+                        ("vertex", "u" | "s" | "texture_u" | "texture_s") => v.uv.x = value,
+                        // ply's v points up the image, like obj's
+                        ("vertex", "v" | "t" | "texture_v" | "texture_t") => {
+                            v.uv.y = 1.0f32 - value
+                        }
+                        ("vertex", "red" | "diffuse_red") => v.color.r = channel(),
+                        ("vertex", "green" | "diffuse_green") => v.color.g = channel(),
+                        ("vertex", "blue" | "diffuse_blue") => v.color.b = channel(),
+                        ("vertex", "alpha") => v.color.a = channel(),
+                        ("face", "vertex_indices" | "vertex_index") => {
+                            // polygons are split into a fan around their first corner
+                            for i in 2..values.len() {
+                                let corners = [values[0], values[i - 1], values[i]];
+                                if let Some(bad) = corners.iter().find(|&&c| c < 0.0) {
+                                    return Err(Error::parse(path, None,
+                                        format!("face uses negative vertex index {}", bad)));
+                                }
+                                tris.push(corners.map(|c| c as u32));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                if element.name == "vertex" {
+                    verts.push(v);
+                }
+            }
+        }
+
+        if let Some(bad) = tris.iter().flatten().find(|&&i| i as usize >= verts.len()) {
+            return Err(Error::parse(path, None, format!("face uses vertex {} but there are only {}",
+                bad, verts.len())));
+        }
+        if tris.is_empty() {
+            return Err(Error::UnsupportedFormat { what: "PLY file without faces".to_string(),
+                path: Some(path.to_path_buf()) });
+        }
+
+        let name = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let mut builder = Self::with_default_material(path, resolver);
+        let draw = [builder.add_mesh(name, verts, &tris)];
+        if !has_normals {
+            builder.generate_normals_for_draws(&draw, NormalGeneration::default());
+        }
+        if has_uvs {
+            builder.generate_tangents_for_draws(&draw);
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_files::*;
+    use crate::numerics::*;
+    use std::path::PathBuf;
+
+    ///
+    /// Writes a PLY file with the specified header lines and body to a temporary file
+    ///
+    fn write_ply(name: &str, header: &[&str], body: &[u8]) -> PathBuf {
+        let mut data = format!("ply\n{}\nend_header\n", header.join("\n")).into_bytes();
+        data.extend_from_slice(body);
+        write_scratch_file(&format!("{}.ply", name), data)
+    }
+
+    #[test]
+    fn loads_ascii_quad_with_colors() {
+        let path = write_ply("ascii", &[
+            "format ascii 1.0",
+            "comment a unit quad facing +z with a red corner",
+            "element vertex 4",
+            "property float x", "property float y", "property float z",
+            "property uchar red", "property uchar green", "property uchar blue",
+            "element face 1",
+            "property list uchar int vertex_indices"
+        ], b"0 0 0 255 0 0\n1 0 0 255 255 255\n1 1 0 255 255 255\n0 1 0 255 255 255\n4 0 1 2 3\n");
+        let builder = ModelBuilder::load_from_ply(&path);
+        fs::remove_file(&path).unwrap();
+        let builder = builder.unwrap();
+
+        assert_eq!(builder.verts.len(), 4);
+        assert_eq!(builder.verts[0].color, Color4::from_rgba(255, 0, 0, 255));
+        assert_eq!(builder.indices, vec![0, 2, 1, 0, 3, 2]);
+        assert_eq!((builder.draws.len(), builder.materials.len()), (1, 1));
+        assert!(builder.verts.iter().all(|v| v.norm == Vector3F::z()));
+    }
+
+    #[test]
+    fn loads_binary_with_extra_elements() {
+        let mut body = Vec::new();
+        for p in [[0.0f32, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]].iter() {
+            for c in p {
+                body.extend_from_slice(&c.to_be_bytes());
+            }
+            body.extend_from_slice(&[0.5f32.to_be_bytes(), 1.0f32.to_be_bytes()].concat());
+        }
+        body.push(3);
+        for i in [0u32, 1, 2].iter() {
+            body.extend_from_slice(&i.to_be_bytes());
+        }
+        body.extend_from_slice(&7i16.to_be_bytes());
+
+        let path = write_ply("binary", &[
+            "format binary_big_endian 1.0",
+            "element vertex 3",
+            "property float x", "property float y", "property float z",
+            "property float green", "property float alpha",
+            "element face 1",
+            "property list uint8 uint32 vertex_index",
+            "element edge 1",
+            "property short crease"
+        ], &body);
+        let builder = ModelBuilder::load_from_ply(&path);
+        fs::remove_file(&path).unwrap();
+        let builder = builder.unwrap();
+
+        assert_eq!(builder.verts[1].pos, Point3F::new(0.0f32, 1.0f32, 0.0f32));
+        assert_eq!(builder.verts[2].color, Color4::from_rgba(255, 128, 255, 255));
+        assert_eq!(builder.indices, vec![0, 2, 1]);
+        // the counter-clockwise triangle faces -z
+        assert!(builder.verts.iter().all(|v| v.norm == -Vector3F::z()));
+    }
+
+    #[test]
+    fn rejects_out_of_range_faces() {
+        let path = write_ply("range", &[
+            "format ascii 1.0",
+            "element vertex 1",
+            "property float x", "property float y", "property float z",
+            "element face 1",
+            "property list uchar int vertex_indices"
+        ], b"0 0 0\n3 0 1 2\n");
+        let result = ModelBuilder::load_from_ply(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn rejects_counts_larger_than_the_file() {
+        let path = write_ply("huge", &[
+            "format binary_little_endian 1.0",
+            "element vertex 4000000000",
+            "property float x", "property float y", "property float z",
+            "element face 4000000000",
+            "property list uchar int vertex_indices"
+        ], &[0u8; 24]);
+        let result = ModelBuilder::load_from_ply(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Parse { .. })));
+    }
+}
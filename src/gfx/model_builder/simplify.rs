@@ -0,0 +1,589 @@
+// external refs
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Weight of the planes that hold open borders in place, relative to the triangles' planes
+///
+const BORDER_WEIGHT: f64 = 10.0f64;
+
+///
+/// How far to simplify the model for a level of detail
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodTarget {
+    /// Keep this fraction of the full model's triangles
+    Ratio(f32),
+    /// Simplify for as long as the surface stays within this fraction of the full model's
+    /// bounding radius of where it was
+    Error(f32)
+}
+
+impl FromStr for LodTarget {
+    type Err = String;
+
+    ///
+    /// Parses a ratio such as `0.25`, optionally written `ratio:0.25`, or an error such as
+    /// `error:0.01`
+    ///
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (value, is_error) = match s.strip_prefix("error:") {
+            Some(value) => (value, true),
+            None => (s.strip_prefix("ratio:").unwrap_or(s), false)
+        };
+        match value.trim().parse::<f32>() {
+            Ok(error) if is_error && error >= 0.0f32 && error.is_finite() => Ok(Self::Error(error)),
+            Ok(ratio) if !is_error && ratio > 0.0f32 && ratio <= 1.0f32 => Ok(Self::Ratio(ratio)),
+            _ => Err(format!("Expected a ratio between 0 and 1 or error:<fraction> but got {}", s))
+        }
+    }
+}
+
+///
+/// Error quadric of a position, the weighted sum of the squared distances to a set of planes
+///
+#[derive(Clone, Copy)]
+struct Quadric(Matrix4<f64>);
+
+impl Quadric {
+    fn from_plane(normal: &Vector3<f64>, point: &Vector3<f64>, weight: f64) -> Self {
+        let plane = Vector4::new(normal.x, normal.y, normal.z, -normal.dot(point));
+        Quadric(plane * plane.transpose() * weight)
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        self.0 += other.0;
+    }
+
+    fn get_error(&self, p: &Vector3<f64>) -> f64 {
+        let v = Vector4::new(p.x, p.y, p.z, 1.0f64);
+        v.dot(&(self.0 * v)).max(0.0f64)
+    }
+}
+
+///
+/// Gets the normal of a triangle scaled by twice its area, which faces the viewer when the
+/// corners are clockwise
+///
+fn triangle_normal(corners: &[Vector3<f64>; 3]) -> Vector3<f64> {
+    (corners[2] - corners[0]).cross(&(corners[1] - corners[0]))
+}
+
+///
+/// Simplifies the triangles of a single draw by collapsing edges onto one of their ends, so the
+/// simplified triangles only use vertices the draw already has. Vertices are grouped by position,
+/// and a position's vertices by their texture coordinates and color into wedges. A position with
+/// several wedges is on a seam and can only move along it, onto a position whose wedges match its
+/// own one for one. Vertices that only differ in their normals don't form seams, so faceted meshes
+/// can still be simplified.
+///
+struct DrawSimplifier<'a> {
+    verts: &'a [MeshVertex],
+    tris: Vec<[u32; 3]>,
+    /// Position of each vertex the triangles use, numbered within the draw
+    vertex_positions: HashMap<u32, usize>,
+    /// Wedge of each vertex the triangles use, numbered within the draw
+    vertex_wedges: HashMap<u32, usize>,
+    points: Vec<Vector3<f64>>,
+    quadrics: Vec<Quadric>,
+    /// Positions that can't move, where the draw meets others or its surface isn't manifold
+    locked: Vec<bool>,
+    /// Positions on an open border, which can only move along it
+    border: Vec<bool>
+}
+
+impl<'a> DrawSimplifier<'a> {
+    ///
+    /// Prepares the triangles for simplification, dropping any that are already degenerate
+    ///
+    fn new(tris: Vec<[u32; 3]>, verts: &'a [MeshVertex], position_ids: &[usize], shared: &[bool])
+        -> Self {
+
+        let mut local = HashMap::new();
+        let mut wedges = HashMap::new();
+        let mut simplifier = Self {
+            verts,
+            tris: Vec::with_capacity(tris.len()),
+            vertex_positions: HashMap::new(),
+            vertex_wedges: HashMap::new(),
+            points: Vec::new(),
+            quadrics: Vec::new(),
+            locked: Vec::new(),
+            border: Vec::new()
+        };
+        for &v in tris.iter().flatten() {
+            let id = position_ids[v as usize];
+            let position = *local.entry(id).or_insert_with(|| {
+                let p = verts[v as usize].pos;
+                simplifier.points.push(Vector3::new(p.x as f64, p.y as f64, p.z as f64));
+                simplifier.locked.push(shared[id]);
+                simplifier.points.len() - 1
+            });
+            simplifier.vertex_positions.insert(v, position);
+
+            let vert = &verts[v as usize];
+            let key = (position, vert.uv.x.to_bits(), vert.uv.y.to_bits(),
+                [vert.color.r, vert.color.g, vert.color.b, vert.color.a]);
+            let num_wedges = wedges.len();
+            simplifier.vertex_wedges.insert(v, *wedges.entry(key).or_insert(num_wedges));
+        }
+        let num_positions = simplifier.points.len();
+        simplifier.quadrics = vec![Quadric(Matrix4::zeros()); num_positions];
+        simplifier.border = vec![false; num_positions];
+
+        simplifier.tris = tris.into_iter()
+            .filter(|tri| {
+                let [a, b, c] = simplifier.get_positions(tri);
+                a != b && b != c && c != a
+            })
+            .collect();
+
+        let mut edge_tris = HashMap::<(usize, usize), Vec<usize>>::new();
+        for (t, tri) in simplifier.tris.iter().enumerate() {
+            let positions = simplifier.get_positions(tri);
+            let corners = positions.map(|p| simplifier.points[p]);
+            let normal = triangle_normal(&corners).try_normalize(0.0f64).unwrap_or_else(zero);
+            for (corner, &p) in positions.iter().enumerate() {
+                simplifier.quadrics[p].add(&Quadric::from_plane(&normal, &corners[corner], 1.0f64));
+                let next = positions[(corner + 1) % 3];
+                edge_tris.entry((p.min(next), p.max(next))).or_default().push(t);
+            }
+        }
+
+        // borders get planes at right angles to their triangles, which resist moving off the line
+        for (&(a, b), edge) in &edge_tris {
+            match edge.len() {
+                1 => {
+                    let positions = simplifier.get_positions(&simplifier.tris[edge[0]]);
+                    let normal = triangle_normal(&positions.map(|p| simplifier.points[p]));
+                    let along = simplifier.points[b] - simplifier.points[a];
+                    if let Some(across) = along.cross(&normal).try_normalize(0.0f64) {
+                        let plane = Quadric::from_plane(&across, &simplifier.points[a],
+                            BORDER_WEIGHT);
+                        simplifier.quadrics[a].add(&plane);
+                        simplifier.quadrics[b].add(&plane);
+                    }
+                    simplifier.border[a] = true;
+                    simplifier.border[b] = true;
+                }
+                2 => {}
+                _ => {
+                    simplifier.locked[a] = true;
+                    simplifier.locked[b] = true;
+                }
+            }
+        }
+
+        simplifier
+    }
+
+    fn get_positions(&self, tri: &[u32; 3]) -> [usize; 3] {
+        tri.map(|v| self.vertex_positions[&v])
+    }
+
+    ///
+    /// Collapses the cheapest edges until the triangle count or the error is reached. Each pass
+    /// collapses edges that don't share any triangles, then rebuilds the adjacency.
+    ///
+    /// # Returns
+    /// The largest error of the collapses made, as a distance
+    ///
+    fn simplify(&mut self, target_tris: usize, max_error: f64) -> f64 {
+        let max_cost = if max_error > 0.0f64 { max_error * max_error } else { 0.0f64 };
+        let mut largest_cost = 0.0f64;
+
+        while self.tris.len() > target_tris {
+            let tri_positions: Vec<[usize; 3]> = self.tris.iter()
+                .map(|tri| self.get_positions(tri))
+                .collect();
+            let mut position_tris = vec![Vec::new(); self.points.len()];
+            for (t, positions) in tri_positions.iter().enumerate() {
+                for &p in positions {
+                    position_tris[p].push(t);
+                }
+            }
+
+            let mut collapses = Vec::new();
+            for positions in &tri_positions {
+                for corner in 0..3 {
+                    let (a, b) = (positions[corner], positions[(corner + 1) % 3]);
+                    for (from, to) in [(a, b), (b, a)] {
+                        if !self.locked[from] {
+                            let mut quadric = self.quadrics[from];
+                            quadric.add(&self.quadrics[to]);
+                            collapses.push((quadric.get_error(&self.points[to]), from, to));
+                        }
+                    }
+                }
+            }
+            collapses.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+            let mut touched = vec![false; self.points.len()];
+            let mut removed = vec![false; self.tris.len()];
+            let mut remaining = self.tris.len();
+            for (cost, from, to) in collapses {
+                if remaining <= target_tris || cost > max_cost {
+                    break;
+                }
+                if touched[from] || touched[to] {
+                    continue;
+                }
+                let Some(remap) = self.check_collapse(from, to, &tri_positions, &position_tris)
+                else {
+                    continue;
+                };
+
+                for &t in &position_tris[from] {
+                    for &p in &tri_positions[t] {
+                        touched[p] = true;
+                    }
+                    if tri_positions[t].contains(&to) {
+                        removed[t] = true;
+                        remaining -= 1;
+                    } else {
+                        for v in &mut self.tris[t] {
+                            *v = remap.get(v).copied().unwrap_or(*v);
+                        }
+                    }
+                }
+                let quadric = self.quadrics[from];
+                self.quadrics[to].add(&quadric);
+                largest_cost = largest_cost.max(cost);
+            }
+
+            if remaining == self.tris.len() {
+                break;
+            }
+            let mut idx = 0;
+            self.tris.retain(|_| {
+                idx += 1;
+                !removed[idx - 1]
+            });
+        }
+
+        largest_cost.sqrt()
+    }
+
+    ///
+    /// Checks whether a position can be moved onto a neighbour without tearing a seam, pulling
+    /// in a border, pinching the surface or flipping a triangle
+    ///
+    /// # Returns
+    /// The vertex each of the position's vertices is replaced with, if the collapse is allowed.
+    /// Each goes to a vertex of the matching wedge, the one whose normal is closest to its own.
+    ///
+    fn check_collapse(&self, from: usize, to: usize, tri_positions: &[[usize; 3]],
+        position_tris: &[Vec<usize>]) -> Option<HashMap<u32, u32>> {
+
+        let vertex_at = |t: usize, p: usize| {
+            let corner = tri_positions[t].iter().position(|&c| c == p)?;
+            Some(self.tris[t][corner])
+        };
+
+        // the triangles along the edge pair up the wedges at either end
+        let mut wedge_remap = HashMap::new();
+        let mut shared = 0;
+        for &t in position_tris[from].iter().filter(|&&t| tri_positions[t].contains(&to)) {
+            let old = self.vertex_wedges[&vertex_at(t, from)?];
+            let new = self.vertex_wedges[&vertex_at(t, to)?];
+            if *wedge_remap.entry(old).or_insert(new) != new {
+                return None;
+            }
+            shared += 1;
+        }
+        let mut remap = HashMap::new();
+        for &t in &position_tris[from] {
+            let old = vertex_at(t, from)?;
+            let wedge = *wedge_remap.get(&self.vertex_wedges[&old])?;
+            let norm = self.verts[old as usize].norm;
+            let new = position_tris[to].iter()
+                .filter_map(|&t| vertex_at(t, to))
+                .filter(|v| self.vertex_wedges[v] == wedge)
+                .max_by(|a, b| {
+                    let closeness = |v: &u32| self.verts[*v as usize].norm.dot(&norm);
+                    closeness(a).total_cmp(&closeness(b))
+                })?;
+            remap.insert(old, new);
+        }
+        if self.border[from] && shared != 1 {
+            return None;
+        }
+
+        // the only positions next to both ends can be the far corners of the triangles between
+        let neighbours = |p: usize| position_tris[p].iter()
+            .flat_map(|&t| tri_positions[t])
+            .filter(|&n| n != p)
+            .collect::<HashSet<usize>>();
+        if neighbours(from).intersection(&neighbours(to)).count() != shared {
+            return None;
+        }
+
+        for &t in position_tris[from].iter().filter(|&&t| !tri_positions[t].contains(&to)) {
+            let before = tri_positions[t].map(|p| self.points[p]);
+            let after = tri_positions[t].map(|p| self.points[if p == from { to } else { p }]);
+            if triangle_normal(&before).dot(&triangle_normal(&after)) <= 0.0f64 {
+                return None;
+            }
+        }
+
+        Some(remap)
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Generates a chain of simplified versions of the model, replacing any generated before.
+    /// Each level is simplified further from the one before it with quadric error metrics (Garland
+    /// and Heckbert 1997), collapsing edges until its target is met or no more edges can be
+    /// collapsed. The levels share the model's vertices, so seams in the texture coordinates and
+    /// colors are kept, and positions where draws meet stay in place so the materials still line
+    /// up.
+    ///
+    /// # Returns
+    /// The simplified versions, from the most to the least detailed
+    ///
+    pub fn generate_lods(&mut self, targets: &[LodTarget]) -> &[ModelLod] {
+        if !self.lods.is_empty() {
+            let full_end = self.draws.iter()
+                .map(|draw| draw.start_index as usize + draw.num_tris as usize * 3)
+                .max()
+                .unwrap_or(0);
+            self.indices.truncate(full_end);
+            self.lods.clear();
+        }
+
+        let mut draw_tris: Vec<Vec<[u32; 3]>> = self.draws.iter().map(|draw| {
+            let start = draw.start_index as usize;
+            self.indices[start..start + draw.num_tris as usize * 3].chunks_exact(3)
+                .map(|tri| [tri[0], tri[1], tri[2]])
+                .collect()
+        }).collect();
+
+        let (position_ids, num_positions) = self.get_position_ids();
+        let mut first_draw = vec![None; num_positions];
+        let mut shared = vec![false; num_positions];
+        for (draw, tris) in draw_tris.iter().enumerate() {
+            for &v in tris.iter().flatten() {
+                let id = position_ids[v as usize];
+                match first_draw[id] {
+                    None => first_draw[id] = Some(draw),
+                    Some(other) => shared[id] |= other != draw
+                }
+            }
+        }
+
+        let points: Vec<Point3F> = draw_tris.iter().flatten().flatten()
+            .map(|&v| self.verts[v as usize].pos)
+            .collect();
+        let radius = BoundingSphere::from_points(&points).map_or(0.0f32, |s| s.radius) as f64;
+        let full_tris = points.len() / 3;
+        let mut error = 0.0f64;
+
+        for target in targets {
+            let (target_tris, max_error) = match *target {
+                LodTarget::Ratio(ratio) => ((full_tris as f32 * ratio) as usize, f64::INFINITY),
+                LodTarget::Error(fraction) => (0, fraction as f64 * radius - error)
+            };
+
+            // each draw keeps its share of the triangles
+            let current_tris = draw_tris.iter().map(Vec::len).sum::<usize>().max(1);
+            let mut step_error = 0.0f64;
+            for tris in &mut draw_tris {
+                let draw_target = (tris.len() * target_tris).div_ceil(current_tris);
+                let mut simplifier = DrawSimplifier::new(std::mem::take(tris), &self.verts,
+                    &position_ids, &shared);
+                step_error = step_error.max(simplifier.simplify(draw_target, max_error));
+                *tris = simplifier.tris;
+            }
+            error += step_error;
+
+            let mut draws = Vec::with_capacity(self.draws.len());
+            for (draw, tris) in self.draws.iter().zip(&draw_tris) {
+                draws.push(DrawData {
+                    start_index: self.indices.len() as u32,
+                    num_tris: tris.len() as u32,
+                    material_idx: draw.material_idx
+                });
+                self.indices.extend(tris.iter().flatten());
+            }
+            for draw in &draws {
+                self.optimize_draw_cache(draw);
+            }
+            self.lods.push(ModelLod { draws, error: error as f32 });
+        }
+
+        &self.lods
+    }
+
+    ///
+    /// Gets the simplified versions of the model, from the most to the least detailed
+    ///
+    pub fn get_lods(&self) -> &[ModelLod] {
+        &self.lods
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///
+    /// Builds a grid of quads in the xy plane, bent around the y axis by the specified angle
+    /// across its width. The columns at `seam` get a second set of vertices with different
+    /// texture coordinates, used by the triangles to their right.
+    ///
+    fn grid(size: u32, bend: f32, seam: Option<u32>) -> ModelBuilder {
+        let vert = |x: u32, y: u32, u: f32| {
+            let angle = bend * (x as f32 / size as f32 - 0.5f32);
+            let pos = if bend == 0.0f32 {
+                Point3F::new(x as f32, y as f32, 0.0f32)
+            } else {
+                let radius = size as f32 / bend;
+                Point3F::new(radius * angle.sin(), y as f32, radius * angle.cos())
+            };
+            MeshVertex { pos, uv: Vector2F::new(u, y as f32), ..Default::default() }
+        };
+
+        let row = size + 1;
+        let mut verts: Vec<MeshVertex> = (0..row * row)
+            .map(|idx| vert(idx % row, idx / row, (idx % row) as f32))
+            .collect();
+        let mut seam_verts = HashMap::new();
+        if let Some(column) = seam {
+            for y in 0..row {
+                seam_verts.insert(y * row + column, verts.len() as u32);
+                verts.push(vert(column, y, 100.0f32));
+            }
+        }
+
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let corner = |dx: u32, dy: u32| {
+                    let idx = (y + dy) * row + x + dx;
+                    match seam {
+                        Some(column) if x == column => seam_verts.get(&idx).copied().unwrap_or(idx),
+                        _ => idx
+                    }
+                };
+                indices.extend_from_slice(&[corner(0, 0), corner(0, 1), corner(1, 1),
+                    corner(0, 0), corner(1, 1), corner(1, 0)]);
+            }
+        }
+
+        let num_tris = indices.len() as u32 / 3;
+        ModelBuilder {
+            verts,
+            indices,
+            draws: vec![DrawData { start_index: 0, num_tris, material_idx: 0 }],
+            ..Default::default()
+        }
+    }
+
+    fn lod_triangles(builder: &ModelBuilder, draw: &DrawData) -> Vec<[u32; 3]> {
+        let start = draw.start_index as usize;
+        builder.indices[start..start + draw.num_tris as usize * 3].chunks_exact(3)
+            .map(|tri| [tri[0], tri[1], tri[2]])
+            .collect()
+    }
+
+    #[test]
+    fn flat_grid_simplifies_to_its_outline() {
+        let mut builder = grid(8, 0.0f32, None);
+        let lods = builder.generate_lods(&[LodTarget::Ratio(0.5f32), LodTarget::Error(0.0f32)]);
+        assert_eq!(lods.len(), 2);
+        assert!(lods[0].get_triangle_count() <= 64);
+        assert!(lods[1].get_triangle_count() <= 4, "{}", lods[1].get_triangle_count());
+        assert!(lods[1].error < 1e-3f32);
+
+        // the corners of the square stay put and no triangle flips
+        let lod = builder.lods[1].clone();
+        let tris = lod_triangles(&builder, &lod.draws[0]);
+        let points: Vec<Point3F> = tris.iter().flatten().map(|&v| builder.verts[v as usize].pos)
+            .collect();
+        assert_eq!(Aabb::from_points(&points).unwrap(),
+            Aabb::new(Point3F::origin(), Point3F::new(8.0f32, 8.0f32, 0.0f32)));
+        for tri in tris {
+            let [a, b, c] = tri.map(|v| builder.verts[v as usize].pos);
+            assert!((c - a).cross(&(b - a)).z > 0.0f32);
+        }
+
+        // generating again replaces the chain
+        builder.generate_lods(&[LodTarget::Ratio(0.5f32)]);
+        assert_eq!(builder.get_lods().len(), 1);
+        assert_eq!(builder.indices.len(), 8 * 8 * 6 + builder.lods[0].get_triangle_count() * 3);
+    }
+
+    #[test]
+    fn error_target_limits_curved_surfaces() {
+        let mut builder = grid(12, 2.0f32, None);
+        let full = builder.draws[0].num_tris as usize;
+        let lods = builder.generate_lods(&[LodTarget::Error(0.0f32), LodTarget::Error(0.05f32)]);
+
+        // curved parts can't be simplified without error, but the straight rows along y can
+        assert!(lods[0].get_triangle_count() < full);
+        assert!(lods[0].error < 1e-3f32);
+        assert!(lods[1].get_triangle_count() < lods[0].get_triangle_count());
+        let radius = 6.0f32 * 2.0f32.sqrt();
+        assert!(lods[1].error <= 0.05f32 * radius * 1.01f32, "{}", lods[1].error);
+    }
+
+    #[test]
+    fn keeps_seams_and_material_boundaries() {
+        let mut builder = grid(8, 0.0f32, Some(4));
+        let lods = builder.generate_lods(&[LodTarget::Ratio(0.05f32)]).to_vec();
+        let right_side = |v: u32| v as usize >= 9 * 9 || builder.verts[v as usize].pos.x > 4.0f32;
+        let tris = lod_triangles(&builder, &lods[0].draws[0]);
+        assert!(tris.len() < 32);
+        // no triangle mixes the vertices on either side of the seam
+        for tri in &tris {
+            let sides: Vec<bool> = tri.iter().map(|&v| right_side(v)).collect();
+            assert!(sides.iter().all(|&s| s == sides[0]), "{:?}", tri);
+        }
+
+        // split the grid into a draw per half, and the positions where they meet stay put
+        let mut builder = grid(8, 0.0f32, None);
+        builder.draws = vec![DrawData { start_index: 0, num_tris: 64, material_idx: 0 },
+            DrawData { start_index: 64 * 3, num_tris: 64, material_idx: 1 }];
+        let lods = builder.generate_lods(&[LodTarget::Ratio(0.01f32)]).to_vec();
+        for (draw, lod_draw) in lods[0].draws.iter().enumerate() {
+            assert_eq!(lod_draw.material_idx, draw as u32);
+            let used: HashSet<u32> = lod_triangles(&builder, lod_draw).into_iter().flatten()
+                .collect();
+            for x in 0..=8 {
+                assert!(used.contains(&(4 * 9 + x)), "draw {} lost ({}, 4)", draw, x);
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_faceted_meshes() {
+        // give every triangle its own vertices, with normals that differ a little
+        let mut builder = grid(8, 0.0f32, None);
+        let corners: Vec<u32> = builder.indices.clone();
+        builder.verts = corners.iter().enumerate()
+            .map(|(idx, &v)| MeshVertex {
+                norm: Vector3F::new((idx / 3) as f32 * 0.01f32, 0.0f32, -1.0f32).normalize(),
+                ..builder.verts[v as usize]
+            })
+            .collect();
+        builder.indices = (0..corners.len() as u32).collect();
+
+        let lods = builder.generate_lods(&[LodTarget::Error(0.0f32)]);
+        assert!(lods[0].get_triangle_count() <= 4, "{}", lods[0].get_triangle_count());
+    }
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!("0.25".parse(), Ok(LodTarget::Ratio(0.25f32)));
+        assert_eq!("ratio:1".parse(), Ok(LodTarget::Ratio(1.0f32)));
+        assert_eq!("error:0.01".parse(), Ok(LodTarget::Error(0.01f32)));
+        assert!("0".parse::<LodTarget>().is_err());
+        assert!("error:-1".parse::<LodTarget>().is_err());
+        assert!("half".parse::<LodTarget>().is_err());
+    }
+}
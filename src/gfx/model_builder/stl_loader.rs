@@ -0,0 +1,252 @@
+// external refs
+use std::fs;
+use std::path::Path;
+
+// local refs
+use crate::error::Error;
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Size of the header of binary files, before the triangle count
+///
+const BINARY_HEADER_SIZE: usize = 80;
+
+///
+/// Size of each triangle in binary files: the normal, three corners and a 16 bit attribute
+///
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+///
+/// Checks whether the file is binary. Some exporters start binary headers with `solid` as well,
+/// so the size has to match the triangle count exactly.
+///
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() < BINARY_HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == BINARY_HEADER_SIZE + 4 + count * BINARY_TRIANGLE_SIZE
+}
+
+///
+/// Expands a 5 bit color channel to 8 bits
+///
+fn color_channel(bits: u16) -> u8 {
+    let bits = (bits & 0x1f) as u8;
+    (bits << 3) | (bits >> 2)
+}
+
+///
+/// Gets the color of a triangle in a binary file from its attribute. Materialise files have a
+/// `COLOR=` default in the header and clear bit 15 on triangles with their own RGB color.
+/// Otherwise bit 15 is set on triangles with a BGR color, as VisCAM and SolidView write them.
+///
+fn triangle_color(attribute: u16, default: Option<Color4>) -> Color4 {
+    let low = color_channel(attribute);
+    let mid = color_channel(attribute >> 5);
+    let high = color_channel(attribute >> 10);
+    match default {
+        Some(_) if attribute & 0x8000 == 0 => Color4::from_rgb(low, mid, high),
+        Some(default) => default,
+        None if attribute & 0x8000 != 0 => Color4::from_rgb(high, mid, low),
+        None => Color4::white()
+    }
+}
+
+///
+/// Reads the triangles of a binary file, giving each corner its triangle's color
+///
+fn read_binary(data: &[u8]) -> Vec<MeshVertex> {
+    let header = &data[..BINARY_HEADER_SIZE];
+    let default = header.windows(10).find(|w| w.starts_with(b"COLOR="))
+        .map(|w| Color4::from_rgba(w[6], w[7], w[8], w[9]));
+
+    let read_f32 = |b: &[u8]| f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+    data[BINARY_HEADER_SIZE + 4..].chunks_exact(BINARY_TRIANGLE_SIZE).flat_map(|tri| {
+        let color = triangle_color(u16::from_le_bytes([tri[48], tri[49]]), default);
+        // the stored facet normal is skipped, as exporters often leave it zero
+        (0..3).map(move |corner| {
+            let p = &tri[12 + corner * 12..];
+            MeshVertex {
+                pos: Point3F::new(read_f32(p), read_f32(&p[4..]), read_f32(&p[8..])),
+                color,
+                ..Default::default()
+            }
+        })
+    }).collect()
+}
+
+///
+/// Reads the solids of an ASCII file
+///
+/// # Returns
+/// The name and corners of each solid
+///
+fn read_ascii(path: &Path, data: &[u8]) -> Result<Vec<(String, Vec<MeshVertex>)>, Error> {
+    let text = String::from_utf8_lossy(data);
+    let mut solids = Vec::new();
+    let mut current: Option<(String, Vec<MeshVertex>)> = None;
+
+    for (idx, line) in text.lines().enumerate() {
+        let error = |message: &str| Error::parse(path, Some(idx + 1), message);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("solid") => {
+                if current.is_some() {
+                    return Err(error("solid inside another solid"));
+                }
+                current = Some((words.collect::<Vec<_>>().join(" "), Vec::new()));
+            }
+            Some("endsolid") => {
+                solids.push(current.take().ok_or_else(|| error("endsolid without a solid"))?);
+            }
+            Some("vertex") => {
+                let (_, verts) = current.as_mut().ok_or_else(|| error("vertex outside a solid"))?;
+                let coords = words.map(|w| w.parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|_| error("vertex coordinate isn't a number"))?;
+                if coords.len() != 3 {
+                    return Err(error("vertex needs three coordinates"));
+                }
+                verts.push(MeshVertex {
+                    pos: Point3F::new(coords[0], coords[1], coords[2]),
+                    ..Default::default()
+                });
+            }
+            Some("endloop") => {
+                let (_, verts) = current.as_ref().ok_or_else(|| error("endloop outside a solid"))?;
+                if verts.len() % 3 != 0 {
+                    return Err(error("facets must have three vertices"));
+                }
+            }
+            // facet normals are recomputed, and the other keywords only group the vertices
+            _ => {}
+        }
+    }
+
+    if current.is_some() {
+        return Err(Error::parse(path, None, "file ends inside a solid"));
+    }
+    Ok(solids)
+}
+
+impl ModelBuilder {
+    ///
+    /// Loads a model from an STL file, in ASCII or binary, using the default resolver to find the
+    /// object shader
+    ///
+    pub fn load_from_stl(path: &Path) -> Result<Self, Error> {
+        Self::load_from_stl_with_resolver(path, &AssetResolver::default())
+    }
+
+    ///
+    /// Loads a model from an STL file. Each solid of an ASCII file becomes a part, and the
+    /// triangle colors of binary files become vertex colors. Normals are always generated.
+    ///
+    pub fn load_from_stl_with_resolver(path: &Path, resolver: &AssetResolver)
+        -> Result<Self, Error> {
+
+        let data = fs::read(path).map_err(|e| Error::io(path, e))?;
+        let stem = path.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+        let solids = if is_binary(&data) {
+            vec![(stem, read_binary(&data))]
+        } else if data.starts_with(b"solid") {
+            read_ascii(path, &data)?
+        } else {
+            return Err(Error::parse(path, None, "neither an ASCII nor a binary STL file"));
+        };
+
+        if solids.iter().all(|(_, verts)| verts.is_empty()) {
+            return Err(Error::UnsupportedFormat { what: "STL file without facets".to_string(),
+                path: Some(path.to_path_buf()) });
+        }
+
+        let mut builder = Self::with_default_material(path, resolver);
+        for (idx, (name, verts)) in solids.into_iter().enumerate() {
+            let name = if name.is_empty() { format!("solid {}", idx) } else { name };
+            let tris: Vec<[u32; 3]> = (0..verts.len() as u32 / 3)
+                .map(|t| [t * 3, t * 3 + 1, t * 3 + 2])
+                .collect();
+            builder.add_mesh(name, verts, &tris);
+        }
+        builder.generate_normals(NormalGeneration::default());
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_files::*;
+
+    ///
+    /// Writes a binary file holding a single triangle facing +z, with the specified header and
+    /// attribute
+    ///
+    fn binary_triangle(header: &[u8], attribute: u16) -> Vec<u8> {
+        let mut data = header.to_vec();
+        data.resize(BINARY_HEADER_SIZE, b' ');
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for c in [0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter() {
+            data.extend_from_slice(&c.to_le_bytes());
+        }
+        data.extend_from_slice(&attribute.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn ascii_solids_become_parts() {
+        let text = "solid base\n\
+            facet normal 0 0 1\n outer loop\n\
+              vertex 0 0 0\n  vertex 1 0 0\n  vertex 0 1 0\n endloop\nendfacet\n\
+            endsolid base\n\
+            solid\n\
+            facet normal 0 0 0\n outer loop\n\
+              vertex 0 0 1\n  vertex 0 1 1\n  vertex 1 0 1\n endloop\nendfacet\n\
+            endsolid\n";
+        let path = write_scratch_file("ascii.stl", text);
+        let builder = ModelBuilder::load_from_stl(&path);
+        fs::remove_file(&path).unwrap();
+        let builder = builder.unwrap();
+
+        let names: Vec<&str> = builder.nodes.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["base", "solid 1"]);
+        assert_eq!(builder.draws.len(), 2);
+        assert_eq!(builder.verts[builder.indices[0] as usize].norm, Vector3F::z());
+        assert_eq!(builder.verts[builder.indices[3] as usize].norm, -Vector3F::z());
+    }
+
+    #[test]
+    fn binary_with_solid_header_and_colors() {
+        // VisCAM colors are BGR with bit 15 set
+        let path = write_scratch_file("viscam.stl",
+            binary_triangle(b"solid exported as binary", 0x8000 | 0x1f));
+        let builder = ModelBuilder::load_from_stl(&path);
+        fs::remove_file(&path).unwrap();
+        let builder = builder.unwrap();
+        assert_eq!(builder.indices.len(), 3);
+        assert!(builder.verts.iter().all(|v| v.color == Color4::from_rgb(0, 0, 255)));
+        assert!(builder.verts.iter().all(|v| v.norm == Vector3F::z()));
+
+        // Materialise colors are RGB with bit 15 clear, and fall back to the header's color
+        let own = triangle_color(0x1f, Some(Color4::white()));
+        assert_eq!(own, Color4::from_rgb(255, 0, 0));
+        let mut header = b"COLOR=".to_vec();
+        header.extend_from_slice(&[10, 20, 30, 255]);
+        let path = write_scratch_file("materialise.stl", binary_triangle(&header, 0x8000));
+        let builder = ModelBuilder::load_from_stl(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(builder.unwrap().verts[0].color, Color4::from_rgba(10, 20, 30, 255));
+    }
+
+    #[test]
+    fn rejects_incomplete_facets() {
+        let text = "solid x\nfacet\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\nendfacet\n\
+            endsolid\n";
+        let path = write_scratch_file("incomplete.stl", text);
+        let result = ModelBuilder::load_from_stl(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(Error::Parse { line: Some(6), .. })));
+    }
+}
@@ -0,0 +1,192 @@
+// external refs
+use bevy_mikktspace as mikktspace;
+
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+use super::VertexVariants;
+
+///
+/// Corners of a stored triangle in the counter-clockwise order that normal map bakers use
+///
+const CCW_CORNERS: [usize; 3] = [0, 2, 1];
+
+///
+/// Triangles of one draw as seen by MikkTSpace, collecting the tangent of every corner
+///
+struct DrawGeometry<'a> {
+    verts: &'a [MeshVertex],
+    tris: &'a [u32],
+    tangents: Vec<Vector4F>
+}
+
+impl DrawGeometry<'_> {
+    ///
+    /// Gets the vertex at a corner of a face
+    ///
+    fn vertex(&self, face: usize, vert: usize) -> &MeshVertex {
+        &self.verts[self.tris[face * 3 + CCW_CORNERS[vert]] as usize]
+    }
+}
+
+impl mikktspace::Geometry for DrawGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.tris.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let p = self.vertex(face, vert).pos;
+        [p.x, p.y, p.z]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let n = self.vertex(face, vert).norm;
+        [n.x, n.y, n.z]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let uv = self.vertex(face, vert).uv;
+        [uv.x, uv.y]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + CCW_CORNERS[vert]] = Vector4F::from_column_slice(&tangent);
+    }
+}
+
+impl ModelBuilder {
+    ///
+    /// Generates MikkTSpace tangents for every vertex from its position, normal and texture
+    /// coordinates, matching the tangent space normal maps are baked in by most tools. Vertices
+    /// are duplicated where the triangles using them need different tangents, such as along
+    /// mirrored texture seams.
+    ///
+    pub fn generate_tangents(&mut self) {
+        let draws: Vec<usize> = (0..self.draws.len()).collect();
+        self.generate_tangents_for_draws(&draws);
+    }
+
+    ///
+    /// Generates tangents for the vertices used by the specified draws
+    ///
+    pub(super) fn generate_tangents_for_draws(&mut self, draws: &[usize]) {
+        // corners whose tangents are this close share a vertex
+        const SAME_TANGENT_COS: f32 = 0.9999f32;
+
+        let mut variants = VertexVariants::new();
+        for &draw_idx in draws {
+            let draw = self.draws[draw_idx];
+            let start = draw.start_index as usize;
+            let end = start + draw.num_tris as usize * 3;
+
+            let mut geometry = DrawGeometry {
+                verts: &self.verts,
+                tris: &self.indices[start..end],
+                tangents: vec![Vector4F::zeros(); end - start]
+            };
+            if !mikktspace::generate_tangents(&mut geometry) {
+                continue;
+            }
+
+            for (corner, tangent) in (start..end).zip(geometry.tangents) {
+                self.indices[corner] = self.vertex_variant(&mut variants, self.indices[corner],
+                    |v| v.tangent.w == tangent.w
+                        && v.tangent.xyz().dot(&tangent.xyz()) >= SAME_TANGENT_COS,
+                    |v| v.tangent = tangent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: &Vector4F, b: &Vector4F) -> bool {
+        (a - b).norm() < 1e-5f32
+    }
+
+    fn all_tangents_near(builder: &ModelBuilder, x: f32, y: f32, w: f32) -> bool {
+        builder.verts.iter().all(|v| near(&v.tangent, &Vector4F::new(x, y, 0.0f32, w)))
+    }
+
+    ///
+    /// Builds a model from quads in the z = 0 plane facing +z, each given as its corners'
+    /// positions and texture coordinates in counter-clockwise order
+    ///
+    fn quads(quads: &[[(f32, f32, f32, f32); 4]]) -> ModelBuilder {
+        let mut builder = ModelBuilder::default();
+
+        // corners at the same position and texture coordinate share a vertex
+        for quad in quads {
+            let mut corners = [0u32; 4];
+            for (corner, &(x, y, u, v)) in corners.iter_mut().zip(quad.iter()) {
+                let pos = Point3F::new(x, y, 0.0f32);
+                let uv = Vector2F::new(u, v);
+                *corner = match builder.verts.iter().position(|v| v.pos == pos && v.uv == uv) {
+                    Some(idx) => idx as u32,
+                    None => {
+                        builder.verts.push(MeshVertex { pos, uv, norm: Vector3F::z(),
+                            ..Default::default() });
+                        (builder.verts.len() - 1) as u32
+                    }
+                };
+            }
+            let c = corners;
+            builder.indices.extend_from_slice(&[c[0], c[2], c[1], c[0], c[3], c[2]]);
+        }
+        builder.draws.push(DrawData { start_index: 0, num_tris: quads.len() as u32 * 2,
+            material_idx: 0 });
+        builder
+    }
+
+    #[test]
+    fn tangents_follow_the_u_direction() {
+        let mut builder = quads(&[[(0.0, 0.0, 0.0, 0.0), (1.0, 0.0, 1.0, 0.0), (1.0, 1.0, 1.0, 1.0),
+            (0.0, 1.0, 0.0, 1.0)]]);
+        builder.generate_tangents();
+        assert_eq!(builder.verts.len(), 4);
+        assert!(all_tangents_near(&builder, 1.0f32, 0.0f32, 1.0f32));
+
+        // with u running up the quad the tangent does too
+        let mut builder = quads(&[[(0.0, 0.0, 0.0, 1.0), (1.0, 0.0, 0.0, 0.0), (1.0, 1.0, 1.0, 0.0),
+            (0.0, 1.0, 1.0, 1.0)]]);
+        builder.generate_tangents();
+        assert!(all_tangents_near(&builder, 0.0f32, 1.0f32, 1.0f32));
+    }
+
+    #[test]
+    fn flipped_v_reverses_handedness() {
+        let mut builder = quads(&[[(0.0, 0.0, 0.0, 1.0), (1.0, 0.0, 1.0, 1.0), (1.0, 1.0, 1.0, 0.0),
+            (0.0, 1.0, 0.0, 0.0)]]);
+        builder.generate_tangents();
+        assert!(all_tangents_near(&builder, 1.0f32, 0.0f32, -1.0f32));
+    }
+
+    #[test]
+    fn mirrored_seams_split_vertices() {
+        // the right quad's texture is mirrored, so the vertices on the shared edge need a tangent
+        // pointing each way
+        let mut builder = quads(&[
+            [(0.0, 0.0, 0.0, 0.0), (1.0, 0.0, 1.0, 0.0), (1.0, 1.0, 1.0, 1.0), (0.0, 1.0, 0.0, 1.0)],
+            [(1.0, 0.0, 1.0, 0.0), (2.0, 0.0, 0.0, 0.0), (2.0, 1.0, 0.0, 1.0), (1.0, 1.0, 1.0, 1.0)]
+        ]);
+        assert_eq!(builder.verts.len(), 6);
+        builder.generate_tangents();
+        assert_eq!(builder.verts.len(), 8);
+
+        for tri in builder.indices.chunks(3) {
+            let centre_x = tri.iter().map(|&i| builder.verts[i as usize].pos.x).sum::<f32>() / 3.0;
+            let expected = if centre_x < 1.0 {
+                Vector4F::new(1.0, 0.0, 0.0, 1.0)
+            } else {
+                Vector4F::new(-1.0, 0.0, 0.0, -1.0)
+            };
+            assert!(tri.iter().all(|&i| near(&builder.verts[i as usize].tangent, &expected)));
+        }
+    }
+}
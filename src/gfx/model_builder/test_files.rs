@@ -0,0 +1,34 @@
+// external refs
+use std::fs;
+use std::path::{Path, PathBuf};
+
+///
+/// Gets a path in the temporary directory that's unique to the test run
+///
+pub fn scratch_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("modelviewer-{}-{}", std::process::id(), name))
+}
+
+///
+/// Writes the data to a file in the temporary directory
+///
+pub fn write_scratch_file(name: &str, data: impl AsRef<[u8]>) -> PathBuf {
+    let path = scratch_path(name);
+    fs::write(&path, data).unwrap();
+    path
+}
+
+///
+/// Copies a test asset into a fresh directory in the temporary directory, away from the files next
+/// to it and where it can be changed
+///
+/// # Returns
+/// The directory and the copy
+///
+pub fn scratch_copy(dir_name: &str, asset: &str) -> (PathBuf, PathBuf) {
+    let dir = scratch_path(dir_name);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(asset);
+    fs::copy(Path::new("tests").join("assets").join(asset), &path).unwrap();
+    (dir, path)
+}
@@ -1,6 +1,7 @@
 // external refs
 
 // local refs
+use crate::error::Error;
 use crate::gfx::*;
 use crate::numerics::*;
 
@@ -8,7 +9,8 @@ use crate::numerics::*;
 /// Handles binding render targets to the graphics pipeline
 ///
 pub struct RenderTargetState {
-    rt: RenderTarget,
+    // only empty while the targets are being recreated
+    rt: Option<RenderTarget>,
     ds: Option<DepthStencilTarget>,
     clear_color: Color4F,
     clear_depth: f32,
@@ -17,14 +19,14 @@ pub struct RenderTargetState {
 
 impl RenderTargetState {
     pub const CLEAR_COLOR: u32  = 1 << 0;
-    pub const CLEAR_Z: u32  = 1 << 0;
+    pub const CLEAR_Z: u32  = 1 << 1;
 
     ///
     /// Creates a new render target state with default settings
     ///
     pub fn new(rt: RenderTarget, ds: Option<DepthStencilTarget>) -> Self {
         Self {
-            rt,
+            rt: Some(rt),
             ds,
             clear_color: Color4F::transparent_black(),
             clear_depth: 0.0f32,
@@ -62,21 +64,65 @@ impl RenderTargetState {
         self.flags &= !Self::CLEAR_Z;
     }
 
+    ///
+    /// Gets the render target this state renders to
+    ///
+    pub fn get_render_target(&self) -> &RenderTarget {
+        self.rt.as_ref().expect("Render target state is missing its render target")
+    }
+
+    ///
+    /// Recreates the targets after the display they render to has been resized. The display is
+    /// resized here since its buffers can't change while the old render target exists.
+    ///
+    pub fn resize_with_display(&mut self, gfx: &Graphics, display: &mut Display, width: u32,
+        height: u32) -> Result<(), Error> {
+
+        self.rt = None;
+        let resized = gfx.resize_display(display, width, height);
+
+        // recreate the render target even if the resize failed so the state stays usable
+        self.rt = Some(gfx.create_render_target(display)?);
+        resized?;
+        self.resize_depth(gfx, width, height)
+    }
+
+    ///
+    /// Replaces the targets with offscreen ones of the specified size
+    ///
+    pub fn resize_offscreen(&mut self, gfx: &Graphics, width: u32, height: u32)
+        -> Result<(), Error> {
+
+        self.rt = Some(gfx.create_offscreen_render_target(width, height)?);
+        self.resize_depth(gfx, width, height)
+    }
+
+    ///
+    /// Recreates the depth buffer, if there is one, at the specified size
+    ///
+    fn resize_depth(&mut self, gfx: &Graphics, width: u32, height: u32) -> Result<(), Error> {
+        if let Some(ds) = &self.ds {
+            self.ds = Some(gfx.create_depth_stencil_target(ds.format, width, height)?);
+        }
+        Ok(())
+    }
+
     ///
     /// Begins rendering to the render target state
     ///
     pub fn begin(&self, gfx: &Graphics) {
+        let rt = self.get_render_target();
         if (self.flags & Self::CLEAR_COLOR) != 0  {
-            gfx.clear_render_target(&self.rt, &self.clear_color);
+            gfx.clear_render_target(rt, &self.clear_color);
         }
         if let Some(ds) = &self.ds {
             if (self.flags & Self::CLEAR_Z) != 0 {
-                gfx.clear_depth_stencil_target(&ds, true, self.clear_depth, false, 0);
+                gfx.clear_depth_stencil_target(ds, true, self.clear_depth, false, 0);
             }
-            gfx.set_render_target_and_depth(&self.rt, &ds);
+            gfx.set_render_target_and_depth(rt, ds);
         }
         else {
-            gfx.set_render_target(&self.rt);
+            gfx.set_render_target(rt);
         }
     }
 
@@ -86,4 +132,34 @@ impl RenderTargetState {
     pub fn end(&self, gfx: &Graphics) {
         gfx.unbind_render_targets();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resizing_with_display_recreates_targets() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let mut display = gfx.create_display(0, 32, 16).unwrap();
+        let rt = gfx.create_render_target(&display).unwrap();
+        let ds = gfx.create_depth_stencil_target(DepthStencilFormat::D32Float, 32, 16).unwrap();
+        let mut rt_state = RenderTargetState::new(rt, Some(ds));
+
+        // the display can't be resized while a render target holds on to its buffers
+        assert!(gfx.resize_display(&mut display, 64, 48).is_err());
+
+        rt_state.resize_with_display(&gfx, &mut display, 64, 48).unwrap();
+        assert_eq!((display.width, display.height), (64, 48));
+        let rt = rt_state.get_render_target();
+        assert_eq!((rt.width, rt.height), (64, 48));
+        let ds = rt_state.ds.as_ref().unwrap();
+        assert_eq!((ds.width, ds.height, ds.format), (64, 48, DepthStencilFormat::D32Float));
+
+        rt_state.enable_clear_color(Color4F::white());
+        rt_state.begin(&gfx);
+        rt_state.end(&gfx);
+        let pixels = gfx.read_render_target(rt_state.get_render_target()).unwrap();
+        assert_eq!(pixels.len(), 64 * 48);
+    }
+}
@@ -1,10 +1,10 @@
-// external refs
-use winapi::um::d3d11;
+// local refs
+use crate::gfx::ResourceHandle;
 
 ///
 /// The type of the shader
 ///
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Pixel
@@ -13,7 +13,7 @@ pub enum ShaderType {
 ///
 /// Shader struct
 ///
-pub enum Shader {
-    Vertex(*mut d3d11::ID3D11VertexShader),
-    Pixel(*mut d3d11::ID3D11PixelShader)
-}
\ No newline at end of file
+pub struct Shader {
+    pub shader_type: ShaderType,
+    pub handle: ResourceHandle
+}
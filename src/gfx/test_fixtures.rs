@@ -0,0 +1,59 @@
+// local refs
+use crate::gfx::*;
+use crate::numerics::*;
+
+///
+/// Width and height of the render target test draws go to
+///
+pub const SIZE: u32 = 16;
+
+///
+/// Creates a vertex facing the camera at the specified clip space position
+///
+pub fn vert(x: f32, y: f32, z: f32, color: Color4) -> MeshVertex {
+    MeshVertex {
+        pos: Point3F::new(x, y, z),
+        norm: -Vector3F::z(),
+        color,
+        ..Default::default()
+    }
+}
+
+///
+/// Creates software graphics with a cleared render target state, and constants for object.hlsl
+/// that pass positions through untransformed and light everything with full ambient so pixels
+/// come out as the vertex color
+///
+pub fn setup() -> (Graphics, RenderTargetState, ShaderBuffer) {
+    let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+    let rt = gfx.create_offscreen_render_target(SIZE, SIZE).unwrap();
+    let ds = gfx.create_depth_stencil_target(DepthStencilFormat::D32Float, SIZE, SIZE).unwrap();
+    let mut rt_state = RenderTargetState::new(rt, Some(ds));
+    rt_state.enable_clear_color(Color4F::black());
+    rt_state.enable_clear_depth(1.0f32);
+
+    let mut constants = [0.0f32; 48];
+    constants[0..16].copy_from_slice(Matrix4F::identity().as_slice());
+    constants[16..32].copy_from_slice(Matrix4F::identity().as_slice());
+    constants[36..40].copy_from_slice(&[1.0f32; 4]);
+    let cbuff = gfx.create_constant_buffer(std::mem::size_of_val(&constants)).unwrap();
+    gfx.map_and_set_buffer_data(&cbuff, &constants);
+    gfx.set_vs_constant_buffer(0, &cbuff);
+    gfx.set_ps_constant_buffer(0, &cbuff);
+    gfx.set_viewport(0.0f32, 0.0f32, SIZE as f32, SIZE as f32, 0.0f32, 1.0f32);
+
+    (gfx, rt_state, cbuff)
+}
+
+///
+/// Loads object.hlsl with default parameters and a white albedo
+///
+pub fn object_material(gfx: &Graphics) -> Material {
+    Material::load(gfx, &MaterialInfo {
+        shader_file: ["data", "shaders", "object.hlsl"].iter().collect(),
+        vert_format: MeshVertex::get_format(),
+        topology: PrimitiveTopology::TriangleList,
+        textures: Default::default(),
+        params: MaterialParams::default()
+    }).unwrap()
+}
@@ -6,7 +6,7 @@ use crate::numerics::*;
 ///
 pub const MAX_INPUTS: usize = 8;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum FormatType {
     Invalid = -1,
 
@@ -44,7 +44,7 @@ pub enum FormatType {
     NumFormatTypes
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SemanticType {
     Invalid = -1,
 
@@ -128,6 +128,7 @@ impl VertexFormat {
     ///
     /// Builder method to add a vertex buffer element
     ///
+    #[allow(clippy::too_many_arguments)]
     pub fn add_new_element(self, format: FormatType, size_bytes: u32, semantic: SemanticType,
         semantic_index: u32, input_slot: u32, slot_class: InputClass, instance_step_rate: u32)
         -> VertexFormat {
@@ -164,6 +165,12 @@ impl VertexFormat {
     }
 }
 
+impl Default for VertexFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 ///
 /// Common vertex format containing position + color
 ///
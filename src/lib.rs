@@ -0,0 +1,4 @@
+pub mod app;
+pub mod error;
+pub mod gfx;
+pub mod numerics;
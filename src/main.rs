@@ -1,9 +1,23 @@
-mod app;
-mod gfx;
-mod numerics;
+// external refs
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
 
-use crate::gfx::*;
-use crate::numerics::*;
+// local refs
+use modelviewer::app;
+use modelviewer::app::{FrameStats, InputState, Key, MouseButton, UpdateMode, WindowBackendType};
+use modelviewer::error::Error;
+use modelviewer::{gfx::*, numerics::*};
+
+///
+/// Simulated time between headless frames when no update rate is given
+///
+const FRAME_TIME: f32 = 1.0f32 / 60.0f32;
+
+///
+/// Most fixed updates run in a single frame before the viewer gives up catching up
+///
+const MAX_CATCH_UP_STEPS: u32 = 8;
 
 ///
 /// Data used by this sample
@@ -13,42 +27,69 @@ struct ModelViewer {
     model: Model,
     cbuff: ShaderBuffer,
     sampler: Sampler,
+    camera: Camera,
+    spin: bool,
     time: f32
 }
 
+#[repr(C)]
 #[derive(Copy, Clone)]
 struct BuffData {
     world: Matrix4F,
     view_proj: Matrix4F,
     camera_pos: Vector3F,
     specular_amount : f32,
-    material_color: Color4F,
-    ambient_color: Color4F,
-    specular_color: Color3F,
-    specular_power: f32,
+    ambient_light: Color4F,
     sun_color: Color4F,
     sun_dir: Vector3F
 }
 
+// every field is made of f32s, so there's no padding
+unsafe impl Pod for BuffData {}
+
 impl ModelViewer {
     ///
-    /// Creates and initializes a new ModelViewer object
+    /// Creates and initializes a new ModelViewer object that renders to the specified target
     ///
-    pub fn new(app: &mut app::Application) -> Result<ModelViewer, ()> {
-        let (width, height) = app.window.get_window_size();
+    pub fn new(gfx: &Graphics, rt: RenderTarget, width: u32, height: u32, opts: &Options)
+        -> Result<ModelViewer, Error> {
 
         // initialize render target state
-        let rt = app.graphics.create_render_target(&app.display)?;
-        let ds = app.graphics.create_depth_stencil_target(DepthStencilFormat::D24UNormS8,
-            width, height)?;
+        let ds = gfx.create_depth_stencil_target(DepthStencilFormat::D24UNormS8, width, height)?;
         let mut rt_state = RenderTargetState::new(rt, Some(ds));
-        rt_state.enable_clear_color(Color4F::black());
+        rt_state.enable_clear_color(opts.background);
         rt_state.enable_clear_depth(1.0f32);
 
-        let objpath = std::path::Path::new("data\\objects\\test2.obj");
-        let model = ModelBuilder::load_from_obj(objpath)?.build(&app.graphics)?;
+        let mut model = load_model(gfx, opts)?;
+        for e in model.get_texture_errors() {
+            eprintln!("Warning: skipping texture. {}", e);
+        }
 
-        let cbuff = app.graphics.create_constant_buffer(std::mem::size_of::<BuffData>())?;
+        let mut camera = Camera::new_orbit(Point3F::origin(), deg_to_rad(opts.orbit.yaw),
+            deg_to_rad(opts.orbit.pitch), opts.orbit.distance);
+        camera.set_perspective(deg_to_rad(opts.fov), width as f32 / height as f32, opts.near,
+            opts.far);
+        if opts.frame {
+            if let Some(bounds) = model.get_bounds() {
+                frame_bounds(&mut camera, bounds);
+            }
+        }
+
+        let find_part = |model: &Model, name: &str| model.find_node(name).ok_or_else(|| {
+            let names: Vec<&str> = model.get_nodes().iter().map(|n| n.name.as_str()).collect();
+            Error::InvalidArgument(format!("the model has no part named {}, it has {}", name,
+                names.join(", ")))
+        });
+        if let Some(name) = &opts.isolate {
+            let part = find_part(&model, name)?;
+            model.isolate(part);
+        }
+        for name in &opts.hide {
+            let part = find_part(&model, name)?;
+            model.set_visible(part, false);
+        }
+
+        let cbuff = gfx.create_constant_buffer(std::mem::size_of::<BuffData>())?;
 
         let samp_data = SamplerData {
             mode: SampleMode::Linear,
@@ -59,77 +100,841 @@ impl ModelViewer {
             lod_bias: 0.0f32,
             max_anisotropy: 1,
             border_color: BorderColor::OpaqueWhite,
-            min_lod: -std::f32::MAX,
-            max_lod: std::f32::MAX
+            min_lod: -f32::MAX,
+            max_lod: f32::MAX
         };
-        let sampler = app.graphics.create_sampler(samp_data)?;
+        let sampler = gfx.create_sampler(samp_data)?;
 
-        Ok(Self { rt_state, model, cbuff, sampler, time: 0.0f32 })
+        Ok(Self { rt_state, model, cbuff, sampler, camera, spin: true, time: 0.0f32 })
     }
-}
 
-impl app::AppInterface for ModelViewer {
     ///
-    /// Does nothing. Just needed to implement AppInterface
+    /// Gets the render target the viewer draws into
     ///
-    fn update(&mut self, _app: &mut app::Application) {
-        self.time += 1.0f32 / 60.0f32; // yeah, gross and hard-coded
+    pub fn get_render_target(&self) -> &RenderTarget {
+        self.rt_state.get_render_target()
     }
 
     ///
-    /// Clears the render target for the frame
+    /// Advances the animation by the specified number of seconds
     ///
-    fn render(&mut self, app: &mut app::Application) {
-        // set up model and camera matrices
+    pub fn step(&mut self, dt: f32) {
+        if self.spin {
+            self.time += dt;
+        }
+    }
+
+    ///
+    /// Drives the camera from mouse, keyboard and gamepad input
+    ///
+    /// Left drag orbits (or looks around in fly mode), right or middle drag pans, the wheel zooms,
+    /// WASD/QE fly, F toggles fly mode, Home frames the model and Space toggles the spin.
+    ///
+    pub fn handle_input(&mut self, input: &InputState, view_height: f32, dt: f32) {
+        // sticks and triggers are scaled to roughly match dragging the mouse at a steady pace
+        const STICK_ROTATE_RATE: f32 = 400.0f32;
+        const TRIGGER_ZOOM_RATE: f32 = 10.0f32;
+
+        if input.was_key_pressed(Key::F) {
+            let mode = match self.camera.get_mode() {
+                CameraMode::Orbit => CameraMode::Fly,
+                CameraMode::Fly => CameraMode::Orbit
+            };
+            self.camera.set_mode(mode);
+        }
+        if input.was_key_pressed(Key::Space) {
+            self.spin = !self.spin;
+        }
+        if input.was_key_pressed(Key::Home) {
+            if let Some(bounds) = self.model.get_bounds() {
+                self.camera.set_mode(CameraMode::Orbit);
+                frame_bounds(&mut self.camera, bounds);
+            }
+        }
+
+        let axis = |pos: Key, neg: Key| {
+            input.is_key_down(pos) as i32 as f32 - input.is_key_down(neg) as i32 as f32
+        };
+        let delta = input.get_cursor_delta();
+        let pad = input.get_gamepad(0);
+
+        let mut cam_input = CameraInput {
+            zoom: input.get_wheel_delta(),
+            movement: Vector3F::new(axis(Key::D, Key::A), axis(Key::E, Key::Q),
+                axis(Key::W, Key::S)),
+            ..Default::default()
+        };
+        if input.is_mouse_down(MouseButton::Left) {
+            cam_input.rotate = delta;
+        }
+        if input.is_mouse_down(MouseButton::Right) || input.is_mouse_down(MouseButton::Middle) {
+            cam_input.pan = delta / view_height.max(1.0f32);
+        }
+        if pad.connected {
+            let look = Vector2F::new(pad.right_stick.x, -pad.right_stick.y);
+            cam_input.rotate += look * (STICK_ROTATE_RATE * dt);
+            cam_input.zoom += (pad.right_trigger - pad.left_trigger) * TRIGGER_ZOOM_RATE * dt;
+            cam_input.movement += Vector3F::new(pad.left_stick.x, 0.0f32, pad.left_stick.y);
+        }
+
+        self.camera.update(&cam_input, dt);
+    }
+
+    ///
+    /// Gets the spin of the model, which turns about the center of its bounds so it stays where the
+    /// camera frames it
+    ///
+    pub fn get_world_matrix(&self) -> Matrix4F {
         let rot_speed = deg_to_rad(45.0f32);
-        let world = Matrix4F::from_axis_angle(&Vector3F::y_axis(), rot_speed * self.time);
-        let cam_trans = Vector3F::new(1.0f32, 1.5f32, 5.0f32);
-        let cam_dir = -cam_trans.normalize();
-        let cam_rot = QuaternionF::rotation_between(&-Vector3::z_axis(), &cam_dir).unwrap();
-        let cam_xform = TransformF::from_parts(Translation3F::from(cam_trans), cam_rot, 1.0f32);
-        let view = Matrix4F::try_inverse(Matrix4F::from(cam_xform)).unwrap();
-        let proj = Matrix4F::new_perspective(16.0f32 / 9.0f32, deg_to_rad(45.0f32), 0.1f32, 5000.0f32);
-        let view_proj = proj * view;
-        let material_color = Color4F::from_rgba(0.3f32, 0.3f32, 0.3f32, 1.0f32);
-        let ambient_color = Color4F::from_rgba(0.0f32, 0.1f32, 0.2f32, 1.0f32);
-        let specular_color = Color3F::from_rgb(0.4f32, 0.4f32, 0.4f32);
-        let specular_power = 1.0f32;
+        let center = self.model.get_bounds()
+            .map_or_else(Vector3F::zeros, |bounds| bounds.sphere.center.coords);
+        Matrix4F::new_translation(&center)
+            * Matrix4F::from_axis_angle(&Vector3F::y_axis(), rot_speed * self.time)
+            * Matrix4F::new_translation(&-center)
+    }
+
+    ///
+    /// Draws the model into the render target
+    ///
+    pub fn draw(&self, gfx: &Graphics) {
+        // set up model and camera matrices
+        let world = self.get_world_matrix();
+        let view_proj = self.camera.get_view_projection_matrix();
+        let ambient_light = Color4F::from_rgba(0.0f32, 0.1f32, 0.2f32, 1.0f32);
         let specular_amount = 0.5f32;
         let sun_dir = Vector3F::new(1.0f32, 1.0f32, 1.0f32);
         let sun_color = Color4F::from_rgba(1.0f32, 1.0f32, 0.75f32, 1.0f32);
         let buffdata = BuffData {
-            world: world,
+            world,
             view_proj,
-            camera_pos: cam_trans,
+            camera_pos: self.camera.get_position().coords,
             specular_amount,
-            material_color,
-            ambient_color,
-            specular_color,
-            specular_power,
+            ambient_light,
             sun_color,
             sun_dir
         };
 
-        app.graphics.map_and_set_buffer_data(&self.cbuff, &buffdata);
-        app.graphics.set_vs_constant_buffer(0, &self.cbuff);
-        app.graphics.set_ps_constant_buffer(0, &self.cbuff);
-        app.graphics.set_ps_sampler(0, &self.sampler);
+        gfx.set_vs_constant_buffer(0, &self.cbuff);
+        gfx.set_ps_constant_buffer(0, &self.cbuff);
+        gfx.set_ps_sampler(0, &self.sampler);
+
+        self.rt_state.begin(gfx);
+        self.model.draw_culled(gfx, &world, &view_proj, |part| {
+            gfx.map_and_set_buffer_data(&self.cbuff, &BuffData { world: world * part, ..buffdata });
+        });
+        self.rt_state.end(gfx);
+    }
+}
+
+///
+/// Centers the camera on the model's bounding sphere and fits the clip planes to it, leaving room
+/// to zoom in close or back out a long way
+///
+fn frame_bounds(camera: &mut Camera, bounds: &Bounds) {
+    let radius = bounds.sphere.radius.max(camera.settings.min_distance);
+    camera.frame_sphere(bounds.sphere.center, radius);
+    camera.set_clip_planes(radius * 0.01f32, camera.get_distance() + radius * 100.0f32);
+}
+
+///
+/// Loads the model from its cache if it's up to date, otherwise from the model file, writing a
+/// new cache after processing it
+///
+fn load_model(gfx: &Graphics, opts: &Options) -> Result<Model, Error> {
+    let resolver = opts.get_resolver();
+    let cache_file = opts.cache_dir.as_ref()
+        .map(|dir| cache_file_name(dir, &opts.model, &opts.lods));
+
+    // a broken cache is just rebuilt
+    let cache = cache_file.as_ref().and_then(|file| match ModelCache::open(file, &resolver) {
+        Ok(Some(cache)) => {
+            eprintln!("Loaded processed model from {:?}", file);
+            Some(cache)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            eprintln!("Warning: ignoring model cache. {}", e);
+            None
+        }
+    });
+    if let Some(mut cache) = cache {
+        for asset in cache.get_unresolved_assets() {
+            eprintln!("Warning: {}", asset);
+        }
+        if let Some(shader) = &opts.shader {
+            cache = cache.with_shader(shader);
+        }
+        return cache.build(gfx);
+    }
+
+    let mut builder = ModelBuilder::load_with_resolver(&opts.model, &resolver)?;
+    for asset in builder.get_unresolved_assets() {
+        eprintln!("Warning: {}", asset);
+    }
+    eprintln!("Optimized mesh: {}", builder.optimize());
+    if !opts.lods.is_empty() {
+        for (idx, lod) in builder.generate_lods(&opts.lods).iter().enumerate() {
+            eprintln!("LOD {}: {} triangles, error {}", idx + 1, lod.get_triangle_count(),
+                lod.error);
+        }
+    }
+
+    // the cache is written before the shader is overridden so it holds the model's own materials
+    if let Some(file) = &cache_file {
+        if let Err(e) = builder.write_cache(file, &resolver) {
+            eprintln!("Warning: couldn't write model cache. {}", e);
+        }
+    }
+    if let Some(shader) = &opts.shader {
+        builder = builder.with_shader(shader);
+    }
+    builder.build(gfx)
+}
+
+///
+/// Gets where the cache of a model goes, named after the model file and its full path so models
+/// with the same name in different directories don't share a cache. The levels of detail are
+/// part of the name too, as the cache holds the ones it was written with.
+///
+fn cache_file_name(dir: &Path, model: &Path, lods: &[LodTarget]) -> PathBuf {
+    let full_path = std::fs::canonicalize(model).unwrap_or_else(|_| model.to_path_buf());
+    let lods = if lods.is_empty() { String::new() } else { format!("{:?}", lods) };
+    let path_hash = full_path.to_string_lossy().bytes().chain(lods.bytes())
+        .fold(0x811c_9dc5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x0100_0193));
+    let name = model.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    dir.join(format!("{}-{:08x}.mvc", name, path_hash))
+}
+
+impl app::AppInterface for ModelViewer {
+    ///
+    /// Moves the camera from the frame's input and advances the animation
+    ///
+    fn update(&mut self, app: &mut app::Application, dt: f32) {
+        let (_, height) = app.window.get_window_size();
+        self.handle_input(&app.input, height as f32, dt);
+        self.step(dt);
+    }
+
+    ///
+    /// Renders the model to the display
+    ///
+    fn render(&mut self, app: &mut app::Application) {
+        self.draw(&app.graphics);
+    }
+
+    ///
+    /// Recreates the render targets for the new window size and keeps the projection's aspect
+    ///
+    fn resize(&mut self, app: &mut app::Application, width: u32, height: u32)
+        -> Result<(), Error> {
+
+        self.rt_state.resize_with_display(&app.graphics, &mut app.display, width, height)?;
+        self.camera.set_aspect(width as f32 / height as f32);
+        Ok(())
+    }
+}
+
+///
+/// Settings parsed from the command line
+///
+#[derive(Debug)]
+struct Options {
+    help: bool,
+    /// Print a report on the model's mesh instead of displaying it
+    analyze: bool,
+    model: PathBuf,
+    shader: Option<PathBuf>,
+    search_paths: Vec<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    /// Simplified versions to generate, from the most to the least detailed
+    lods: Vec<LodTarget>,
+    export: Option<PathBuf>,
+    isolate: Option<String>,
+    hide: Vec<String>,
+    width: u32,
+    height: u32,
+    fov: f32,
+    near: f32,
+    far: f32,
+    background: Color4F,
+    orbit: Orbit,
+    frame: bool,
+    headless: bool,
+    frames: u32,
+    output: PathBuf,
+    update_rate: Option<f32>,
+    frame_stats: Option<PathBuf>
+}
+
+///
+/// Camera placement on a sphere around the origin
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Orbit {
+    yaw: f32,
+    pitch: f32,
+    distance: f32
+}
+
+impl Default for Orbit {
+    ///
+    /// Orbit that places the camera at (1, 1.5, 5)
+    ///
+    fn default() -> Self {
+        let pos = Vector3F::new(1.0f32, 1.5f32, 5.0f32);
+        let distance = pos.norm();
+        Self {
+            yaw: rad_to_deg(pos.x.atan2(pos.z)),
+            pitch: rad_to_deg((pos.y / distance).asin()),
+            distance
+        }
+    }
+}
+
+impl Options {
+    const USAGE: &'static str = "Usage: modelviewer [options]
+       modelviewer analyze [<model>] [--search-path <dir>]
+
+Commands:
+  analyze                    Print the vertex and triangle counts of the model and the problems
+                             found in its mesh, exiting with an error if there are any
+
+Options:
+  --model <file>             Model to display, .obj, .gltf, .glb, .ply or .stl
+                             (default: data/objects/test2.obj)
+  --shader <file.hlsl>       Shader to use for every material instead of object.hlsl
+  --search-path <dir>        Directory to look for textures and material libraries in when they
+                             aren't next to the model, can be repeated
+  --cache-dir <dir>          Directory for caches of processed models (default: the system's temporary
+                             directory)
+  --no-cache                 Always load the model from its file, without reading or writing a cache
+  --lods <targets>           Simplified versions to draw the model with when it's small on screen,
+                             as triangle ratios or error:<fraction of the model's radius>,
+                             separated by commas, e.g. 0.5,0.25,error:0.01
+  --export <file>            Write the processed model to an .obj, .gltf or .glb file and exit
+  --isolate <part>           Only show the named part of the model and the parts below it
+  --hide <part>              Hide the named part of the model, can be repeated
+  --width <pixels>           Window or image width (default: 1280)
+  --height <pixels>          Window or image height (default: 720)
+  --fov <degrees>            Vertical field of view (default: 45)
+  --near <distance>          Near clip plane distance (default: 0.1)
+  --far <distance>           Far clip plane distance (default: 5000)
+  --background <#rrggbb>     Background color in sRGB hex, with optional alpha (default: #000000)
+  --orbit <yaw,pitch,dist>   Initial camera orbit around the origin in degrees and units
+  --frame                    Start with the camera framing the whole model, with clip planes
+                             fitted to it
+  --headless                 Render offscreen and write an image instead of opening a window
+  --frames <count>           Number of frames to render in headless mode (default: 1)
+  --output <file.png|.ppm>   Image to write in headless mode, implies --headless
+  --fixed-step <hz>          Update at a fixed rate instead of once per frame with the frame time
+  --frame-stats <file.csv>   Write the frame times to a CSV file on exit
+  --help                     Show this message";
+
+    ///
+    /// Parses the options from the command line arguments (excluding the program name)
+    ///
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let mut opts = Self {
+            help: false,
+            analyze: args.next_if(|arg| arg == "analyze").is_some(),
+            model: ["data", "objects", "test2.obj"].iter().collect(),
+            shader: None,
+            search_paths: Vec::new(),
+            cache_dir: Some(std::env::temp_dir().join("modelviewer")),
+            lods: Vec::new(),
+            export: None,
+            isolate: None,
+            hide: Vec::new(),
+            width: 1280,
+            height: 720,
+            fov: 45.0f32,
+            near: 0.1f32,
+            far: 5000.0f32,
+            background: Color4F::black(),
+            orbit: Orbit::default(),
+            frame: false,
+            headless: false,
+            frames: 1,
+            output: PathBuf::from("modelviewer.png"),
+            update_rate: None,
+            frame_stats: None
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            let count = |v: String| match v.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(format!("Expected a positive whole number for {} but got {}", arg, v))
+            };
+            let number = |v: String| match v.parse::<f32>() {
+                Ok(n) if n.is_finite() => Ok(n),
+                _ => Err(format!("Expected a number for {} but got {}", arg, v))
+            };
+
+            match arg.as_str() {
+                "--help" | "-h" => opts.help = true,
+                "--model" => opts.model = PathBuf::from(value()?),
+                "--shader" => opts.shader = Some(PathBuf::from(value()?)),
+                "--search-path" => opts.search_paths.push(PathBuf::from(value()?)),
+                "--cache-dir" => opts.cache_dir = Some(PathBuf::from(value()?)),
+                "--no-cache" => opts.cache_dir = None,
+                "--lods" => {
+                    opts.lods = value()?.split(',')
+                        .map(|target| target.trim().parse::<LodTarget>()
+                            .map_err(|e| format!("{} for {}", e, arg)))
+                        .collect::<Result<_, String>>()?;
+                }
+                "--export" => opts.export = Some(PathBuf::from(value()?)),
+                "--isolate" => opts.isolate = Some(value()?),
+                "--hide" => opts.hide.push(value()?),
+                "--width" => opts.width = count(value()?)?,
+                "--height" => opts.height = count(value()?)?,
+                "--fov" => opts.fov = number(value()?)?,
+                "--near" => opts.near = number(value()?)?,
+                "--far" => opts.far = number(value()?)?,
+                "--background" => {
+                    let v = value()?;
+                    opts.background = parse_color(&v)
+                        .ok_or(format!("Expected a color like #3366cc for {} but got {}", arg, v))?;
+                }
+                "--orbit" => {
+                    let v = value()?;
+                    let parts = v.split(',')
+                        .map(|p| number(p.trim().to_string()))
+                        .collect::<Result<Vec<f32>, String>>()?;
+                    match parts[..] {
+                        [yaw, pitch, distance] => opts.orbit = Orbit { yaw, pitch, distance },
+                        _ => return Err(format!("Expected yaw,pitch,distance for {} but got {}",
+                            arg, v))
+                    }
+                }
+                "--frame" => opts.frame = true,
+                "--frames" => opts.frames = count(value()?)?,
+                "--headless" => opts.headless = true,
+                "--output" => {
+                    opts.output = PathBuf::from(value()?);
+                    opts.headless = true;
+                }
+                "--fixed-step" => {
+                    let v = value()?;
+                    match number(v.clone())? {
+                        hz if hz > 0.0f32 => opts.update_rate = Some(hz),
+                        _ => return Err(format!("Expected a positive rate for {} but got {}", arg,
+                            v))
+                    }
+                }
+                "--frame-stats" => opts.frame_stats = Some(PathBuf::from(value()?)),
+                _ if opts.analyze && !arg.starts_with('-') => opts.model = PathBuf::from(arg),
+                _ => return Err(format!("Unknown argument {}", arg))
+            }
+        }
+
+        if !opts.help {
+            opts.validate()?;
+        }
+
+        Ok(opts)
+    }
+
+    ///
+    /// Checks that the parsed options are usable together
+    ///
+    fn validate(&self) -> Result<(), String> {
+        if !self.model.is_file() {
+            return Err(format!("Model {:?} does not exist", self.model));
+        }
+        if let Some(shader) = &self.shader {
+            if !shader.is_file() {
+                return Err(format!("Shader {:?} does not exist", shader));
+            }
+        }
+        if let Some(dir) = self.search_paths.iter().find(|dir| !dir.is_dir()) {
+            return Err(format!("Search path {:?} is not a directory", dir));
+        }
+        if self.fov <= 0.0f32 || self.fov >= 180.0f32 {
+            return Err(format!("Field of view must be between 0 and 180 degrees, got {}", self.fov));
+        }
+        if self.near <= 0.0f32 {
+            return Err(format!("Near plane must be greater than 0, got {}", self.near));
+        }
+        if self.far <= self.near {
+            return Err(format!("Far plane ({}) must be beyond the near plane ({})", self.far,
+                self.near));
+        }
+        if self.orbit.distance <= 0.0f32 {
+            return Err(format!("Orbit distance must be greater than 0, got {}",
+                self.orbit.distance));
+        }
+        if self.orbit.pitch.abs() >= 90.0f32 {
+            return Err(format!("Orbit pitch must be between -90 and 90 degrees, got {}",
+                self.orbit.pitch));
+        }
+        if self.headless && ImageFormat::from_path(&self.output).is_none() {
+            return Err(format!("Output {:?} must be a .png or .ppm file", self.output));
+        }
+        if let Some(export) = &self.export {
+            let ext = export.extension().map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+            if !matches!(ext.as_deref(), Some("obj" | "gltf" | "glb")) {
+                return Err(format!("Export {:?} must be an .obj, .gltf or .glb file", export));
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Creates the resolver that finds the model's assets, searching the extra paths given
+    ///
+    fn get_resolver(&self) -> AssetResolver {
+        self.search_paths.iter()
+            .fold(AssetResolver::default(), |resolver, dir| resolver.with_search_root(dir))
+    }
+
+    ///
+    /// Gets how the viewer's updates should be scheduled
+    ///
+    fn get_update_mode(&self) -> UpdateMode {
+        match self.update_rate {
+            Some(hz) => UpdateMode::Fixed { step: 1.0f32 / hz, max_steps: MAX_CATCH_UP_STEPS },
+            None => UpdateMode::default()
+        }
+    }
+}
+
+///
+/// Reports the frame time statistics and writes them to the requested CSV file
+///
+fn report_frame_stats(stats: &FrameStats, path: Option<&PathBuf>) -> Result<(), Error> {
+    if let Some(summary) = stats.summarize() {
+        println!("Frame times over the last {} frames (ms): min {:.2}, avg {:.2}, max {:.2}, \
+            p50 {:.2}, p95 {:.2}, p99 {:.2}", summary.count, summary.min, summary.average,
+            summary.max, summary.p50, summary.p95, summary.p99);
+    }
+    if let Some(path) = path {
+        stats.write_csv(path)?;
+        println!("Wrote {:?}", path);
+    }
+
+    Ok(())
+}
+
+///
+/// Parses an sRGB hex color of the form #rrggbb or #rrggbbaa into a linear color
+///
+fn parse_color(s: &str) -> Option<Color4F> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+        .ok()
+        .map(|c| c as f32 / 255.0f32);
+    let alpha = if hex.len() == 8 { channel(3)? } else { 1.0f32 };
+
+    Some(Color4F::from_rgba(
+        srgb_to_linear(channel(0)?),
+        srgb_to_linear(channel(1)?),
+        srgb_to_linear(channel(2)?),
+        alpha))
+}
+
+///
+/// Renders a fixed number of frames offscreen and writes the last one to an image file
+///
+fn run_headless(opts: &Options) -> Result<(), Error> {
+    let gfx = Graphics::new()?;
+    let rt = gfx.create_offscreen_render_target(opts.width, opts.height)?;
+    let mut viewer = ModelViewer::new(&gfx, rt, opts.width, opts.height, opts)?;
+
+    // headless frames advance by simulated time so the output doesn't depend on render speed
+    let dt = opts.update_rate.map_or(FRAME_TIME, |hz| 1.0f32 / hz);
+    let mut stats = FrameStats::default();
+    for _ in 0..opts.frames {
+        let start = Instant::now();
+        viewer.step(dt);
+        gfx.set_viewport(0.0f32, 0.0f32, opts.width as f32, opts.height as f32, 0.0f32, 1.0f32);
+        viewer.draw(&gfx);
+        stats.record(start.elapsed().as_secs_f32());
+    }
+
+    let pixels = gfx.read_render_target(viewer.get_render_target())?;
+    write_image(&opts.output, opts.width, opts.height, &pixels)?;
+    println!("Wrote {:?}", opts.output);
+
+    if opts.frame_stats.is_some() {
+        report_frame_stats(&stats, opts.frame_stats.as_ref())?;
+    }
+
+    Ok(())
+}
+
+///
+/// Loads and processes the model, then writes it to the export file instead of displaying it
+///
+fn run_export(opts: &Options, export: &Path) -> Result<(), Error> {
+    let mut builder = ModelBuilder::load_with_resolver(&opts.model, &opts.get_resolver())?;
+    for asset in builder.get_unresolved_assets() {
+        eprintln!("Warning: {}", asset);
+    }
+    eprintln!("Optimized mesh: {}", builder.optimize());
+
+    builder.save(export)?;
+    println!("Wrote {:?}", export);
+    Ok(())
+}
+
+///
+/// Loads the model as it is in its file and prints a report on its mesh
+///
+/// # Returns
+/// Whether the mesh has no problems
+///
+fn run_analyze(opts: &Options) -> Result<bool, Error> {
+    let builder = ModelBuilder::load_with_resolver(&opts.model, &opts.get_resolver())?;
+    for asset in builder.get_unresolved_assets() {
+        eprintln!("Warning: {}", asset);
+    }
+
+    let report = builder.analyze();
+    println!("{:?}: {}", opts.model, report);
+    Ok(!report.has_problems())
+}
 
-        self.rt_state.begin(&app.graphics);
-        self.model.draw(&app.graphics);
-        self.rt_state.end(&app.graphics);
+///
+/// Opens the viewer in a window and runs it until the window is closed
+///
+fn run_windowed(opts: &Options) -> Result<(), Error> {
+    // an invisible window would leave the viewer running with no way to close it
+    if !WindowBackendType::platform_default().is_native() {
+        return Err(Error::InvalidArgument("the interactive viewer needs a native window, which \
+            this platform doesn't have; use --headless or --output".to_string()));
     }
+
+    let mut app = app::Application::create("Model Viewer", opts.width, opts.height)?;
+    let rt = app.graphics.create_render_target(&app.display)?;
+    let (width, height) = app.window.get_window_size();
+    let mut sample = ModelViewer::new(&app.graphics, rt, width, height, opts)?;
+    app.timer.set_mode(opts.get_update_mode());
+    app.run(&mut sample)?;
+
+    report_frame_stats(&app.frame_stats, opts.frame_stats.as_ref())
 }
 
 ///
 /// Program entry point for ModelViewer
 ///
-fn main() -> Result<(), i32> {
-    let init_err = |_| {
-        println!("Failed to initialize application");
-        1
+fn main() -> ExitCode {
+    let opts = match Options::parse(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, Options::USAGE);
+            return ExitCode::from(2);
+        }
     };
-    let mut app = app::Application::create("Model Viewer", 1280, 720).map_err(init_err)?;
-    let mut sample = ModelViewer::new(&mut app).map_err(init_err)?;
-    app.run(&mut sample)
+    if opts.help {
+        println!("{}", Options::USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let result = match &opts.export {
+        _ if opts.analyze => run_analyze(&opts),
+        Some(export) => run_export(&opts, export).map(|_| true),
+        None if opts.headless => run_headless(&opts).map(|_| true),
+        None => run_windowed(&opts).map(|_| true)
+    };
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn defaults_match_original_viewer() {
+        let opts = parse(&[]).unwrap();
+        assert_eq!((opts.width, opts.height), (1280, 720));
+        assert_eq!(opts.fov, 45.0f32);
+        let camera = Camera::new_orbit(Point3F::origin(), deg_to_rad(opts.orbit.yaw),
+            deg_to_rad(opts.orbit.pitch), opts.orbit.distance);
+        let pos = camera.get_position().coords;
+        assert!((pos - Vector3F::new(1.0f32, 1.5f32, 5.0f32)).norm() < 1e-4f32);
+        assert!(!opts.headless);
+    }
+
+    #[test]
+    fn parses_camera_and_background() {
+        let opts = parse(&["--fov", "60", "--near", "1", "--far", "10", "--orbit", "90, 0, 2",
+            "--background", "#ff000080"]).unwrap();
+        assert_eq!((opts.fov, opts.near, opts.far), (60.0f32, 1.0f32, 10.0f32));
+        assert_eq!(opts.orbit, Orbit { yaw: 90.0f32, pitch: 0.0f32, distance: 2.0f32 });
+        assert_eq!(opts.background.r, 1.0f32);
+        assert_eq!(opts.background.g, 0.0f32);
+        assert!((opts.background.a - 128.0f32 / 255.0f32).abs() < 1e-6f32);
+    }
+
+    #[test]
+    fn output_implies_headless() {
+        let opts = parse(&["--output", "thumb.ppm", "--frames", "3"]).unwrap();
+        assert!(opts.headless);
+        assert_eq!(opts.frames, 3);
+    }
+
+    #[test]
+    fn fixed_step_selects_update_mode() {
+        assert_eq!(parse(&[]).unwrap().get_update_mode(), UpdateMode::default());
+
+        let opts = parse(&["--fixed-step", "120", "--frame-stats", "frames.csv"]).unwrap();
+        assert_eq!(opts.get_update_mode(),
+            UpdateMode::Fixed { step: 1.0f32 / 120.0f32, max_steps: MAX_CATCH_UP_STEPS });
+        assert_eq!(opts.frame_stats, Some(PathBuf::from("frames.csv")));
+    }
+
+    #[test]
+    fn parses_part_visibility() {
+        let opts = parse(&["--isolate", "engine", "--hide", "bolts", "--hide", "cover"]).unwrap();
+        assert_eq!(opts.isolate.as_deref(), Some("engine"));
+        assert_eq!(opts.hide, vec!["bolts".to_string(), "cover".to_string()]);
+    }
+
+    #[test]
+    fn export_needs_a_format_that_can_be_saved() {
+        assert_eq!(parse(&["--export", "out.GLB"]).unwrap().export, Some(PathBuf::from("out.GLB")));
+        assert!(parse(&["--export", "out.stl"]).is_err());
+        assert!(parse(&["--export"]).is_err());
+    }
+
+    #[test]
+    fn analyze_is_a_subcommand() {
+        let opts = parse(&["analyze", "tests/assets/parts.obj"]).unwrap();
+        assert!(opts.analyze);
+        assert_eq!(opts.model, PathBuf::from("tests/assets/parts.obj"));
+        assert!(!parse(&[]).unwrap().analyze);
+        assert!(parse(&["tests/assets/parts.obj"]).is_err());
+        assert!(parse(&["--headless", "analyze"]).is_err());
+    }
+
+    #[test]
+    fn cache_can_be_moved_or_disabled() {
+        assert_eq!(parse(&[]).unwrap().cache_dir, Some(std::env::temp_dir().join("modelviewer")));
+        assert_eq!(parse(&["--cache-dir", "cache"]).unwrap().cache_dir, Some(PathBuf::from("cache")));
+        assert_eq!(parse(&["--no-cache"]).unwrap().cache_dir, None);
+
+        // models with the same name in different directories get their own caches
+        let dir = Path::new("cache");
+        assert_ne!(cache_file_name(dir, Path::new("a/model.obj"), &[]),
+            cache_file_name(dir, Path::new("b/model.obj"), &[]));
+        assert_ne!(cache_file_name(dir, Path::new("a/model.obj"), &[]),
+            cache_file_name(dir, Path::new("a/model.obj"), &[LodTarget::Ratio(0.5f32)]));
+    }
+
+    #[test]
+    fn parses_levels_of_detail() {
+        assert!(parse(&[]).unwrap().lods.is_empty());
+        assert_eq!(parse(&["--lods", "0.5, error:0.01"]).unwrap().lods,
+            vec![LodTarget::Ratio(0.5f32), LodTarget::Error(0.01f32)]);
+        assert!(parse(&["--lods", "0.5,2"]).is_err());
+        assert!(parse(&["--lods"]).is_err());
+    }
+
+    #[test]
+    fn help_skips_validation() {
+        assert!(parse(&["--model", "missing.obj", "--help"]).unwrap().help);
+    }
+
+    #[test]
+    fn input_drives_camera() {
+        use modelviewer::app::InputEvent;
+
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let rt = gfx.create_offscreen_render_target(64, 64).unwrap();
+        // loading from the file keeps the test from sharing a cache in the system's temp directory
+        let opts = parse(&["--no-cache"]).unwrap();
+        let mut viewer = ModelViewer::new(&gfx, rt, 64, 64, &opts).unwrap();
+        let yaw = viewer.camera.get_yaw();
+        let distance = viewer.camera.get_distance();
+
+        let mut input = InputState::new();
+        let events = [
+            InputEvent::MouseMove(Vector2F::new(10.0f32, 10.0f32)),
+            InputEvent::MouseDown(MouseButton::Left),
+            InputEvent::MouseMove(Vector2F::new(30.0f32, 10.0f32)),
+            InputEvent::MouseWheel(1.0f32),
+            InputEvent::KeyDown(Key::Space)
+        ];
+        for event in events.iter() {
+            input.handle_event(event);
+        }
+        viewer.handle_input(&input, 64.0f32, FRAME_TIME);
+        assert!(viewer.camera.get_yaw() < yaw);
+        assert!(viewer.camera.get_distance() < distance);
+        assert!(!viewer.spin);
+
+        input.begin_frame();
+        input.handle_event(&InputEvent::KeyDown(Key::F));
+        viewer.handle_input(&input, 64.0f32, FRAME_TIME);
+        assert_eq!(viewer.camera.get_mode(), CameraMode::Fly);
+    }
+
+    #[test]
+    fn framing_keeps_large_models_in_view() {
+        let corners = [Point3F::new(1000.0f32, -500.0f32, -500.0f32),
+            Point3F::new(2000.0f32, 500.0f32, 500.0f32)];
+        let bounds = Bounds::from_points(&corners).unwrap();
+        let mut camera = Camera::new_orbit(Point3F::origin(), 0.0f32, 0.0f32, 5.0f32);
+        frame_bounds(&mut camera, &bounds);
+
+        assert_eq!(camera.get_target(), bounds.sphere.center);
+        let view_proj = camera.get_view_projection_matrix();
+        let depth = |p: Point3F| {
+            let clip = view_proj * p.to_homogeneous();
+            clip.z / clip.w
+        };
+        let forward = camera.get_forward() * bounds.sphere.radius;
+        for p in [bounds.sphere.center - forward, bounds.sphere.center + forward].iter() {
+            assert!(depth(*p).abs() < 1.0f32, "{:?} is clipped", p);
+        }
+    }
+
+    #[test]
+    fn model_spins_about_its_center() {
+        let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+        let rt = gfx.create_offscreen_render_target(64, 64).unwrap();
+        let opts = parse(&["--no-cache"]).unwrap();
+        let mut viewer = ModelViewer::new(&gfx, rt, 64, 64, &opts).unwrap();
+        viewer.step(1.0f32);
+
+        let center = viewer.model.get_bounds().unwrap().sphere.center;
+        let moved = viewer.get_world_matrix() * center.to_homogeneous();
+        assert!((moved.xyz() - center.coords).norm() < 1e-4f32);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(parse(&["--width", "0"]).is_err());
+        assert!(parse(&["--fov", "180"]).is_err());
+        assert!(parse(&["--near", "5", "--far", "1"]).is_err());
+        assert!(parse(&["--orbit", "1,2"]).is_err());
+        assert!(parse(&["--orbit", "0,90,1"]).is_err());
+        assert!(parse(&["--background", "red"]).is_err());
+        assert!(parse(&["--model", "missing.obj"]).is_err());
+        assert!(parse(&["--shader", "missing.hlsl"]).is_err());
+        assert!(parse(&["--search-path", "missing"]).is_err());
+        assert!(parse(&["--output", "image.bmp"]).is_err());
+        assert!(parse(&["--frames"]).is_err());
+        assert!(parse(&["--fixed-step", "0"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
 }
@@ -1,5 +1,7 @@
+mod bounds;
 mod color;
 
+pub use self::bounds::*;
 pub use self::color::*;
 
 // use na to generate our typical numeric types
@@ -8,6 +10,7 @@ pub use self::na::*;
 
 pub type Vector2F = na::Vector2<f32>;
 pub type Vector3F = na::Vector3<f32>;
+pub type Vector4F = na::Vector4<f32>;
 pub type Point2F = na::Point3<f32>;
 pub type Point3F = na::Point3<f32>;
 pub type Translation2F = na::Translation3<f32>;
@@ -0,0 +1,376 @@
+// local refs
+use super::*;
+
+///
+/// Axis-aligned bounding box
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Point3F,
+    pub max: Point3F
+}
+
+impl Aabb {
+    ///
+    /// Creates a box from its minimum and maximum corners
+    ///
+    pub fn new(min: Point3F, max: Point3F) -> Self {
+        Self { min, max }
+    }
+
+    ///
+    /// Creates the smallest box enclosing the points, or `None` if there aren't any
+    ///
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a Point3F>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| Self {
+            min: Point3F::from(aabb.min.coords.zip_map(&p.coords, f32::min)),
+            max: Point3F::from(aabb.max.coords.zip_map(&p.coords, f32::max))
+        }))
+    }
+
+    ///
+    /// Gets the point in the middle of the box
+    ///
+    pub fn get_center(&self) -> Point3F {
+        Point3F::from((self.min.coords + self.max.coords) * 0.5f32)
+    }
+
+    ///
+    /// Gets half the size of the box along each axis
+    ///
+    pub fn get_half_extents(&self) -> Vector3F {
+        (self.max - self.min) * 0.5f32
+    }
+
+    ///
+    /// Gets the smallest box enclosing both boxes
+    ///
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Self {
+            min: Point3F::from(self.min.coords.zip_map(&other.min.coords, f32::min)),
+            max: Point3F::from(self.max.coords.zip_map(&other.max.coords, f32::max))
+        }
+    }
+
+    ///
+    /// Checks whether a point is inside the box or on its surface
+    ///
+    pub fn contains(&self, p: &Point3F) -> bool {
+        (0..3).all(|axis| self.min[axis] <= p[axis] && p[axis] <= self.max[axis])
+    }
+
+    ///
+    /// Gets the box transformed by an affine matrix, which is no longer axis-aligned
+    ///
+    pub fn to_obb(&self, transform: &Matrix4F) -> Obb {
+        let linear = transform.fixed_slice::<U3, U3>(0, 0);
+        Obb {
+            center: transform.transform_point(&self.get_center()),
+            axes: linear * Matrix3F::from_diagonal(&self.get_half_extents())
+        }
+    }
+
+    ///
+    /// Gets the smallest axis-aligned box enclosing this box transformed by an affine matrix
+    ///
+    pub fn transform(&self, transform: &Matrix4F) -> Aabb {
+        self.to_obb(transform).get_aabb()
+    }
+}
+
+///
+/// Bounding sphere
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3F,
+    pub radius: f32
+}
+
+impl BoundingSphere {
+    ///
+    /// Creates a sphere enclosing the points, or `None` if there aren't any. The sphere is found
+    /// with Ritter's method, falling back to the one around the points' box when that's smaller,
+    /// so it's close to but not always the smallest possible.
+    ///
+    pub fn from_points(points: &[Point3F]) -> Option<Self> {
+        let farthest = |from: &Point3F| points.iter()
+            .max_by(|a, b| distance_squared(from, a).total_cmp(&distance_squared(from, b)))
+            .copied();
+
+        // start from the two points farthest apart along one direction, then grow to fit the rest
+        let a = farthest(points.first()?)?;
+        let b = farthest(&a)?;
+        let mut ritter = Self { center: center(&a, &b), radius: distance(&a, &b) * 0.5f32 };
+        for p in points {
+            let dist = distance(&ritter.center, p);
+            if dist > ritter.radius {
+                let radius = (ritter.radius + dist) * 0.5f32;
+                ritter.center += (p - ritter.center) * ((radius - ritter.radius) / dist);
+                ritter.radius = radius;
+            }
+        }
+
+        let box_center = Aabb::from_points(points)?.get_center();
+        let box_radius = points.iter().map(|p| distance(&box_center, p)).fold(0.0f32, f32::max);
+        Some(if box_radius < ritter.radius {
+            Self { center: box_center, radius: box_radius }
+        } else {
+            ritter
+        })
+    }
+
+    ///
+    /// Gets a sphere enclosing both spheres
+    ///
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.center - self.center;
+        let dist = offset.norm();
+        if dist + other.radius <= self.radius {
+            return *self;
+        }
+        if dist + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (dist + self.radius + other.radius) * 0.5f32;
+        Self { center: self.center + offset * ((radius - self.radius) / dist), radius }
+    }
+
+    ///
+    /// Checks whether a point is inside the sphere or on its surface
+    ///
+    pub fn contains(&self, p: &Point3F) -> bool {
+        distance_squared(&self.center, p) <= self.radius * self.radius
+    }
+
+    ///
+    /// Gets the sphere transformed by an affine matrix. Non-uniform scales stretch the sphere into
+    /// an ellipsoid, so the radius is scaled by the largest of them to keep enclosing it.
+    ///
+    pub fn transform(&self, transform: &Matrix4F) -> BoundingSphere {
+        let linear = transform.fixed_slice::<U3, U3>(0, 0);
+        let scale = (0..3).map(|axis| linear.column(axis).norm()).fold(0.0f32, f32::max);
+        Self { center: transform.transform_point(&self.center), radius: self.radius * scale }
+    }
+}
+
+///
+/// Oriented bounding box
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Obb {
+    pub center: Point3F,
+    /// The box's axes as columns, each scaled by half the size of the box along it
+    pub axes: Matrix3F
+}
+
+impl Obb {
+    ///
+    /// Gets the eight corners of the box
+    ///
+    pub fn get_corners(&self) -> [Point3F; 8] {
+        let mut corners = [self.center; 8];
+        for (idx, corner) in corners.iter_mut().enumerate() {
+            for axis in 0..3 {
+                let sign = if idx & (1 << axis) == 0 { -1.0f32 } else { 1.0f32 };
+                *corner += self.axes.column(axis) * sign;
+            }
+        }
+        corners
+    }
+
+    ///
+    /// Gets the smallest axis-aligned box enclosing this box
+    ///
+    pub fn get_aabb(&self) -> Aabb {
+        let half = self.axes.abs() * Vector3F::repeat(1.0f32);
+        Aabb::new(self.center - half, self.center + half)
+    }
+}
+
+///
+/// An axis-aligned box and a sphere enclosing the same geometry. Tests against the sphere are
+/// cheaper, while the box usually fits more tightly.
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere
+}
+
+impl Bounds {
+    ///
+    /// Creates bounds enclosing the points, or `None` if there aren't any
+    ///
+    pub fn from_points(points: &[Point3F]) -> Option<Self> {
+        Some(Self {
+            aabb: Aabb::from_points(points)?,
+            sphere: BoundingSphere::from_points(points)?
+        })
+    }
+
+    ///
+    /// Gets bounds enclosing both bounds
+    ///
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Self { aabb: self.aabb.union(&other.aabb), sphere: self.sphere.union(&other.sphere) }
+    }
+
+    ///
+    /// Gets the bounds transformed by an affine matrix
+    ///
+    pub fn transform(&self, transform: &Matrix4F) -> Bounds {
+        Self { aabb: self.aabb.transform(transform), sphere: self.sphere.transform(transform) }
+    }
+}
+
+///
+/// The volume a camera can see, as six planes facing inward
+///
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frustum {
+    /// Normal in xyz and offset in w, so points inside have a positive distance
+    planes: [Vector4F; 6]
+}
+
+impl Frustum {
+    ///
+    /// Extracts the planes from a view projection matrix. Depth is clipped to -w..w, which also
+    /// covers projections that clip it to 0..w.
+    ///
+    pub fn from_matrix(view_proj: &Matrix4F) -> Self {
+        let row = |idx: usize| view_proj.row(idx).transpose();
+        let planes = [row(3) + row(0), row(3) - row(0), row(3) + row(1), row(3) - row(1),
+            row(3) + row(2), row(3) - row(2)];
+        Self { planes: planes.map(|plane| plane / plane.xyz().norm()) }
+    }
+
+    ///
+    /// Checks whether any part of the sphere may be visible
+    ///
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| distance_to(plane, &sphere.center) >= -sphere.radius)
+    }
+
+    ///
+    /// Checks whether any part of the box may be visible
+    ///
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.intersects_obb(&aabb.to_obb(&Matrix4F::identity()))
+    }
+
+    ///
+    /// Checks whether any part of the box may be visible. Boxes near the frustum's edges can pass
+    /// without being visible, but visible boxes always pass.
+    ///
+    pub fn intersects_obb(&self, obb: &Obb) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let extent: f32 = (0..3).map(|axis| normal.dot(&obb.axes.column(axis)).abs()).sum();
+            distance_to(plane, &obb.center) >= -extent
+        })
+    }
+
+    ///
+    /// Checks whether any part of the bounds may be visible once transformed by an affine matrix,
+    /// testing the sphere first and then the box
+    ///
+    pub fn intersects_bounds(&self, bounds: &Bounds, transform: &Matrix4F) -> bool {
+        self.intersects_sphere(&bounds.sphere.transform(transform))
+            && self.intersects_obb(&bounds.aabb.to_obb(transform))
+    }
+}
+
+///
+/// Gets the signed distance from a frustum plane to a point
+///
+fn distance_to(plane: &Vector4F, p: &Point3F) -> f32 {
+    plane.xyz().dot(&p.coords) + plane.w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube_corners() -> Vec<Point3F> {
+        (0..8).map(|idx| Point3F::new((idx & 1) as f32, (idx >> 1 & 1) as f32,
+            (idx >> 2 & 1) as f32)).collect()
+    }
+
+    #[test]
+    fn encloses_a_unit_cube() {
+        let corners = cube_corners();
+        let aabb = Aabb::from_points(&corners).unwrap();
+        assert_eq!(aabb, Aabb::new(Point3F::origin(), Point3F::new(1.0f32, 1.0f32, 1.0f32)));
+        assert_eq!(aabb.get_center(), Point3F::new(0.5f32, 0.5f32, 0.5f32));
+
+        let sphere = BoundingSphere::from_points(&corners).unwrap();
+        assert!((sphere.radius - 3.0f32.sqrt() * 0.5f32).abs() < 1e-5f32);
+        assert!((sphere.center - aabb.get_center()).norm() < 1e-5f32);
+        assert!(Aabb::from_points(&[]).is_none());
+        assert!(BoundingSphere::from_points(&[]).is_none());
+    }
+
+    #[test]
+    fn sphere_fits_a_thin_triangle() {
+        // a long thin triangle, whose box corner is far from every point
+        let points = [Point3F::origin(), Point3F::new(10.0f32, 0.0f32, 0.0f32),
+            Point3F::new(0.0f32, 1.0f32, 0.0f32)];
+        let sphere = BoundingSphere::from_points(&points).unwrap();
+        assert!(points.iter().all(|p| distance(&sphere.center, p) <= sphere.radius + 1e-5f32));
+        assert!(sphere.radius < (10.0f32 * 10.0f32 + 1.0f32).sqrt() * 0.5f32 + 1e-5f32);
+    }
+
+    #[test]
+    fn transforms_enclose_the_transformed_points() {
+        let corners = cube_corners();
+        let bounds = Bounds::from_points(&corners).unwrap();
+        let transform = Matrix4F::new_translation(&Vector3F::new(2.0f32, 0.0f32, 0.0f32))
+            * Matrix4F::from_axis_angle(&Vector3F::y_axis(), deg_to_rad(45.0f32))
+            * Matrix4F::new_nonuniform_scaling(&Vector3F::new(1.0f32, 3.0f32, 1.0f32));
+        let moved: Vec<Point3F> = corners.iter().map(|p| transform.transform_point(p)).collect();
+
+        let aabb = bounds.transform(&transform).aabb;
+        let tight = Aabb::from_points(&moved).unwrap();
+        assert!((aabb.min - tight.min).norm() < 1e-5f32 && (aabb.max - tight.max).norm() < 1e-5f32);
+
+        let obb = bounds.aabb.to_obb(&transform);
+        for (corner, expected) in obb.get_corners().iter().zip(&moved) {
+            assert!((corner - expected).norm() < 1e-5f32);
+        }
+
+        let sphere = bounds.sphere.transform(&transform);
+        assert!((sphere.radius - bounds.sphere.radius * 3.0f32).abs() < 1e-5f32);
+        assert!(moved.iter().all(|p| distance(&sphere.center, p) <= sphere.radius + 1e-5f32));
+    }
+
+    #[test]
+    fn union_encloses_both() {
+        let a = BoundingSphere { center: Point3F::origin(), radius: 1.0f32 };
+        let b = BoundingSphere { center: Point3F::new(4.0f32, 0.0f32, 0.0f32), radius: 1.0f32 };
+        let both = a.union(&b);
+        assert_eq!(both.center, Point3F::new(2.0f32, 0.0f32, 0.0f32));
+        assert_eq!(both.radius, 3.0f32);
+        assert_eq!(both.union(&a), both);
+    }
+
+    #[test]
+    fn frustum_culls_what_the_camera_cant_see() {
+        let view = Matrix4F::look_at_rh(&Point3F::new(0.0f32, 0.0f32, 5.0f32), &Point3F::origin(),
+            &Vector3F::y());
+        let proj = Matrix4F::new_perspective(1.0f32, deg_to_rad(45.0f32), 0.1f32, 100.0f32);
+        let frustum = Frustum::from_matrix(&(proj * view));
+        let bounds = Bounds::from_points(&cube_corners()).unwrap();
+        let at = |x: f32, z: f32| Matrix4F::new_translation(&Vector3F::new(x, 0.0f32, z));
+
+        assert!(frustum.intersects_bounds(&bounds, &Matrix4F::identity()));
+        // behind the camera, beyond the far plane and off to the side
+        assert!(!frustum.intersects_bounds(&bounds, &at(0.0f32, 10.0f32)));
+        assert!(!frustum.intersects_bounds(&bounds, &at(0.0f32, -200.0f32)));
+        assert!(!frustum.intersects_bounds(&bounds, &at(20.0f32, 0.0f32)));
+        // partly inside the left edge
+        assert!(frustum.intersects_aabb(&bounds.transform(&at(-2.5f32, 0.0f32)).aabb));
+    }
+}
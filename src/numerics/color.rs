@@ -13,6 +13,7 @@ impl ColorScalar for u8 {
     const BLACK: u8 = 0;
 }
 
+#[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Color3T<T> {
     pub r: T,
@@ -48,6 +49,7 @@ impl<T: ColorScalar> Color3T<T> {
     }
 }
 
+#[repr(C)]
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Color4T<T> {
     pub r: T,
@@ -113,6 +115,20 @@ impl<T: ColorScalar + Copy> Color4T<T> {
     }
 }
 
+///
+/// Converts an sRGB-encoded channel value to linear
+///
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045f32 { c / 12.92f32 } else { ((c + 0.055f32) / 1.055f32).powf(2.4f32) }
+}
+
+///
+/// Converts a linear channel value to sRGB encoding
+///
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8_f32 { c * 12.92f32 } else { 1.055f32 * c.powf(1.0f32 / 2.4f32) - 0.055f32 }
+}
+
 pub type Color3 = Color3T<u8>;
 pub type Color4 = Color4T<u8>;
 
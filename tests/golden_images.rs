@@ -0,0 +1,149 @@
+//!
+//! Renders every model in data/objects with the software backend and compares the result against
+//! the reference images in tests/golden.
+//!
+//! Set `UPDATE_GOLDEN_IMAGES=1` to regenerate the references after an intentional change. Failed
+//! comparisons write the rendered and diff images to target/golden-diffs.
+//!
+
+// external refs
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// local refs
+use modelviewer::gfx::*;
+use modelviewer::numerics::*;
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 128;
+
+///
+/// Constant buffer layout expected by data/shaders/object.hlsl
+///
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Constants {
+    world: Matrix4F,
+    view_proj: Matrix4F,
+    camera_pos: Vector3F,
+    specular_amount : f32,
+    ambient_light: Color4F,
+    sun_color: Color4F,
+    sun_dir: Vector3F
+}
+
+unsafe impl Pod for Constants {}
+
+///
+/// Renders a model with the fixed reference camera and lighting
+///
+fn render_model(path: &Path) -> Vec<Color4> {
+    let gfx = Graphics::new_with_backend(BackendType::Software).unwrap();
+    let rt = gfx.create_offscreen_render_target(WIDTH, HEIGHT).unwrap();
+    let ds = gfx.create_depth_stencil_target(DepthStencilFormat::D32Float, WIDTH, HEIGHT).unwrap();
+    let mut rt_state = RenderTargetState::new(rt, Some(ds));
+    rt_state.enable_clear_color(Color4F::black());
+    rt_state.enable_clear_depth(1.0f32);
+
+    let model = ModelBuilder::load_from_obj(path).unwrap().build(&gfx).unwrap();
+
+    let cam_pos = Vector3F::new(1.0f32, 1.5f32, 5.0f32);
+    let view = Matrix4F::look_at_rh(&Point3F::from(cam_pos), &Point3F::origin(), &Vector3F::y());
+    let proj = Matrix4F::new_perspective(WIDTH as f32 / HEIGHT as f32, deg_to_rad(45.0f32),
+        0.1f32, 100.0f32);
+    let constants = Constants {
+        world: Matrix4F::identity(),
+        view_proj: proj * view,
+        camera_pos: cam_pos,
+        specular_amount: 0.5f32,
+        ambient_light: Color4F::from_rgba(0.0f32, 0.1f32, 0.2f32, 1.0f32),
+        sun_color: Color4F::from_rgba(1.0f32, 1.0f32, 0.75f32, 1.0f32),
+        sun_dir: Vector3F::new(1.0f32, 1.0f32, 1.0f32)
+    };
+    let cbuff = gfx.create_constant_buffer(std::mem::size_of::<Constants>()).unwrap();
+    gfx.map_and_set_buffer_data(&cbuff, &constants);
+
+    let sampler = gfx.create_sampler(SamplerData {
+        mode: SampleMode::Linear,
+        address_u: AddressMode::Wrap,
+        address_v: AddressMode::Wrap,
+        address_w: AddressMode::Wrap,
+        comparison: ComparisonFunc::Never,
+        lod_bias: 0.0f32,
+        max_anisotropy: 1,
+        border_color: BorderColor::OpaqueWhite,
+        min_lod: -f32::MAX,
+        max_lod: f32::MAX
+    }).unwrap();
+
+    gfx.set_viewport(0.0f32, 0.0f32, WIDTH as f32, HEIGHT as f32, 0.0f32, 1.0f32);
+    gfx.set_vs_constant_buffer(0, &cbuff);
+    gfx.set_ps_constant_buffer(0, &cbuff);
+    gfx.set_ps_sampler(0, &sampler);
+
+    rt_state.begin(&gfx);
+    model.draw(&gfx);
+    rt_state.end(&gfx);
+
+    gfx.read_render_target(rt_state.get_render_target()).unwrap()
+}
+
+///
+/// Compares a rendered image against its reference
+///
+/// # Returns
+/// A description of the failure if the images don't match
+///
+fn check_against_reference(name: &str, pixels: &[Color4]) -> Result<(), String> {
+    let golden_path = Path::new("tests").join("golden").join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN_IMAGES").is_some() {
+        write_image(&golden_path, WIDTH, HEIGHT, pixels).unwrap();
+        return Ok(());
+    }
+
+    let (width, height, expected) = read_image(&golden_path).map_err(|_| {
+        format!("{}: missing reference {:?}, run with UPDATE_GOLDEN_IMAGES=1 to create it",
+            name, golden_path)
+    })?;
+    if (width, height) != (WIDTH, HEIGHT) {
+        return Err(format!("{}: reference is {}x{} but rendered {}x{}", name, width, height,
+            WIDTH, HEIGHT));
+    }
+
+    let tolerance = ImageTolerance::default();
+    let result = compare_images(&expected, pixels, &tolerance);
+    if result.passes(&tolerance) {
+        return Ok(());
+    }
+
+    let out_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("golden-diffs");
+    fs::create_dir_all(&out_dir).unwrap();
+    let actual_path = out_dir.join(format!("{}.actual.png", name));
+    let diff_path = out_dir.join(format!("{}.diff.png", name));
+    write_image(&actual_path, WIDTH, HEIGHT, pixels).unwrap();
+    write_image(&diff_path, WIDTH, HEIGHT, &create_diff_image(&expected, pixels, &tolerance))
+        .unwrap();
+
+    Err(format!("{}: {} of {} pixels differ (max channel error {}, mean dE {:.3}, max dE {:.3}), \
+        see {:?}", name, result.failed_pixels, result.num_pixels, result.max_channel_error,
+        result.mean_delta_e, result.max_delta_e, diff_path))
+}
+
+#[test]
+fn models_match_golden_images() {
+    let mut models: Vec<PathBuf> = fs::read_dir(Path::new("data").join("objects")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "obj"))
+        .collect();
+    models.sort();
+    assert!(!models.is_empty(), "No models found in data/objects");
+
+    let failures: Vec<String> = models.iter()
+        .filter_map(|path| {
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            check_against_reference(name, &render_model(path)).err()
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "Golden image mismatches:\n{}", failures.join("\n"));
+}